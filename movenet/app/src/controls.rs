@@ -0,0 +1,341 @@
+//! Camera controls such as exposure, gain, white balance and focus, as
+//! `VideoCapture` enumerates them, and profiles of their values kept in files.
+
+use std::{
+    fs,
+    io::{self, Error},
+    path::Path,
+};
+
+/// IDs of the controls webcams commonly offer, from `linux/v4l2-controls.h`.
+pub mod cid {
+    const USER_BASE: u32 = 0x0098_0900;
+    const CAMERA_CLASS_BASE: u32 = 0x009a_0900;
+
+    pub const BRIGHTNESS: u32 = USER_BASE;
+    pub const CONTRAST: u32 = USER_BASE + 1;
+    pub const SATURATION: u32 = USER_BASE + 2;
+    pub const HUE: u32 = USER_BASE + 3;
+    pub const AUTO_WHITE_BALANCE: u32 = USER_BASE + 12;
+    pub const GAMMA: u32 = USER_BASE + 16;
+    pub const GAIN: u32 = USER_BASE + 19;
+    pub const POWER_LINE_FREQUENCY: u32 = USER_BASE + 24;
+    pub const WHITE_BALANCE_TEMPERATURE: u32 = USER_BASE + 26;
+    pub const SHARPNESS: u32 = USER_BASE + 27;
+    pub const BACKLIGHT_COMPENSATION: u32 = USER_BASE + 28;
+    /// Menu of `ExposureMode`s.
+    pub const EXPOSURE_AUTO: u32 = CAMERA_CLASS_BASE + 1;
+    /// In units of 100 µs.
+    pub const EXPOSURE_ABSOLUTE: u32 = CAMERA_CLASS_BASE + 2;
+    pub const EXPOSURE_AUTO_PRIORITY: u32 = CAMERA_CLASS_BASE + 3;
+    pub const FOCUS_ABSOLUTE: u32 = CAMERA_CLASS_BASE + 10;
+    pub const FOCUS_AUTO: u32 = CAMERA_CLASS_BASE + 12;
+}
+
+pub(crate) const V4L2_CTRL_FLAG_DISABLED: u32 = 0x0001;
+pub(crate) const V4L2_CTRL_FLAG_READ_ONLY: u32 = 0x0004;
+pub(crate) const V4L2_CTRL_FLAG_INACTIVE: u32 = 0x0010;
+pub(crate) const V4L2_CTRL_FLAG_WRITE_ONLY: u32 = 0x0040;
+
+/// What kind of value a control holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlType {
+    Integer,
+    Boolean,
+    /// One of the named `ControlInfo::menu` items.
+    Menu,
+    /// Triggers an action when set, holds no value.
+    Button,
+    Integer64,
+    String,
+    Bitmask,
+    /// One of the numbered `ControlInfo::menu` items.
+    IntegerMenu,
+    /// A type this module does not know, by its `V4L2_CTRL_TYPE_*` code.
+    Other(u32),
+}
+
+impl ControlType {
+    pub(crate) fn from_v4l2(code: u32) -> Self {
+        match code {
+            1 => ControlType::Integer,
+            2 => ControlType::Boolean,
+            3 => ControlType::Menu,
+            4 => ControlType::Button,
+            5 => ControlType::Integer64,
+            7 => ControlType::String,
+            8 => ControlType::Bitmask,
+            9 => ControlType::IntegerMenu,
+            code => ControlType::Other(code),
+        }
+    }
+
+    /// Whether the value fits the 32 bits of `VIDIOC_G_CTRL`/`VIDIOC_S_CTRL`.
+    pub fn is_plain(&self) -> bool {
+        matches!(
+            self,
+            ControlType::Integer | ControlType::Boolean | ControlType::Menu
+                | ControlType::Bitmask | ControlType::IntegerMenu
+        )
+    }
+}
+
+/// An entry of a menu control.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuItem {
+    /// The value selecting this item.
+    pub index: u32,
+    /// Empty for integer menus.
+    pub name: String,
+    /// The number of an integer menu item, zero for named ones.
+    pub value: i64,
+}
+
+/// A control, as reported by `VIDIOC_QUERYCTRL`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlInfo {
+    pub id: u32,
+    pub name: String,
+    pub kind: ControlType,
+    pub minimum: i32,
+    pub maximum: i32,
+    pub step: i32,
+    pub default: i32,
+    /// `V4L2_CTRL_FLAG_*` flags.
+    pub flags: u32,
+    /// Items of menu controls, left out where the driver skips an index.
+    pub menu: Vec<MenuItem>,
+}
+
+impl ControlInfo {
+    pub fn is_disabled(&self) -> bool {
+        self.flags & V4L2_CTRL_FLAG_DISABLED != 0
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.flags & V4L2_CTRL_FLAG_READ_ONLY != 0
+    }
+
+    pub fn is_write_only(&self) -> bool {
+        self.flags & V4L2_CTRL_FLAG_WRITE_ONLY != 0
+    }
+
+    /// Inactive controls are overridden by another one, as manual exposure is
+    /// by auto exposure. Setting them fails or has no effect.
+    pub fn is_inactive(&self) -> bool {
+        self.flags & V4L2_CTRL_FLAG_INACTIVE != 0
+    }
+
+    /// Whether the name matches `name`, ignoring case, spaces and underscores,
+    /// so that "white_balance_temperature" finds "White Balance Temperature".
+    pub fn matches(&self, name: &str) -> bool {
+        let normalize = |s: &str| {
+            s.chars()
+                .filter(|c| !c.is_whitespace() && *c != '_')
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        };
+        normalize(&self.name) == normalize(name)
+    }
+}
+
+/// Values of `cid::EXPOSURE_AUTO`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExposureMode {
+    Auto = 0,
+    Manual = 1,
+    ShutterPriority = 2,
+    AperturePriority = 3,
+}
+
+impl ExposureMode {
+    pub fn from_value(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(ExposureMode::Auto),
+            1 => Some(ExposureMode::Manual),
+            2 => Some(ExposureMode::ShutterPriority),
+            3 => Some(ExposureMode::AperturePriority),
+            _ => None,
+        }
+    }
+}
+
+/// The value of one control in a `ControlProfile`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedControl {
+    pub id: u32,
+    pub value: i32,
+    /// Only kept to annotate the file.
+    pub name: String,
+}
+
+/// Saved control values, restored in the order they were saved.
+///
+/// Kept in files as one `<id> <value>` line per control, the id in hex, with
+/// the control name after a `#` for whoever reads it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControlProfile {
+    pub controls: Vec<SavedControl>,
+}
+
+impl ControlProfile {
+    pub fn get(&self, id: u32) -> Option<i32> {
+        self.controls.iter().find(|saved| saved.id == id).map(|saved| saved.value)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut controls = Vec::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let (entry, name) = line.split_once('#').unwrap_or((line, ""));
+            if entry.trim().is_empty() {
+                continue;
+            }
+            let mut fields = entry.split_whitespace();
+            let id = fields.next().and_then(|id| {
+                u32::from_str_radix(id.trim_start_matches("0x"), 16).ok()
+            });
+            let value = fields.next().and_then(|value| value.parse().ok());
+            let (Some(id), Some(value)) = (id, value) else {
+                return Err(Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: expected <id> <value>", number + 1)
+                ));
+            };
+            controls.push(SavedControl { id, value, name: name.trim().to_string() });
+        }
+        Ok(Self { controls })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = self.controls.iter()
+            .map(|saved| format!("{:#010x} {} # {}\n", saved.id, saved.value, saved.name))
+            .collect::<String>();
+        fs::write(path, text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::path::PathBuf;
+
+    // A file of its own for each test, removed again when dropped.
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(env::temp_dir().join(format!("{}-{}.ctrl", name, std::process::id())))
+        }
+
+        fn with(name: &str, text: &str) -> Self {
+            let file = Self::new(name);
+            fs::write(&file.0, text).unwrap();
+            file
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn saved(id: u32, value: i32, name: &str) -> SavedControl {
+        SavedControl { id, value, name: name.to_string() }
+    }
+
+    #[test]
+    fn profiles_round_trip() {
+        let profile = ControlProfile {
+            controls: vec![
+                saved(cid::EXPOSURE_AUTO, ExposureMode::Manual as i32, "Auto Exposure"),
+                saved(cid::EXPOSURE_ABSOLUTE, 156, "Exposure Time, Absolute"),
+                saved(cid::BRIGHTNESS, -64, "Brightness"),
+                saved(cid::GAIN, 0, ""),
+            ],
+        };
+        let file = TempFile::new("round-trip");
+        profile.save(&file.0).unwrap();
+        let text = fs::read_to_string(&file.0).unwrap();
+        assert!(text.starts_with("0x009a0901 1 # Auto Exposure\n"), "{}", text);
+        assert_eq!(ControlProfile::load(&file.0).unwrap(), profile);
+        assert_eq!(profile.get(cid::BRIGHTNESS), Some(-64));
+        assert_eq!(profile.get(cid::FOCUS_AUTO), None);
+    }
+
+    #[test]
+    fn profiles_skip_comments_and_blank_lines() {
+        let file = TempFile::with("comments", concat!(
+            "# saved by hand\n",
+            "\n",
+            "   \n",
+            "980900 12\n",
+            "  0x00980913\t-3   # Gain  \n",
+        ));
+        let profile = ControlProfile::load(&file.0).unwrap();
+        assert_eq!(profile.controls, vec![
+            saved(cid::BRIGHTNESS, 12, ""),
+            saved(cid::GAIN, -3, "Gain"),
+        ]);
+    }
+
+    #[test]
+    fn profiles_name_the_malformed_line() {
+        for (text, line) in [
+            ("0x00980900 1\nbrightness 1\n", 2),
+            ("# only a comment\n0x00980900\n", 2),
+            ("0x00980900 high\n", 1),
+            ("0x00980900 99999999999\n", 1),
+            ("\n\n# value\n0xzz 1 # Brightness\n", 4),
+        ] {
+            let file = TempFile::with("malformed", text);
+            let err = ControlProfile::load(&file.0).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+            assert_eq!(err.to_string(), format!("line {}: expected <id> <value>", line));
+        }
+        let missing = TempFile::new("missing");
+        assert_eq!(ControlProfile::load(&missing.0).unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn names_match_loosely() {
+        let info = ControlInfo {
+            id: cid::WHITE_BALANCE_TEMPERATURE,
+            name: "White Balance Temperature".to_string(),
+            kind: ControlType::Integer,
+            minimum: 2800,
+            maximum: 6500,
+            step: 1,
+            default: 4600,
+            flags: V4L2_CTRL_FLAG_INACTIVE,
+            menu: Vec::new(),
+        };
+        for name in [
+            "White Balance Temperature",
+            "white_balance_temperature",
+            "WHITEBALANCE temp erature",
+        ] {
+            assert!(info.matches(name), "{}", name);
+        }
+        for name in ["white balance", "white-balance-temperature", ""] {
+            assert!(!info.matches(name), "{}", name);
+        }
+        assert!(info.is_inactive() && !info.is_disabled() && !info.is_read_only());
+    }
+
+    #[test]
+    fn exposure_modes_by_value() {
+        for mode in [
+            ExposureMode::Auto,
+            ExposureMode::Manual,
+            ExposureMode::ShutterPriority,
+            ExposureMode::AperturePriority,
+        ] {
+            assert_eq!(ExposureMode::from_value(mode as i32), Some(mode));
+        }
+        assert_eq!(ExposureMode::from_value(4), None);
+        assert_eq!(ExposureMode::from_value(-1), None);
+    }
+}
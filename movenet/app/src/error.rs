@@ -0,0 +1,104 @@
+//! What can go wrong talking to a capture device.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use nix::errno::Errno;
+
+#[derive(Debug)]
+pub enum CaptureError {
+    /// The video device, or the camdriver module's device file, could not be opened.
+    Open { path: PathBuf, source: io::Error },
+    /// The driver refused a request, named as in `linux/videodev2.h`.
+    Ioctl { request: &'static str, errno: Errno },
+    /// Buffer `index` could not be mapped into memory.
+    Mmap { index: usize, errno: Errno },
+    /// The device delivers frames in a pixel format, by FourCC, that cannot be handled.
+    UnsupportedFormat(u32),
+    /// No frame arrived within the timeout.
+    Timeout(Duration),
+    /// The device went away, unplugged or played to its end.
+    DeviceLost,
+    /// The capture was closed with `VideoCapture::close`.
+    Closed,
+    /// Talking to the camdriver module or reading a playback file failed.
+    Io(io::Error),
+}
+
+impl CaptureError {
+    /// A failed `request`. `ENODEV` is how every request fails once the device
+    /// is unplugged, reported as `DeviceLost`.
+    pub(crate) fn ioctl(request: &'static str, errno: Errno) -> Self {
+        match errno {
+            Errno::ENODEV => CaptureError::DeviceLost,
+            errno => CaptureError::Ioctl { request, errno },
+        }
+    }
+
+    /// The errno behind the failure, if there is one.
+    pub fn errno(&self) -> Option<Errno> {
+        match self {
+            CaptureError::Ioctl { errno, .. } | CaptureError::Mmap { errno, .. } => Some(*errno),
+            CaptureError::Open { source: e, .. } | CaptureError::Io(e) => {
+                e.raw_os_error().map(Errno::from_i32)
+            }
+            CaptureError::DeviceLost => Some(Errno::ENODEV),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureError::Open { path, source } => {
+                write!(f, "cannot open {}: {}", path.display(), source)
+            }
+            CaptureError::Ioctl { request, errno } => write!(f, "{} failed: {}", request, errno),
+            CaptureError::Mmap { index, errno } => {
+                write!(f, "cannot map buffer {}: {}", index, errno)
+            }
+            CaptureError::UnsupportedFormat(fourcc) => {
+                let code = fourcc.to_le_bytes().map(|b| b as char);
+                write!(f, "unsupported pixel format {}", code.iter().collect::<String>())
+            }
+            CaptureError::Timeout(timeout) => write!(f, "no frame within {:?}", timeout),
+            CaptureError::DeviceLost => write!(f, "capture device lost"),
+            CaptureError::Closed => write!(f, "capture closed"),
+            CaptureError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CaptureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CaptureError::Open { source: e, .. } | CaptureError::Io(e) => Some(e),
+            CaptureError::Ioctl { errno: e, .. } | CaptureError::Mmap { errno: e, .. } => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(e: io::Error) -> Self {
+        CaptureError::Io(e)
+    }
+}
+
+impl From<CaptureError> for io::Error {
+    fn from(e: CaptureError) -> Self {
+        let kind = match e {
+            CaptureError::Io(e) => return e,
+            CaptureError::Open { ref source, .. } => source.kind(),
+            CaptureError::UnsupportedFormat(_) => io::ErrorKind::Unsupported,
+            CaptureError::Timeout(_) => io::ErrorKind::TimedOut,
+            CaptureError::DeviceLost | CaptureError::Closed => io::ErrorKind::NotConnected,
+            CaptureError::Ioctl { .. } | CaptureError::Mmap { .. } => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, e.to_string())
+    }
+}
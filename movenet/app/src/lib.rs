@@ -1,3 +1,7 @@
+pub mod controls;
+pub mod error;
+#[cfg(feature = "stream")]
+pub mod stream;
 pub mod utils;
 pub mod v4l2;
 pub mod pagemap;
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::env;
 use std::io;
 use std::sync::Arc;
 use std::sync::mpsc;
@@ -7,27 +8,90 @@ use opencv::{
 	highgui::*,
 };
 
+use app::controls::ControlProfile;
+use app::error::CaptureError;
 use app::utils::*;
-use app::v4l2;
-use moveneter_sdk::recognizer::Recognizer;
+use app::v4l2::{self, CaptureBackend, CaptureConfig, CaptureEvent, ReconnectPolicy};
+use moveneter_sdk::recognizer::{Preprocessing, Recognizer};
+use shared::format::{Colorimetry, Orientation, PixelFormat};
+use shared::playback::{Playback, PlaybackSource};
+use shared::protocol::PayloadEncoding;
+use shared::skeleton::{self, SkeletonStyle};
 use shared::threadpool::ThreadPool;
 use std::time::SystemTime;
 
 const THRESHOLD: f32 = 0.25;
 const N_WORKERS: usize = 20;
+const BUFFER_COUNT: usize = 4;
 const TIME_INTERVEL: u128 = 150;
 const SHOW_PREVIEW: bool = false;
+const PAYLOAD_ENCODING: PayloadEncoding = PayloadEncoding::Raw;
+const PREPROCESSING: Preprocessing = Preprocessing::Server;
+const ORIENTATION: Orientation = Orientation::MIRRORED;
+const CAPTURE_BACKEND: CaptureBackend = CaptureBackend::Camdriver;
+/// `/dev/videoN` to capture from.
+const DEVICE_INDEX: u32 = 0;
+/// Formats to capture in, most preferred first. The preview converts YUYV.
+const CAPTURE_FORMATS: &[PixelFormat] = &[PixelFormat::Yuyv];
+/// Path of images or a recording to play back instead of opening the camera,
+/// see `PlaybackSource::from_path`.
+const PLAYBACK_ENV: &str = "MOVENET_PLAYBACK";
+/// Path of a control profile, see `ControlProfile`, applied once streaming.
+const CONTROLS_ENV: &str = "MOVENET_CONTROLS";
+
+fn open_camera() -> io::Result<v4l2::VideoCapture> {
+    match env::var_os(PLAYBACK_ENV) {
+        Some(path) => {
+            let source = PlaybackSource::from_path(path)?;
+            let playback = Playback::open(source, Colorimetry::default())?.with_looping(true);
+            Ok(v4l2::VideoCapture::with_playback(playback))
+        }
+        None => Ok(v4l2::VideoCapture::open_index(DEVICE_INDEX, CAPTURE_BACKEND)?),
+    }
+}
+
+fn quit_pressed() -> bool {
+	let key = wait_key(1).unwrap();
+	key > 0 && key != 255
+}
 
 fn main() -> io::Result<()> {
-    let recog = Arc::new(Recognizer::try_new().unwrap());
-    let (tx, rx) = mpsc::channel();
-    let pool = ThreadPool::new(N_WORKERS);
+    let mut cam = open_camera()?;
+    let granted = cam.prep_stream(
+        Some(BUFFER_COUNT),
+        &CaptureConfig::default().with_formats(CAPTURE_FORMATS)
+    )?;
+    let (frame_width, frame_height) = (granted.width, granted.height);
+    if let Some(path) = env::var_os(CONTROLS_ENV) {
+        let profile = ControlProfile::load(path)?;
+        if let Err(e) = cam.restore_controls(&profile) {
+            eprintln!("Failed to restore camera controls. {}", e);
+        }
+    }
+    // Keeps trying for as long as it takes. Reading times out meanwhile, but
+    // through camdriver, which cannot time out, it blocks until reconnected.
+    cam.set_reconnect(Some(ReconnectPolicy::default()));
+    cam.add_listener(|event| {
+        eprintln!("[camera] {}", event);
+        let title = match event {
+            CaptureEvent::Reconnected(_) => "MoveNet".to_string(),
+            event => format!("MoveNet - {}", event),
+        };
+        // Before the first frame there is no window to title yet.
+        let _ = set_window_title("MoveNet", &title);
+    });
 
-    let mut cam = v4l2::VideoCapture::new().expect("Failed to open camera.");
-    let (frame_width, frame_height) = cam.prep_stream(
-        Some(1),
-        None
+    let recog = Arc::new(
+        Recognizer::try_new()
+            .unwrap()
+            .with_encoding(PAYLOAD_ENCODING)
+            .with_preprocessing(PREPROCESSING)
+            .with_orientation(ORIENTATION)
     );
+    let (tx, rx) = mpsc::channel();
+    let pool = ThreadPool::new(N_WORKERS);
+    let converter = shared::utils::EasyConverter::with_colorimetry(cam.colorimetry());
+    let style = SkeletonStyle { threshold: THRESHOLD, ..Default::default() };
     
     let mut frame = unsafe {
         Mat::new_rows_cols(
@@ -38,28 +102,58 @@ fn main() -> io::Result<()> {
     };
     let mut flipped = Mat::default();
     let mut throttle_timer = SystemTime::now();
-    let mut rgb_data: Vec<u8>;
+    let mut rgb_data = vec![0u8; frame_width as usize * frame_height as usize * 3];
+    unsafe {
+        frame.set_data(rgb_data.as_mut_ptr());
+    }
 
     loop {
-        let out = cam.read().unwrap();
+        let lent = match cam.read_mapped() {
+            Ok(lent) => lent,
+            // A stalled camera may still come back, and a lost one is opened
+            // again. The window stays responsive meanwhile.
+            Err(CaptureError::Timeout(_) | CaptureError::DeviceLost) => {
+                if quit_pressed() {
+                    break;
+                }
+                continue;
+            }
+            Err(e) => {
+                eprintln!("Capture failed. {}", e);
+                break;
+            }
+        };
 
         if SHOW_PREVIEW {
-            let converter = shared::utils::EasyConverter::new();
-            rgb_data = converter.rgb(&out);
-            
-            unsafe {
-                frame.set_data(rgb_data.as_mut_ptr());
+            // Straight from the driver's buffer unless its rows are padded.
+            let yuyv_len = rgb_data.len() / 3 * 2;
+            if lent.format() == PixelFormat::Yuyv
+                && lent.stride() == frame_width as usize * 2 && lent.len() >= yuyv_len
+            {
+                converter.rgb_into(&lent[..yuyv_len], &mut rgb_data);
+            } else if let Ok(frame) = lent.to_frame() {
+                // A reconnected camera may have come back at another size.
+                let rgb = converter.convert(&frame).ok()
+                    .filter(|rgb| rgb.data().len() == rgb_data.len());
+                if let Some(rgb) = rgb {
+                    rgb_data.copy_from_slice(rgb.data());
+                }
             }
         }
+        let sending = frame_width > 0
+            && SystemTime::now().duration_since(throttle_timer).unwrap().as_millis() > TIME_INTERVEL;
+        // Only frames sent for detection are copied out of the buffer.
+        let out = if sending { lent.to_frame().ok() } else { None };
+        drop(lent);
 
         if frame_width > 0 {
 			flip(&frame, &mut flipped, 1).expect("flip [FAILED]");
 
-            if SystemTime::now().duration_since(throttle_timer).unwrap().as_millis() > TIME_INTERVEL {
+            if let Some(out) = out {
                 let recog = Arc::clone(&recog);
                 let job_tx = tx.clone();
                 pool.execute(move || {
-                    if let Ok(data) = recog.detect(&out, [frame_width, frame_height]) {
+                    if let Ok(data) = recog.detect(&out) {
                         job_tx.send(data.clone()).unwrap();
                     }
                 });
@@ -72,8 +166,10 @@ fn main() -> io::Result<()> {
             }
 
             if !data_out.is_empty() {
+                // Keypoints refer to the captured frame, the preview is mirrored.
+                skeleton::restore_orientation(&mut data_out, Orientation::MIRRORED);
                 draw_keypoints(
-                    &mut flipped, data_out.as_slice(), THRESHOLD
+                    &mut flipped, data_out.as_slice(), &style
                 );
             }
 
@@ -81,11 +177,12 @@ fn main() -> io::Result<()> {
 		}
 
 		// keypress check
-		let key = wait_key(1).unwrap();
-		if key > 0 && key != 255 {
+		if quit_pressed() {
 			break;
 		}
     }
+    println!("[metrics] {}: {}", recog.encoding(), recog.metrics());
+    println!("[metrics] capture: {}", cam.drop_stats());
     println!("Exiting...");
     Ok(())
 }
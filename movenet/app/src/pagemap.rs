@@ -67,13 +67,13 @@ pub fn get_pagesize() -> usize {
     }
 }
 
-pub fn get_pagemap(virt_addr: u64, length: u64) -> Vec<i64> {
+pub fn get_pagemap(virt_addr: u64, length: u64) -> std::io::Result<Vec<i64>> {
     let mut virt_addr = virt_addr;
     let entry_size: u64 = 8;
     let page_size = get_pagesize() as u64;
 
     let path = "/proc/self/pagemap";
-    let mut f = File::open(path).unwrap();
+    let mut f = File::open(path)?;
 
     let virt_addr_end = virt_addr + length;
     let mut pfns: Vec<i64> = Vec::new();
@@ -82,11 +82,9 @@ pub fn get_pagemap(virt_addr: u64, length: u64) -> Vec<i64> {
         let page_index = virt_addr / page_size;
         // let page_offset = virt_addr % page_size;
     
-        f.seek(SeekFrom::Start(page_index * entry_size))
-            .expect("Failed to seek.");
+        f.seek(SeekFrom::Start(page_index * entry_size))?;
         let mut page_data = [0u8; 8];
-        f.read_exact(&mut page_data)
-            .expect("Failed to read rge page entry data.");
+        f.read_exact(&mut page_data)?;
         
         let page_data_raw = u64::from_ne_bytes(page_data);
         let pfn = PM_PFRAME(page_data_raw as i64);
@@ -96,5 +94,5 @@ pub fn get_pagemap(virt_addr: u64, length: u64) -> Vec<i64> {
         virt_addr += page_size;
     }
     
-    pfns
+    Ok(pfns)
 }
\ No newline at end of file
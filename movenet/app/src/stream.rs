@@ -0,0 +1,68 @@
+//! Frames of a `VideoCapture` as an async `Stream`, with the `stream` feature.
+//!
+//! Reading blocks in `poll`, so frames are read on a thread of their own and
+//! handed over to the stream. At most one frame waits there, the driver drops
+//! the ones nobody is ready for.
+
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use futures_core::Stream;
+use shared::frame::Frame;
+
+use crate::error::CaptureError;
+use crate::v4l2::VideoCapture;
+
+/// Frames as iterating over the capture yields them, see
+/// `VideoCapture::into_stream`. Ends when the iteration does. Dropping the
+/// stream closes the capture once its thread is done with the current read.
+pub struct FrameStream {
+    frames: Receiver<Result<Frame, CaptureError>>,
+    waker: Arc<Mutex<Option<Waker>>>,
+}
+
+impl VideoCapture {
+    /// Reads frames on a thread of its own and yields them as a `Stream`.
+    /// Set the read timeout beforehand, timeouts are yielded like in iteration.
+    pub fn into_stream(mut self) -> FrameStream {
+        let (tx, frames) = mpsc::sync_channel(1);
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let wake = {
+            let waker = Arc::clone(&waker);
+            move || {
+                if let Some(waker) = waker.lock().unwrap().take() {
+                    waker.wake();
+                }
+            }
+        };
+        thread::spawn(move || {
+            for frame in self.by_ref() {
+                // The stream was dropped.
+                if tx.send(frame).is_err() {
+                    break;
+                }
+                wake();
+            }
+            drop(tx);
+            wake();
+        });
+        FrameStream { frames, waker }
+    }
+}
+
+impl Stream for FrameStream {
+    type Item = Result<Frame, CaptureError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Registered before looking, so a frame sent in between still wakes us.
+        *self.waker.lock().unwrap() = Some(cx.waker().clone());
+        match self.frames.try_recv() {
+            Ok(frame) => Poll::Ready(Some(frame)),
+            Err(TryRecvError::Empty) => Poll::Pending,
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+        }
+    }
+}
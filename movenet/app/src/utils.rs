@@ -3,18 +3,14 @@ use opencv::{
 	imgproc::*,
 	core::*,
 };
+use shared::letterbox::Letterbox;
+use shared::skeleton::{self, Canvas, Pose, Rgb, SkeletonStyle};
 
 pub fn resize_with_padding(img: &Mat, new_shape: [i32;2]) -> Mat {
-	let img_shape = [img.cols(), img.rows()];
-	let width: i32;
-	let height: i32;
-	if img_shape[0] as f64 / img_shape[1] as f64 > new_shape[0] as f64 / new_shape[1] as f64 {
-		width = new_shape[0];
-		height = (new_shape[0] as f64 / img_shape[0] as f64 * img_shape[1] as f64) as i32;
-	} else {
-		width = (new_shape[1] as f64 / img_shape[1] as f64 * img_shape[0] as f64) as i32;
-		height = new_shape[1];
-	}
+	let letterbox = Letterbox::new(
+		[img.cols() as u32, img.rows() as u32], [new_shape[0] as u32, new_shape[1] as u32]
+	);
+	let [width, height] = letterbox.fitted().map(|v| v as i32);
 
 	let mut resized = Mat::default();
 	resize(
@@ -26,11 +22,8 @@ pub fn resize_with_padding(img: &Mat, new_shape: [i32;2]) -> Mat {
 	)
 	.expect("resize_with_padding: resize [FAILED]");
 
-	let delta_w = new_shape[0] - width;
-	let delta_h = new_shape[1] - height;
-	let (top, bottom) = (delta_h / 2, delta_h - delta_h / 2);
-	let (left, right) = (delta_w / 2, delta_w - delta_w / 2);
-		
+	let [top, bottom, left, right] = letterbox.padding().map(|v| v as i32);
+
 	let mut rslt = Mat::default();
 	copy_make_border(
 		&resized,
@@ -42,31 +35,53 @@ pub fn resize_with_padding(img: &Mat, new_shape: [i32;2]) -> Mat {
 	rslt
 }
 
-pub fn draw_keypoints(img: &mut Mat, keypoints: &[f32], threshold: f32) {
-	// keypoints: [1, 17, 3]
-	let base: f32;
-	let pad_x: i32;
-	let pad_y: i32;
-	if img.rows() > img.cols() {
-		base = img.rows() as f32;
-		pad_x = (img.rows() - img.cols()) / 2;
-		pad_y = 0;
-	} else {
-		base = img.cols() as f32;
-		pad_x = 0;
-		pad_y = (img.cols() - img.rows()) / 2;
+/// Draws onto a `Mat` holding RGB pixels, as the preview frames do.
+pub struct MatCanvas<'a>(pub &'a mut Mat);
+
+const FONT_SCALE: f64 = 0.4;
+
+fn point([x, y]: [i32; 2]) -> Point {
+	Point { x, y }
+}
+
+// The preview `Mat`s hold RGB, not OpenCV's usual BGR.
+fn scalar([r, g, b]: Rgb) -> Scalar {
+	Scalar::new(r as f64, g as f64, b as f64, 0.0)
+}
+
+impl Canvas for MatCanvas<'_> {
+	fn size(&self) -> [u32; 2] {
+		[self.0.cols() as u32, self.0.rows() as u32]
 	}
 
-	for index in 0..(keypoints.len() / 3) {
-		let y_ratio = keypoints[index * 3];
-		let x_ratio = keypoints[index * 3 + 1];
-		let confidence = keypoints[index * 3 + 2];
-		if confidence > threshold {
-			circle(img,
-				Point { x: (x_ratio * base) as i32 - pad_x, y: (y_ratio * base) as i32 - pad_y},
-				0,
-				Scalar::new(0.0, 255.0, 0.0, 0.0),
-				5, LINE_AA, 0).expect("Draw circle [FAILED]");
-		}
+	fn line(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb, thickness: u32) {
+		line(self.0, point(from), point(to), scalar(color), thickness as i32, LINE_AA, 0)
+			.expect("Draw line [FAILED]");
 	}
+
+	fn circle(&mut self, center: [i32; 2], radius: u32, color: Rgb) {
+		circle(self.0, point(center), radius as i32, scalar(color), FILLED, LINE_AA, 0)
+			.expect("Draw circle [FAILED]");
+	}
+
+	fn rectangle(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb, thickness: u32) {
+		rectangle_points(self.0, point(from), point(to), scalar(color), thickness as i32, LINE_8, 0)
+			.expect("Draw rectangle [FAILED]");
+	}
+
+	fn text(&mut self, origin: [i32; 2], text: &str, color: Rgb) {
+		// `put_text` places the baseline, not the top, at the origin.
+		let mut baseline = 0;
+		let size = get_text_size(text, FONT_HERSHEY_SIMPLEX, FONT_SCALE, 1, &mut baseline)
+			.expect("Measure text [FAILED]");
+		let origin = point([origin[0], origin[1] + size.height]);
+		put_text(self.0, text, origin, FONT_HERSHEY_SIMPLEX, FONT_SCALE, scalar(color), 1, LINE_AA, false)
+			.expect("Draw text [FAILED]");
+	}
+}
+
+/// Draws every pose in a model output onto `img` in `style`.
+pub fn draw_keypoints(img: &mut Mat, keypoints: &[f32], style: &SkeletonStyle) {
+	// keypoints: [1, 17, 3] single-pose, [n, 56] multi-pose
+	skeleton::draw(&mut MatCanvas(img), &Pose::parse_all(keypoints), style);
 }
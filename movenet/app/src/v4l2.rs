@@ -1,31 +1,58 @@
 //! Rust port of V4L2 support with `ioctl` invocation.
 //! 
-//! Provides a class `VideoCapture` to interact with video driver from userspace program.
+//! Provides a class `VideoCapture` to interact with video driver from userspace program,
+//! either through the `rust_camera` kernel module or with plain V4L2 ioctls. A virtual
+//! camera playing back files stands in for the device on machines without one.
 
 use std::{
-    fs::File, 
-    os::unix::prelude::AsRawFd, 
+    fs::{self, File}, 
+    os::unix::prelude::{AsRawFd, OsStrExt}, 
+    path::{Path, PathBuf},
     str, 
     io::{self, Write, Error, Seek, SeekFrom, Read}, 
+    mem,
     ptr, 
-    ffi::{c_void, c_int}
+    ffi::{c_void, c_int},
+    fmt,
+    thread,
+    time::{Duration, Instant}
 };
 use nix::{
-    sys::{
-        mman::{self, ProtFlags, MapFlags},
-        select,
-        time
-    },
+    sys::mman::{self, ProtFlags, MapFlags},
+    poll::{self, PollFd, PollFlags},
     libc,
     errno::Errno,
 };
 
+use shared::format::{Colorimetry, PixelFormat};
+use shared::frame::{self, CaptureInfo, Frame};
+use shared::metrics::DropStats;
+use shared::playback::Playback;
+
+use crate::controls::{
+    cid, ControlInfo, ControlProfile, ControlType, ExposureMode, MenuItem, SavedControl,
+};
+use crate::error::CaptureError;
 use crate::pagemap;
 
+// Progress of opening and configuring the device, and the addresses handed to
+// the camdriver module. Printed only with the `v4l2-debug` feature.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if cfg!(feature = "v4l2-debug") {
+            println!($($arg)*);
+        }
+    };
+}
+
 // #define VIDIOC_QUERYCAP		 _IOR('V',  0, struct v4l2_capability)
 const VIDIOC_QUERYCAP_MAGIC: u8 = 'V' as u8;
 const VIDIOC_QUERYCAP_TYPE_MODE: u8 = 0;
 
+// #define VIDIOC_ENUM_FMT         _IOWR('V',  2, struct v4l2_fmtdesc)
+const VIDIOC_ENUM_FMT_MAGIC: u8 = 'V' as u8;
+const VIDIOC_ENUM_FMT_TYPE_MODE: u8 = 2;
+
 // #define VIDIOC_G_FMT		     _IOWR('V',  4, struct v4l2_format)
 const VIDIOC_G_FMT_MAGIC: u8 = 'V' as u8;
 const VIDIOC_G_FMT_TYPE_MODE: u8 = 4;
@@ -66,12 +93,140 @@ const VIDIOC_QBUF_TYPE_MODE: u8 = 15;
 const VIDIOC_DQBUF_MAGIC: u8 = 'V' as u8;
 const VIDIOC_DQBUF_TYPE_MODE: u8 = 17;
 
+// #define VIDIOC_G_CTRL		_IOWR('V', 27, struct v4l2_control)
+const VIDIOC_G_CTRL_MAGIC: u8 = 'V' as u8;
+const VIDIOC_G_CTRL_TYPE_MODE: u8 = 27;
+
+// #define VIDIOC_S_CTRL		_IOWR('V', 28, struct v4l2_control)
+const VIDIOC_S_CTRL_MAGIC: u8 = 'V' as u8;
+const VIDIOC_S_CTRL_TYPE_MODE: u8 = 28;
+
+// #define VIDIOC_QUERYCTRL	_IOWR('V', 36, struct v4l2_queryctrl)
+const VIDIOC_QUERYCTRL_MAGIC: u8 = 'V' as u8;
+const VIDIOC_QUERYCTRL_TYPE_MODE: u8 = 36;
+
+// #define VIDIOC_QUERYMENU	_IOWR('V', 37, struct v4l2_querymenu)
+const VIDIOC_QUERYMENU_MAGIC: u8 = 'V' as u8;
+const VIDIOC_QUERYMENU_TYPE_MODE: u8 = 37;
+
+// #define VIDIOC_ENUM_FRAMESIZES	 _IOWR('V', 74, struct v4l2_frmsizeenum)
+const VIDIOC_ENUM_FRAMESIZES_MAGIC: u8 = 'V' as u8;
+const VIDIOC_ENUM_FRAMESIZES_TYPE_MODE: u8 = 74;
+
+// #define VIDIOC_ENUM_FRAMEINTERVALS _IOWR('V', 75, struct v4l2_frmivalenum)
+const VIDIOC_ENUM_FRAMEINTERVALS_MAGIC: u8 = 'V' as u8;
+const VIDIOC_ENUM_FRAMEINTERVALS_TYPE_MODE: u8 = 75;
+
+const V4L2_CAP_VIDEO_CAPTURE: u32 = 0x00000001;
+const V4L2_CAP_DEVICE_CAPS: u32 = 0x80000000;
+const V4L2_FMT_FLAG_COMPRESSED: u32 = 0x0001;
+const V4L2_FMT_FLAG_EMULATED: u32 = 0x0002;
+const V4L2_FRMSIZE_TYPE_DISCRETE: u32 = 1;
+const V4L2_FRMIVAL_TYPE_DISCRETE: u32 = 1;
+const V4L2_CTRL_FLAG_NEXT_CTRL: u32 = 0x80000000;
+const V4L2_CTRL_TYPE_CTRL_CLASS: u32 = 6;
 const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
 const V4L2_MEMORY_MMAP: u32 = 1;
+const V4L2_BUF_FLAG_TIMESTAMP_MASK: u32 = 0xe000;
+const V4L2_BUF_FLAG_TIMESTAMP_MONOTONIC: u32 = 0x2000;
 const MAX_V4L_BUFFERS: usize = 10;
 const DEFAULT_STREAM_FPS: usize = 30;
+const DEFAULT_READ_TIMEOUT: Duration = Duration::from_secs(2);
 
 static DEVICE_FILE_PATH: &'static str = "/dev/camdriver";
+static DEFAULT_VIDEO_PATH: &'static str = "/dev/video0";
+
+// Wrappers of the V4L2 ioctls used by the direct backend.
+mod ioctl {
+    use super::*;
+
+    nix::ioctl_read!(querycap, VIDIOC_QUERYCAP_MAGIC, VIDIOC_QUERYCAP_TYPE_MODE, v4l2_capability);
+    nix::ioctl_readwrite!(enum_fmt, VIDIOC_ENUM_FMT_MAGIC, VIDIOC_ENUM_FMT_TYPE_MODE, v4l2_fmtdesc);
+    nix::ioctl_readwrite!(
+        enum_framesizes, VIDIOC_ENUM_FRAMESIZES_MAGIC, VIDIOC_ENUM_FRAMESIZES_TYPE_MODE,
+        v4l2_frmsizeenum
+    );
+    nix::ioctl_readwrite!(
+        enum_frameintervals, VIDIOC_ENUM_FRAMEINTERVALS_MAGIC, VIDIOC_ENUM_FRAMEINTERVALS_TYPE_MODE,
+        v4l2_frmivalenum
+    );
+    nix::ioctl_readwrite!(g_fmt, VIDIOC_G_FMT_MAGIC, VIDIOC_G_FMT_TYPE_MODE, v4l2_format);
+    nix::ioctl_readwrite!(s_fmt, VIDIOC_S_FMT_MAGIC, VIDIOC_S_FMT_TYPE_MODE, v4l2_format);
+    nix::ioctl_readwrite!(s_parm, VIDIOC_S_PARM_MAGIC, VIDIOC_S_PARM_TYPE_MODE, v4l2_streamparm);
+    nix::ioctl_readwrite!(g_parm, VIDIOC_G_PARM_MAGIC, VIDIOC_G_PARM_TYPE_MODE, v4l2_streamparm);
+    nix::ioctl_readwrite!(reqbufs, VIDIOC_REQBUFS_MAGIC, VIDIOC_REQBUFS_TYPE_MODE, v4l2_requestbuffers);
+    nix::ioctl_readwrite!(querybuf, VIDIOC_QUERYBUF_MAGIC, VIDIOC_QUERYBUF_TYPE_MODE, v4l2_buffer);
+    nix::ioctl_readwrite!(qbuf, VIDIOC_QBUF_MAGIC, VIDIOC_QBUF_TYPE_MODE, v4l2_buffer);
+    nix::ioctl_readwrite!(dqbuf, VIDIOC_DQBUF_MAGIC, VIDIOC_DQBUF_TYPE_MODE, v4l2_buffer);
+    nix::ioctl_readwrite!(g_ctrl, VIDIOC_G_CTRL_MAGIC, VIDIOC_G_CTRL_TYPE_MODE, v4l2_control);
+    nix::ioctl_readwrite!(s_ctrl, VIDIOC_S_CTRL_MAGIC, VIDIOC_S_CTRL_TYPE_MODE, v4l2_control);
+    nix::ioctl_readwrite!(queryctrl, VIDIOC_QUERYCTRL_MAGIC, VIDIOC_QUERYCTRL_TYPE_MODE, v4l2_queryctrl);
+    nix::ioctl_readwrite!(querymenu, VIDIOC_QUERYMENU_MAGIC, VIDIOC_QUERYMENU_TYPE_MODE, v4l2_querymenu);
+    nix::ioctl_write_ptr!(streamon, VIDIOC_STREAMON_MAGIC, VIDIOC_STREAMON_TYPE_MODE, c_int);
+    nix::ioctl_write_ptr!(streamoff, VIDIOC_STREAMOFF_MAGIC, VIDIOC_STREAMOFF_TYPE_MODE, c_int);
+}
+
+/// How `VideoCapture` talks to the camera.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureBackend {
+    /// Through the `rust_camera` kernel module at `/dev/camdriver`, which issues
+    /// the ioctls and copies frames out of the mapped buffer itself.
+    #[default]
+    Camdriver,
+    /// Plain V4L2 ioctls on the video device, for machines without the module.
+    Direct,
+    /// No device at all, frames are played back from files. Opened with
+    /// `VideoCapture::with_playback`.
+    Virtual,
+}
+
+// The requests `VideoCapture` issues, each on the struct of its own it keeps
+// for it. Discriminants are the `io_type` codes of the camdriver module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Request {
+    QueryCap = 0,
+    GetFormat = 1,
+    SetFormat = 2,
+    SetParm = 3,
+    GetParm = 4,
+    RequestBuffers = 5,
+    QueryBuffer = 6,
+    QueueBuffer = 7,
+    StreamOn = 8,
+    StreamOff = 9,
+    QueryCtrl = 10,
+    QueryMenu = 11,
+    GetCtrl = 12,
+    SetCtrl = 13,
+    DequeueBuffer = 14,
+}
+
+impl Request {
+    fn name(self) -> &'static str {
+        match self {
+            Request::QueryCap => "VIDIOC_QUERYCAP",
+            Request::GetFormat => "VIDIOC_G_FMT",
+            Request::SetFormat => "VIDIOC_S_FMT",
+            Request::SetParm => "VIDIOC_S_PARM",
+            Request::GetParm => "VIDIOC_G_PARM",
+            Request::RequestBuffers => "VIDIOC_REQBUFS",
+            Request::QueryBuffer => "VIDIOC_QUERYBUF",
+            Request::QueueBuffer => "VIDIOC_QBUF",
+            Request::StreamOn => "VIDIOC_STREAMON",
+            Request::StreamOff => "VIDIOC_STREAMOFF",
+            Request::QueryCtrl => "VIDIOC_QUERYCTRL",
+            Request::QueryMenu => "VIDIOC_QUERYMENU",
+            Request::GetCtrl => "VIDIOC_G_CTRL",
+            Request::SetCtrl => "VIDIOC_S_CTRL",
+            Request::DequeueBuffer => "VIDIOC_DQBUF",
+        }
+    }
+}
+
+// How the camdriver module passes on the errno of a failed request.
+fn module_errno(e: &Error) -> Errno {
+    Errno::from_i32(e.raw_os_error().unwrap_or(libc::EIO))
+}
 
 #[repr(C)]
 #[derive(Default)]
@@ -92,7 +247,16 @@ pub struct v4l2_pix_format {
     pub width: u32,
     pub height: u32,
     pub pixelformat: u32,
-    pub others: [u8; 200 - 3*4]
+    pub field: u32,
+    pub bytesperline: u32,
+    pub sizeimage: u32,
+    pub colorspace: u32,
+    pub priv_: u32,
+    pub flags: u32,
+    pub ycbcr_enc: u32,
+    pub quantization: u32,
+    pub xfer_func: u32,
+    pub others: [u8; 200 - 12*4]
 }
 
 impl Default for v4l2_pix_format {
@@ -101,7 +265,16 @@ impl Default for v4l2_pix_format {
             width: Default::default(), 
             height: Default::default(), 
             pixelformat: Default::default(), 
-            others: [0u8; 200 - 3*4]
+            field: Default::default(), 
+            bytesperline: Default::default(), 
+            sizeimage: Default::default(), 
+            colorspace: Default::default(), 
+            priv_: Default::default(), 
+            flags: Default::default(), 
+            ycbcr_enc: Default::default(), 
+            quantization: Default::default(), 
+            xfer_func: Default::default(), 
+            others: [0u8; 200 - 12*4]
         }
     }
 }
@@ -128,6 +301,7 @@ impl Default for v4l2_pix_format_mplane {
 }
 
 #[repr(C)]
+#[derive(Copy, Clone)]
 pub enum Fmt {
     Pix(v4l2_pix_format)
 }
@@ -155,7 +329,7 @@ impl Default for v4l2_format {
 
 #[repr(C)]
 #[derive(Default)]
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct v4l2_fract {
     pub numerator: u32,
     pub denominator: u32,
@@ -221,6 +395,20 @@ pub struct timeval {
     tv_usec: i64
 }
 
+impl timeval {
+    pub fn as_duration(&self) -> Duration {
+        Duration::from_secs(self.tv_sec.max(0) as u64)
+            + Duration::from_micros(self.tv_usec.max(0) as u64)
+    }
+}
+
+// Now on CLOCK_MONOTONIC, the clock of V4L2 buffer timestamps.
+fn monotonic_now() -> Duration {
+    let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    Duration::new(now.tv_sec as u64, now.tv_nsec as u32)
+}
+
 #[repr(C)]
 #[derive(Default, Clone, Copy, Debug)]
 pub struct v4l2_timecode {
@@ -262,6 +450,324 @@ pub struct v4l2_requestbuffers {
     pub reserved: [u32; 1]
 }
 
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_fmtdesc {
+    pub index: u32,
+    pub r#type: u32,
+    pub flags: u32,
+    pub description: [u8; 32],
+    pub pixelformat: u32,
+    pub mbus_code: u32,
+    pub reserved: [u32; 3]
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_frmsizeenum {
+    pub index: u32,
+    pub pixel_format: u32,
+    pub r#type: u32,
+    /// `[width, height]` when discrete, otherwise `[min_width, max_width,
+    /// step_width, min_height, max_height, step_height]`.
+    pub size: [u32; 6],
+    pub reserved: [u32; 2]
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_frmivalenum {
+    pub index: u32,
+    pub pixel_format: u32,
+    pub width: u32,
+    pub height: u32,
+    pub r#type: u32,
+    /// The interval when discrete, otherwise `[min, max, step]`.
+    pub interval: [v4l2_fract; 3],
+    pub reserved: [u32; 2]
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_queryctrl {
+    pub id: u32,
+    pub r#type: u32,
+    pub name: [u8; 32],
+    pub minimum: i32,
+    pub maximum: i32,
+    pub step: i32,
+    pub default_value: i32,
+    pub flags: u32,
+    pub reserved: [u32; 2]
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_querymenu {
+    pub id: u32,
+    pub index: u32,
+    /// The item name, or for integer menus its `i64` value in the first 8 bytes.
+    pub name: [u8; 32],
+    pub reserved: u32
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_control {
+    pub id: u32,
+    pub value: i32
+}
+
+// A NUL-padded string of a V4L2 struct.
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+/// A video device, as reported by `VIDIOC_QUERYCAP`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub path: PathBuf,
+    pub driver: String,
+    pub card: String,
+    pub bus_info: String,
+    /// Kernel version, as `(major << 16) | (minor << 8) | patch`.
+    pub version: u32,
+    /// `V4L2_CAP_*` flags of this device node.
+    pub capabilities: u32,
+}
+
+impl DeviceInfo {
+    fn new(path: &Path, cap: &v4l2_capability) -> Self {
+        let capabilities = if cap.capabilities & V4L2_CAP_DEVICE_CAPS != 0 {
+            cap.device_caps
+        } else {
+            cap.capabilities
+        };
+        Self {
+            path: path.to_path_buf(),
+            driver: c_string(&cap.driver),
+            card: c_string(&cap.card),
+            bus_info: c_string(&cap.bus_info),
+            version: cap.version,
+            capabilities,
+        }
+    }
+
+    /// `N` of a `/dev/videoN` path.
+    pub fn index(&self) -> Option<u32> {
+        self.path.file_name()?.to_str()?.strip_prefix("video")?.parse().ok()
+    }
+
+    pub fn can_capture(&self) -> bool {
+        self.capabilities & V4L2_CAP_VIDEO_CAPTURE != 0
+    }
+}
+
+/// Path of the video device on `index`.
+pub fn device_path(index: u32) -> PathBuf {
+    PathBuf::from(format!("/dev/video{}", index))
+}
+
+/// Lists the devices able to capture video, by index. Webcams often come with
+/// a metadata node as well, which is left out.
+pub fn list_devices() -> Result<Vec<DeviceInfo>, CaptureError> {
+    let mut devices = Vec::new();
+    for entry in fs::read_dir("/dev")? {
+        let path = entry?.path();
+        let is_video = path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.starts_with("video"));
+        if !is_video {
+            continue;
+        }
+        // Nodes gone or not allowed to be opened are skipped as well.
+        if let Ok(info) = query_device(&path) {
+            if info.can_capture() {
+                devices.push(info);
+            }
+        }
+    }
+    devices.sort_by_key(|device| device.index());
+    Ok(devices)
+}
+
+/// Queries the capabilities of the video device at `path`.
+pub fn query_device(path: &Path) -> Result<DeviceInfo, CaptureError> {
+    let file = File::options().read(true).write(true).open(path)
+        .map_err(|source| CaptureError::Open { path: path.to_path_buf(), source })?;
+    let mut cap = v4l2_capability::default();
+    unsafe { ioctl::querycap(file.as_raw_fd(), &mut cap) }
+        .map_err(|e| CaptureError::ioctl("VIDIOC_QUERYCAP", e))?;
+    Ok(DeviceInfo::new(path, &cap))
+}
+
+/// A pixel format a device captures in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatDescription {
+    pub fourcc: u32,
+    pub description: String,
+    pub compressed: bool,
+    /// Converted in software rather than produced by the device.
+    pub emulated: bool,
+}
+
+impl FormatDescription {
+    /// The format, if frames in it can be handled at all.
+    pub fn pixel_format(&self) -> Option<PixelFormat> {
+        PixelFormat::from_fourcc(self.fourcc)
+    }
+}
+
+/// Frame sizes a device offers for a pixel format, all as `[width, height]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSize {
+    Discrete([u32; 2]),
+    /// Any size from `min` to `max` in increments of `step`.
+    Stepwise { min: [u32; 2], max: [u32; 2], step: [u32; 2] },
+}
+
+/// Frame intervals, seconds per frame, a device offers for a format and size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameInterval {
+    Discrete(v4l2_fract),
+    /// Any interval from `min` to `max` in increments of `step`.
+    Stepwise { min: v4l2_fract, max: v4l2_fract, step: v4l2_fract },
+}
+
+/// What to ask the driver for. Anything left `None` keeps the device's current
+/// setting, and the driver adjusts the rest to what it can do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureConfig {
+    /// Pixel formats to try, most preferred first.
+    pub formats: Vec<PixelFormat>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub fps: Option<u32>,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            formats: vec![PixelFormat::Yuyv],
+            width: None,
+            height: None,
+            fps: Some(DEFAULT_STREAM_FPS as u32),
+        }
+    }
+}
+
+impl CaptureConfig {
+    pub fn with_formats(mut self, formats: &[PixelFormat]) -> Self {
+        self.formats = formats.to_vec();
+        self
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.fps = Some(fps);
+        self
+    }
+}
+
+/// The format the driver granted, read back after negotiating a `CaptureConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureFormat {
+    pub format: PixelFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row, padding included.
+    pub stride: u32,
+    /// Bytes of a whole frame.
+    pub image_size: u32,
+    /// Seconds per frame. Zero when the driver does not report it.
+    pub interval: v4l2_fract,
+}
+
+impl CaptureFormat {
+    /// Frames per second, rounded down. Zero when unknown.
+    pub fn fps(&self) -> u32 {
+        if self.interval.numerator == 0 {
+            return 0;
+        }
+        self.interval.denominator / self.interval.numerator
+    }
+}
+
+/// How `VideoCapture` opens a lost device again, see `set_reconnect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy {
+    /// Wait before the first attempt, doubled after every failed one.
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    /// Attempts before giving up, `None` to keep trying.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(8),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    // Wait before the next attempt, after `failed` attempts.
+    fn delay(&self, failed: u32) -> Duration {
+        self.initial_delay.saturating_mul(1 << failed.min(16)).min(self.max_delay)
+    }
+
+    fn gives_up(&self, failed: u32) -> bool {
+        self.max_attempts.is_some_and(|max| failed >= max)
+    }
+}
+
+/// What `VideoCapture` tells its listeners about the device, see `add_listener`.
+#[derive(Debug)]
+pub enum CaptureEvent {
+    /// The device went away, unplugged or reset. Its buffers are unmapped.
+    Disconnected,
+    /// Opening it again failed. The next attempt follows in `retry_in`, `None`
+    /// when the `ReconnectPolicy` gave up.
+    ReconnectFailed { attempt: u32, error: CaptureError, retry_in: Option<Duration> },
+    /// The device is back and streaming, in the format granted.
+    Reconnected(CaptureFormat),
+}
+
+impl fmt::Display for CaptureEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CaptureEvent::Disconnected => write!(f, "camera disconnected"),
+            CaptureEvent::ReconnectFailed { attempt, error, retry_in: Some(retry_in) } => {
+                write!(f, "reconnect attempt {} failed, retrying in {:?}: {}", attempt, retry_in, error)
+            }
+            CaptureEvent::ReconnectFailed { attempt, error, retry_in: None } => {
+                write!(f, "reconnect attempt {} failed, giving up: {}", attempt, error)
+            }
+            CaptureEvent::Reconnected(granted) => {
+                write!(f, "camera reconnected, {}x{} {:?}", granted.width, granted.height, granted.format)
+            }
+        }
+    }
+}
+
+type Listener = Box<dyn FnMut(&CaptureEvent) + Send>;
+
+// A lost device being opened again.
+#[derive(Debug, Clone, Copy)]
+struct Outage {
+    failed: u32,
+    next_attempt: Instant,
+}
+
 #[repr(C)]
 #[derive(Default, Clone, Copy)]
 struct Memory {
@@ -269,12 +775,23 @@ struct Memory {
     length: usize
 }
 
+/// Who a mapped buffer belongs to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BufferState {
+    /// Handed to the driver, which may be filling it.
+    Queued,
+    /// Owned by the app: not queued yet, or dequeued with a frame it still holds.
+    #[default]
+    Dequeued,
+}
+
 #[repr(C)]
 #[derive(Default, Copy, Clone)]
 pub struct Buffer {
     memories: Memory,
     bytesused: u32,
-    buffer: v4l2_buffer
+    buffer: v4l2_buffer,
+    state: BufferState
 }
 
 impl Buffer {
@@ -284,266 +801,1046 @@ impl Buffer {
 }
 
 pub struct VideoCapture {
+    backend: CaptureBackend,
+    path: PathBuf,
     is_open: bool,
-    _device_file: File,
+    _device_file: Option<File>,
     raw_fd: i32,
     fps: u32,
+    read_timeout: Option<Duration>,
     buffers: Vec<Buffer>,
-    dev_file: Result<File, Error>,
-    cap: v4l2_capability,
-    format: v4l2_format,
-    streamparm: v4l2_streamparm,
-    reqbuffers: v4l2_requestbuffers,
-    bufs: v4l2_buffer,
-    start_cap_type: u32,
-    stop_cap_type: u32
+    // The camdriver module, open with the camdriver backend only.
+    dev_file: Option<File>,
+    // Arguments of the ioctls the camdriver module issues, which it reads and
+    // writes at the addresses `setup_module` gave it. Boxed so they stay put
+    // when the capture moves, as into a thread.
+    cap: Box<v4l2_capability>,
+    format: Box<v4l2_format>,
+    streamparm: Box<v4l2_streamparm>,
+    reqbuffers: Box<v4l2_requestbuffers>,
+    bufs: Box<v4l2_buffer>,
+    start_cap_type: Box<u32>,
+    stop_cap_type: Box<u32>,
+    queryctrl: v4l2_queryctrl,
+    querymenu: v4l2_querymenu,
+    control: v4l2_control,
+    // The last buffer read, as `VIDIOC_DQBUF` left it.
+    dequeued: v4l2_buffer,
+    // What `read_frame` copied the last frame to, kept across reads.
+    frame_buf: Vec<u8>,
+    drops: DropStats,
+    playback: Option<Playback>,
+    // What `prep_stream` was asked for, to stream the same once reconnected.
+    stream_config: Option<(Option<usize>, CaptureConfig)>,
+    // Set with `restore_controls`, applied again once reconnected.
+    controls: Option<ControlProfile>,
+    reconnect: Option<ReconnectPolicy>,
+    outage: Option<Outage>,
+    listeners: Vec<Listener>
+}
+
+// Opens the device at `path`, and the camdriver module with that backend.
+fn open_files(path: &Path, backend: CaptureBackend) -> Result<(File, Option<File>), CaptureError> {
+    let file = File::options()
+                            .write(true)
+                            .read(true)
+                            .open(path)
+                            .map_err(|source| CaptureError::Open {
+                                path: path.to_path_buf(),
+                                source
+                            })?;
+
+    let dev_file = match backend {
+        CaptureBackend::Camdriver => Some(File::options()
+                            .read(true)
+                            .write(true)
+                            .open(DEVICE_FILE_PATH)
+                            .map_err(|source| CaptureError::Open {
+                                path: PathBuf::from(DEVICE_FILE_PATH),
+                                source
+                            })?),
+        _ => None,
+    };
+    Ok((file, dev_file))
 }
 
 impl VideoCapture {
-    /// Open the default video device on index 0.
-    pub fn new() -> io::Result<Self> {
-        let file = File::options()
-                                .write(true)
-                                .read(true)
-                                .open("/dev/video0")?;
-
-        let dev_file = File::options()
-                                .read(true)
-                                .write(true)
-                                .open(DEVICE_FILE_PATH);
+    /// Open the default video device on index 0, through the camdriver module.
+    pub fn new() -> Result<Self, CaptureError> {
+        Self::with_backend(CaptureBackend::default())
+    }
+
+    /// Open the default video device on index 0, through `backend`.
+    pub fn with_backend(backend: CaptureBackend) -> Result<Self, CaptureError> {
+        Self::open(DEFAULT_VIDEO_PATH, backend)
+    }
+
+    /// Open the video device on `index`, see `list_devices`, through `backend`.
+    pub fn open_index(index: u32, backend: CaptureBackend) -> Result<Self, CaptureError> {
+        Self::open(device_path(index), backend)
+    }
+
+    /// Open the video device at `path` through `backend`. The camdriver module
+    /// is switched over to it as well, failing if the module is not loaded.
+    pub fn open<P: AsRef<Path>>(path: P, backend: CaptureBackend) -> Result<Self, CaptureError> {
+        let path = path.as_ref();
+        if backend == CaptureBackend::Virtual {
+            return Err(CaptureError::Io(Error::new(
+                io::ErrorKind::InvalidInput, "virtual cameras are opened with_playback"
+            )));
+        }
+        let (file, dev_file) = open_files(path, backend)?;
         
-                                let fd = file.as_raw_fd();
-        println!("camera fd = {}", fd);
-        Ok(Self {
+        let fd = file.as_raw_fd();
+        debug!("camera {} fd = {}, backend = {:?}", path.display(), fd, backend);
+        let mut capture = Self {
+            backend,
+            path: path.to_path_buf(),
             is_open: true,
-            _device_file: file,
+            _device_file: Some(file),
             raw_fd: fd,
             fps: Default::default(),
+            read_timeout: (backend != CaptureBackend::Camdriver).then_some(DEFAULT_READ_TIMEOUT),
             buffers: Vec::new(),
             dev_file,
-            cap: v4l2_capability::default(),
-            format: v4l2_format::default(),
-            streamparm: v4l2_streamparm::default(),
-            reqbuffers: v4l2_requestbuffers::default(),
-            bufs: v4l2_buffer::default(),
-            start_cap_type: V4L2_BUF_TYPE_VIDEO_CAPTURE,
-            stop_cap_type: V4L2_BUF_TYPE_VIDEO_CAPTURE
-        })
+            cap: Box::default(),
+            format: Box::default(),
+            streamparm: Box::default(),
+            reqbuffers: Box::default(),
+            bufs: Box::default(),
+            start_cap_type: Box::new(V4L2_BUF_TYPE_VIDEO_CAPTURE),
+            stop_cap_type: Box::new(V4L2_BUF_TYPE_VIDEO_CAPTURE),
+            queryctrl: v4l2_queryctrl::default(),
+            querymenu: v4l2_querymenu::default(),
+            control: v4l2_control::default(),
+            dequeued: v4l2_buffer::default(),
+            frame_buf: Vec::new(),
+            drops: DropStats::new(),
+            playback: None,
+            stream_config: None,
+            controls: None,
+            reconnect: None,
+            outage: None,
+            listeners: Vec::new()
+        };
+        if backend == CaptureBackend::Camdriver {
+            capture.open_module_device()?;
+        }
+        Ok(capture)
+    }
+
+    /// A virtual camera delivering the frames of `playback` instead of a device's.
+    /// `prep_stream` sets its frame rate.
+    pub fn with_playback(playback: Playback) -> Self {
+        Self {
+            backend: CaptureBackend::Virtual,
+            path: PathBuf::new(),
+            is_open: true,
+            _device_file: None,
+            raw_fd: -1,
+            fps: Default::default(),
+            read_timeout: Some(DEFAULT_READ_TIMEOUT),
+            buffers: Vec::new(),
+            dev_file: None,
+            cap: Box::default(),
+            format: Box::default(),
+            streamparm: Box::default(),
+            reqbuffers: Box::default(),
+            bufs: Box::default(),
+            start_cap_type: Box::new(V4L2_BUF_TYPE_VIDEO_CAPTURE),
+            stop_cap_type: Box::new(V4L2_BUF_TYPE_VIDEO_CAPTURE),
+            queryctrl: v4l2_queryctrl::default(),
+            querymenu: v4l2_querymenu::default(),
+            control: v4l2_control::default(),
+            dequeued: v4l2_buffer::default(),
+            frame_buf: Vec::new(),
+            drops: DropStats::new(),
+            playback: Some(playback),
+            stream_config: None,
+            controls: None,
+            reconnect: None,
+            outage: None,
+            listeners: Vec::new()
+        }
+    }
+
+    // The camdriver module, which only the camdriver backend opens.
+    fn module(&mut self) -> Result<&mut File, CaptureError> {
+        self.dev_file.as_mut().ok_or_else(|| CaptureError::Io(Error::new(
+            io::ErrorKind::Unsupported, "only the camdriver backend uses camdriver"
+        )))
+    }
+
+    fn write_uaddr(&mut self, set_type: u64, uaddr: std::ffi::c_ulong) -> Result<(), CaptureError> {
+        let f = self.module()?;
+        let mut result: Vec<u8> = Vec::new();
+        let cmd_bytes = 0u64.to_ne_bytes();
+        debug!("cmd_bytes: {:?}", cmd_bytes);
+        for byte in cmd_bytes {
+            result.push(byte);
+        }
+        for byte in set_type.to_ne_bytes() {
+            result.push(byte);
+        }
+        let uaddr_bytes = uaddr.to_ne_bytes();
+        for byte in uaddr_bytes {
+            result.push(byte);
+        }
+        debug!("results: {:?}", result);
+        f.write_all(&result)?;
+        f.flush()?;
+        Ok(())
+    }
+
+    pub fn setup_module(
+        &mut self
+    ) -> Result<(), CaptureError> {
+        debug!("Write cap.");
+        let cap = &mut *self.cap as *const _ as std::ffi::c_ulong;
+        self.write_uaddr(0, cap)?;
+
+        debug!("Write format.");
+        let format = &mut *self.format as *const _ as std::ffi::c_ulong;
+        self.write_uaddr(1, format)?;
+
+        debug!("Write streamparm.");
+        let streamparm = &mut *self.streamparm as *const _ as std::ffi::c_ulong;
+        self.write_uaddr(2, streamparm)?;
+
+        debug!("Write reqbuffers.");
+        let reqbuffers = &mut *self.reqbuffers as *const _ as std::ffi::c_ulong;
+        self.write_uaddr(3, reqbuffers)?;
+
+        debug!("Write bufs.");
+        let bufs = &mut *self.bufs as *const _ as std::ffi::c_ulong;
+        self.write_uaddr(4, bufs)?;
+
+        debug!("Write start_cap_type.");
+        let start_cap_type = &mut *self.start_cap_type as *const _ as std::ffi::c_ulong;
+        self.write_uaddr(5, start_cap_type)?;
+
+        let stop_cap_type = &mut *self.stop_cap_type as *const _ as std::ffi::c_ulong;
+        self.write_uaddr(6, stop_cap_type)
+    }
+
+    pub fn backend(&self) -> CaptureBackend {
+        self.backend
     }
 
-    fn write_uaddr(&mut self, set_type: u64, uaddr: std::ffi::c_ulong) {
-        match &mut self.dev_file {
-            Ok(f) => {
-                let mut result: Vec<u8> = Vec::new();
-                let cmd_bytes = 0u64.to_ne_bytes();
-                println!("cmd_bytes: {:?}", cmd_bytes);
-                for byte in cmd_bytes {
-                    result.push(byte);
+    /// Whether the capture is still open, see `close`.
+    pub fn is_open(&self) -> bool {
+        self.is_open
+    }
+
+    /// How long reading waits for a frame before failing with `Timeout`,
+    /// `None` to wait for as long as it takes. Two seconds unless set, and
+    /// `None` with the camdriver backend, which refuses any other: the module
+    /// dequeues in a blocking call that no timeout can cut short.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), CaptureError> {
+        if self.backend == CaptureBackend::Camdriver && timeout.is_some() {
+            return Err(CaptureError::Io(Error::new(
+                io::ErrorKind::Unsupported, "reading through camdriver cannot time out"
+            )));
+        }
+        self.read_timeout = timeout;
+        Ok(())
+    }
+
+    pub fn read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// Stops streaming, unmaps the buffers and closes the device. Reading
+    /// afterwards fails with `Closed`, which ends iterating over frames.
+    pub fn close(&mut self) -> Result<(), CaptureError> {
+        if !self.is_open {
+            return Ok(());
+        }
+        if self.playback.take().is_some() {
+            self.is_open = false;
+            return Ok(());
+        }
+        let stopped = match self.outage {
+            Some(_) => Ok(()),
+            None => self.stop_stream(),
+        };
+        self.disconnect();
+        self.is_open = false;
+        self.outage = None;
+        stopped
+    }
+
+    /// Opens the device again with `policy` once it is lost, rather than failing
+    /// for good with `DeviceLost`. Until it is back, reading fails with `Timeout`.
+    /// A virtual camera is never reconnected.
+    pub fn set_reconnect(&mut self, policy: Option<ReconnectPolicy>) {
+        self.reconnect = policy;
+    }
+
+    /// Has `listener` told when the device is lost and when it is back. Listeners
+    /// are called from within reading.
+    pub fn add_listener<F>(&mut self, listener: F)
+        where F: FnMut(&CaptureEvent) + Send + 'static
+    {
+        self.listeners.push(Box::new(listener));
+    }
+
+    /// Whether the device was lost and is still to be opened again.
+    pub fn is_reconnecting(&self) -> bool {
+        match (self.outage, self.reconnect) {
+            (Some(outage), Some(policy)) => !policy.gives_up(outage.failed),
+            _ => false,
+        }
+    }
+
+    fn notify(&mut self, event: CaptureEvent) {
+        for listener in &mut self.listeners {
+            listener(&event);
+        }
+    }
+
+    // Drops the buffers and files of a device gone or closed.
+    fn disconnect(&mut self) {
+        self.unmap_buffers();
+        self.dev_file = None;
+        self._device_file = None;
+        self.raw_fd = -1;
+    }
+
+    // Tears down a lost device when reconnecting, passing `e` on either way.
+    fn lost(&mut self, e: CaptureError) -> CaptureError {
+        let reconnecting = self.reconnect.is_some() && self.playback.is_none();
+        if matches!(e, CaptureError::DeviceLost) && reconnecting && self.outage.is_none() {
+            debug!("Device lost, tearing down. [FAILED]");
+            self.disconnect();
+            let delay = self.reconnect.unwrap_or_default().delay(0);
+            self.outage = Some(Outage { failed: 0, next_attempt: Instant::now() + delay });
+            self.notify(CaptureEvent::Disconnected);
+        }
+        e
+    }
+
+    // Opens a lost device again once the policy allows, waiting no longer than
+    // `deadline`. Does nothing unless the device was lost.
+    fn reconnect_if_lost(&mut self, deadline: Option<Instant>) -> Result<(), CaptureError> {
+        loop {
+            let Some(outage) = self.outage else {
+                return Ok(());
+            };
+            let policy = match self.reconnect {
+                Some(policy) if !policy.gives_up(outage.failed) => policy,
+                _ => return Err(CaptureError::DeviceLost),
+            };
+            if let Some(deadline) = deadline.filter(|deadline| *deadline < outage.next_attempt) {
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                return Err(self.timed_out());
+            }
+            thread::sleep(outage.next_attempt.saturating_duration_since(Instant::now()));
+
+            let attempt = outage.failed + 1;
+            match self.reopen() {
+                Ok(granted) => {
+                    self.outage = None;
+                    self.notify(CaptureEvent::Reconnected(granted));
+                    return Ok(());
                 }
-                for byte in set_type.to_ne_bytes() {
-                    result.push(byte);
+                Err(error) => {
+                    // Whatever got opened goes again before the next attempt.
+                    self.disconnect();
+                    let retry_in = (!policy.gives_up(attempt)).then(|| policy.delay(attempt));
+                    self.outage = Some(Outage {
+                        failed: attempt,
+                        next_attempt: Instant::now() + retry_in.unwrap_or_default(),
+                    });
+                    self.notify(CaptureEvent::ReconnectFailed { attempt, error, retry_in });
                 }
-                let uaddr_bytes = uaddr.to_ne_bytes();
-                for byte in uaddr_bytes {
-                    result.push(byte);
+            }
+        }
+    }
+
+    // Opens the device anew and streams as before it was lost.
+    fn reopen(&mut self) -> Result<CaptureFormat, CaptureError> {
+        let (file, dev_file) = open_files(&self.path, self.backend)?;
+        self.raw_fd = file.as_raw_fd();
+        self._device_file = Some(file);
+        self.dev_file = dev_file;
+        // Until streaming again, requests go to the device rather than failing.
+        let outage = self.outage.take();
+        let result = self.restart_stream();
+        self.outage = outage;
+        result
+    }
+
+    fn restart_stream(&mut self) -> Result<CaptureFormat, CaptureError> {
+        if self.backend == CaptureBackend::Camdriver {
+            // Closes the module's stale file of the lost device.
+            self.open_module_device()?;
+        }
+        let (buffer_count, config) = self.stream_config.clone().unwrap_or_default();
+        let granted = self.prep_stream(buffer_count, &config)?;
+        // Devices come back with their controls reset.
+        if let Some(profile) = self.controls.clone() {
+            self.restore_controls(&profile)?;
+        }
+        Ok(granted)
+    }
+
+    fn timed_out(&self) -> CaptureError {
+        CaptureError::Timeout(self.read_timeout.unwrap_or_default())
+    }
+
+    fn unmap_buffers(&mut self) {
+        for buffer in self.buffers.drain(..) {
+            let mem = buffer.memories;
+            if mem.start == 0 {
+                continue;
+            }
+            unsafe {
+                let result = mman::munmap(
+                    mem.start as *mut c_int as *mut c_void, 
+                    mem.length
+                );
+                match result {
+                    Ok(()) => {},
+                    Err(e) => {
+                        eprintln!("Failed to munmap. [FAILED] {}", e);
+                    }
                 }
-                println!("results: {:?}", result);
-                f.write(&result).unwrap();
-                f.flush().unwrap();
             }
-            Err(_) => {
-                println!("Failed to open camdriver device file. SKIP.")
+        }
+
+        debug!("munmap. [OK]");
+    }
+
+    /// Path of the video device, empty for a virtual camera.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Has the module issue its ioctls on our device rather than the one it
+    // opened when loaded.
+    fn open_module_device(&mut self) -> Result<(), CaptureError> {
+        let mut command = 3u64.to_ne_bytes().to_vec();
+        command.extend_from_slice(self.path.as_os_str().as_bytes());
+        let f = self.module()?;
+        f.write_all(&command)?;
+        f.flush()?;
+        Ok(())
+    }
+
+    // The device itself, also with the camdriver backend: enumerating needs none
+    // of the state the module keeps.
+    fn device_fd(&self, request: &'static str) -> Result<c_int, CaptureError> {
+        match self.backend {
+            CaptureBackend::Virtual => Err(CaptureError::ioctl(request, Errno::ENOTTY)),
+            _ => Ok(self.raw_fd),
+        }
+    }
+
+    /// Capabilities of the open device.
+    pub fn device_info(&self) -> Result<DeviceInfo, CaptureError> {
+        let mut cap = v4l2_capability::default();
+        unsafe { ioctl::querycap(self.device_fd("VIDIOC_QUERYCAP")?, &mut cap) }
+            .map_err(|e| CaptureError::ioctl("VIDIOC_QUERYCAP", e))?;
+        Ok(DeviceInfo::new(&self.path, &cap))
+    }
+
+    /// Pixel formats the device captures in (`VIDIOC_ENUM_FMT`).
+    pub fn formats(&self) -> Result<Vec<FormatDescription>, CaptureError> {
+        let fd = self.device_fd("VIDIOC_ENUM_FMT")?;
+        let mut formats = Vec::new();
+        for index in 0.. {
+            let mut desc = v4l2_fmtdesc {
+                index,
+                r#type: V4L2_BUF_TYPE_VIDEO_CAPTURE,
+                ..Default::default()
+            };
+            match unsafe { ioctl::enum_fmt(fd, &mut desc) } {
+                Ok(_) => formats.push(FormatDescription {
+                    fourcc: desc.pixelformat,
+                    description: c_string(&desc.description),
+                    compressed: desc.flags & V4L2_FMT_FLAG_COMPRESSED != 0,
+                    emulated: desc.flags & V4L2_FMT_FLAG_EMULATED != 0,
+                }),
+                // Past the last one.
+                Err(Errno::EINVAL) => break,
+                Err(e) => return Err(CaptureError::ioctl("VIDIOC_ENUM_FMT", e)),
             }
         }
+        Ok(formats)
     }
 
-    pub fn setup_module(
-        &mut self
-    ) {
-        println!("Write cap.");
-        let cap = &mut self.cap as * const _ as std::ffi::c_ulong;
-        self.write_uaddr(0, cap);
-
-        println!("Write format.");
-        let format = &mut self.format as * const _ as std::ffi::c_ulong;
-        self.write_uaddr(1, format);
-
-        println!("Write streamparm.");
-        let streamparm = &mut self.streamparm as * const _ as std::ffi::c_ulong;
-        self.write_uaddr(2, streamparm);
-
-        println!("Write reqbuffers.");
-        let reqbuffers = &mut self.reqbuffers as * const _ as std::ffi::c_ulong;
-        self.write_uaddr(3, reqbuffers);
-
-        println!("Write bufs.");
-        let bufs = &mut self.bufs as * const _ as std::ffi::c_ulong;
-        self.write_uaddr(4, bufs);
-
-        println!("Write start_cap_type.");
-        let start_cap_type = &mut self.start_cap_type as * const _ as std::ffi::c_ulong;
-        self.write_uaddr(5, start_cap_type);
-
-        let stop_cap_type = &mut self.stop_cap_type as * const _ as std::ffi::c_ulong;
-        self.write_uaddr(6, stop_cap_type);
-    }
-
-    // // Command module to prepare the driver.
-    // pub fn prep_module(&mut self) {
-        // match &mut self.dev_file {
-        //     Ok(f) => {
-        //         let cmd_bytes = 2u64.to_ne_bytes();
-        //         f.write(&cmd_bytes).unwrap();
-        //         f.flush().unwrap();
-        //     }
-        //     Err(_) => {
-        //         println!("Failed to open camdriver device file. SKIP.")
-        //     }
-        // }
-
-    // }
-
-    pub fn start_ioctl(&mut self, io_type: u64) {
-        match &mut self.dev_file {
-            Ok(f) => {
-                let mut result: Vec<u8> = Vec::new();
-                let cmd_bytes = 2u64.to_ne_bytes();
-                for byte in cmd_bytes {
-                    result.push(byte);
-                }
-                let io_bytes = io_type.to_ne_bytes();
-                for byte in io_bytes {
-                    result.push(byte);
-                }
-                println!("Start IOCTL with io_type: {}", io_type);
-                f.write(&result).unwrap();
-                f.flush().unwrap();
+    /// Frame sizes the device offers for the `fourcc` pixel format
+    /// (`VIDIOC_ENUM_FRAMESIZES`).
+    pub fn frame_sizes(&self, fourcc: u32) -> Result<Vec<FrameSize>, CaptureError> {
+        let fd = self.device_fd("VIDIOC_ENUM_FRAMESIZES")?;
+        let mut sizes = Vec::new();
+        for index in 0.. {
+            let mut size = v4l2_frmsizeenum { index, pixel_format: fourcc, ..Default::default() };
+            match unsafe { ioctl::enum_framesizes(fd, &mut size) } {
+                Ok(_) => {}
+                Err(Errno::EINVAL) => break,
+                Err(e) => return Err(CaptureError::ioctl("VIDIOC_ENUM_FRAMESIZES", e)),
             }
-            Err(_) => {
-                println!("Failed to open camdriver device file. SKIP.")
+            let [a, b, c, d, e, f] = size.size;
+            if size.r#type == V4L2_FRMSIZE_TYPE_DISCRETE {
+                sizes.push(FrameSize::Discrete([a, b]));
+            } else {
+                // Continuous or stepwise, the only entry.
+                sizes.push(FrameSize::Stepwise { min: [a, d], max: [b, e], step: [c, f] });
+                break;
+            }
+        }
+        Ok(sizes)
+    }
+
+    /// Frame intervals the device offers for the `fourcc` pixel format at
+    /// `[width, height]` (`VIDIOC_ENUM_FRAMEINTERVALS`).
+    pub fn frame_intervals(
+        &self, fourcc: u32, size: [u32; 2]
+    ) -> Result<Vec<FrameInterval>, CaptureError> {
+        let fd = self.device_fd("VIDIOC_ENUM_FRAMEINTERVALS")?;
+        let mut intervals = Vec::new();
+        for index in 0.. {
+            let mut interval = v4l2_frmivalenum {
+                index,
+                pixel_format: fourcc,
+                width: size[0],
+                height: size[1],
+                ..Default::default()
+            };
+            match unsafe { ioctl::enum_frameintervals(fd, &mut interval) } {
+                Ok(_) => {}
+                Err(Errno::EINVAL) => break,
+                Err(e) => return Err(CaptureError::ioctl("VIDIOC_ENUM_FRAMEINTERVALS", e)),
+            }
+            let [min, max, step] = interval.interval;
+            if interval.r#type == V4L2_FRMIVAL_TYPE_DISCRETE {
+                intervals.push(FrameInterval::Discrete(min));
+            } else {
+                intervals.push(FrameInterval::Stepwise { min, max, step });
+                break;
+            }
+        }
+        Ok(intervals)
+    }
+
+    // Control and dequeue requests through the module, which unlike the others
+    // reports their failures. The struct is registered anew each time as `self`
+    // may have moved.
+    fn module_request(&mut self, request: Request) -> Result<(), CaptureError> {
+        let (set_type, uaddr) = match request {
+            Request::QueryCtrl => (7u64, &mut self.queryctrl as *mut _ as std::ffi::c_ulong),
+            Request::QueryMenu => (8u64, &mut self.querymenu as *mut _ as std::ffi::c_ulong),
+            Request::DequeueBuffer => (4u64, &mut *self.bufs as *mut _ as std::ffi::c_ulong),
+            _ => (9u64, &mut self.control as *mut _ as std::ffi::c_ulong),
+        };
+        let f = self.module()?;
+        let to_error = |e: Error| CaptureError::ioctl(request.name(), module_errno(&e));
+
+        let mut command = 0u64.to_ne_bytes().to_vec();
+        command.extend_from_slice(&set_type.to_ne_bytes());
+        command.extend_from_slice(&(uaddr as u64).to_ne_bytes());
+        f.write_all(&command).map_err(to_error)?;
+
+        let mut command = 2u64.to_ne_bytes().to_vec();
+        command.extend_from_slice(&(request as u64).to_ne_bytes());
+        f.write_all(&command).map_err(to_error)
+    }
+
+    /// Every control of the device, in the order the driver lists them.
+    pub fn controls(&mut self) -> Result<Vec<ControlInfo>, CaptureError> {
+        let mut controls = Vec::new();
+        let mut id = 0;
+        loop {
+            self.queryctrl = v4l2_queryctrl {
+                id: id | V4L2_CTRL_FLAG_NEXT_CTRL,
+                ..Default::default()
+            };
+            match self.ioctl(Request::QueryCtrl) {
+                Ok(()) => {}
+                // Past the last one.
+                Err(e) if e.errno() == Some(Errno::EINVAL) => break,
+                Err(e) => return Err(e),
+            }
+            id = self.queryctrl.id;
+            // Headings of control classes, not controls.
+            if self.queryctrl.r#type != V4L2_CTRL_TYPE_CTRL_CLASS {
+                controls.push(self.read_control_info()?);
+            }
+        }
+        Ok(controls)
+    }
+
+    /// The control `id`, `EINVAL` if the device has none such.
+    pub fn control_info(&mut self, id: u32) -> Result<ControlInfo, CaptureError> {
+        self.queryctrl = v4l2_queryctrl { id, ..Default::default() };
+        self.ioctl(Request::QueryCtrl)?;
+        self.read_control_info()
+    }
+
+    /// ID of the control named `name`, see `ControlInfo::matches`.
+    pub fn control_id(&mut self, name: &str) -> Result<u32, CaptureError> {
+        self.controls()?
+            .into_iter()
+            .find(|control| control.matches(name))
+            .map(|control| control.id)
+            .ok_or(CaptureError::ioctl(Request::QueryCtrl.name(), Errno::EINVAL))
+    }
+
+    // Describes the control just queried, with its menu items.
+    fn read_control_info(&mut self) -> Result<ControlInfo, CaptureError> {
+        let query = &self.queryctrl;
+        let mut info = ControlInfo {
+            id: query.id,
+            name: c_string(&query.name),
+            kind: ControlType::from_v4l2(query.r#type),
+            minimum: query.minimum,
+            maximum: query.maximum,
+            step: query.step,
+            default: query.default_value,
+            flags: query.flags,
+            menu: Vec::new(),
+        };
+        if !matches!(info.kind, ControlType::Menu | ControlType::IntegerMenu) {
+            return Ok(info);
+        }
+        for index in info.minimum.max(0)..=info.maximum.max(0) {
+            self.querymenu = v4l2_querymenu { id: info.id, index: index as u32, ..Default::default() };
+            match self.ioctl(Request::QueryMenu) {
+                Ok(()) => {}
+                // Menus may skip values.
+                Err(e) if e.errno() == Some(Errno::EINVAL) => continue,
+                Err(e) => return Err(e),
+            }
+            let name = &self.querymenu.name;
+            info.menu.push(match info.kind {
+                ControlType::Menu => MenuItem { index: index as u32, name: c_string(name), value: 0 },
+                _ => MenuItem {
+                    index: index as u32,
+                    name: String::new(),
+                    value: i64::from_ne_bytes(name[..8].try_into().unwrap()),
+                },
+            });
+        }
+        Ok(info)
+    }
+
+    /// Current value of the control `id`.
+    pub fn get_control(&mut self, id: u32) -> Result<i32, CaptureError> {
+        self.control = v4l2_control { id, value: 0 };
+        self.ioctl(Request::GetCtrl)?;
+        Ok(self.control.value)
+    }
+
+    /// Sets the control `id`. Drivers clamp out of range values or reject them
+    /// with `ERANGE`, and refuse inactive controls with `EACCES` or `EBUSY`.
+    pub fn set_control(&mut self, id: u32, value: i32) -> Result<(), CaptureError> {
+        self.control = v4l2_control { id, value };
+        self.ioctl(Request::SetCtrl)
+    }
+
+    pub fn get_control_by_name(&mut self, name: &str) -> Result<i32, CaptureError> {
+        let id = self.control_id(name)?;
+        self.get_control(id)
+    }
+
+    pub fn set_control_by_name(&mut self, name: &str, value: i32) -> Result<(), CaptureError> {
+        let id = self.control_id(name)?;
+        self.set_control(id, value)
+    }
+
+    pub fn exposure_mode(&mut self) -> Result<ExposureMode, CaptureError> {
+        let value = self.get_control(cid::EXPOSURE_AUTO)?;
+        ExposureMode::from_value(value)
+            .ok_or(CaptureError::ioctl(Request::GetCtrl.name(), Errno::EINVAL))
+    }
+
+    /// Switches auto exposure. `cid::EXPOSURE_ABSOLUTE` only takes in `Manual`
+    /// and `ShutterPriority`.
+    pub fn set_exposure_mode(&mut self, mode: ExposureMode) -> Result<(), CaptureError> {
+        self.set_control(cid::EXPOSURE_AUTO, mode as i32)
+    }
+
+    /// Values of every control that can be set back, in the driver's order.
+    /// Controls that are inactive, such as the exposure time under auto
+    /// exposure, are left out: their values do not apply.
+    pub fn save_controls(&mut self) -> Result<ControlProfile, CaptureError> {
+        let mut profile = ControlProfile::default();
+        for control in self.controls()? {
+            if !control.kind.is_plain() || control.is_disabled() || control.is_read_only()
+                || control.is_write_only() || control.is_inactive()
+            {
+                continue;
+            }
+            let value = self.get_control(control.id)?;
+            profile.controls.push(SavedControl { id: control.id, value, name: control.name });
+        }
+        Ok(profile)
+    }
+
+    /// Sets every control of `profile`. Those refused are tried again once the
+    /// rest are set, for manual values that only take once the auto mode gating
+    /// them is restored, whichever order the driver lists them in. The profile
+    /// is set again whenever the device is reconnected.
+    pub fn restore_controls(&mut self, profile: &ControlProfile) -> Result<(), CaptureError> {
+        self.controls = Some(profile.clone());
+        let mut refused = Vec::new();
+        for saved in &profile.controls {
+            if self.set_control(saved.id, saved.value).is_err() {
+                refused.push(saved);
+            }
+        }
+        for saved in refused {
+            self.set_control(saved.id, saved.value)?;
+        }
+        Ok(())
+    }
+
+    // Issues `request` on its struct, through the module or straight on the device.
+    fn ioctl(&mut self, request: Request) -> Result<(), CaptureError> {
+        if !self.is_open {
+            return Err(CaptureError::Closed);
+        }
+        if self.outage.is_some() {
+            return Err(CaptureError::DeviceLost);
+        }
+        match self.backend {
+            CaptureBackend::Camdriver if request as u64 >= Request::QueryCtrl as u64 => {
+                return self.module_request(request);
+            }
+            CaptureBackend::Camdriver => return self.start_ioctl(request as u64),
+            CaptureBackend::Virtual => return Err(CaptureError::ioctl(request.name(), Errno::ENOTTY)),
+            CaptureBackend::Direct => {}
+        }
+
+        let fd = self.raw_fd;
+        let result = unsafe {
+            match request {
+                Request::QueryCap => ioctl::querycap(fd, &mut *self.cap),
+                Request::GetFormat => ioctl::g_fmt(fd, &mut *self.format),
+                Request::SetFormat => ioctl::s_fmt(fd, &mut *self.format),
+                Request::SetParm => ioctl::s_parm(fd, &mut *self.streamparm),
+                Request::GetParm => ioctl::g_parm(fd, &mut *self.streamparm),
+                Request::RequestBuffers => ioctl::reqbufs(fd, &mut *self.reqbuffers),
+                Request::QueryBuffer => ioctl::querybuf(fd, &mut *self.bufs),
+                Request::QueueBuffer => ioctl::qbuf(fd, &mut *self.bufs),
+                Request::StreamOn => ioctl::streamon(fd, &(*self.start_cap_type as c_int)),
+                Request::StreamOff => ioctl::streamoff(fd, &(*self.stop_cap_type as c_int)),
+                Request::QueryCtrl => ioctl::queryctrl(fd, &mut self.queryctrl),
+                Request::QueryMenu => ioctl::querymenu(fd, &mut self.querymenu),
+                Request::GetCtrl => ioctl::g_ctrl(fd, &mut self.control),
+                Request::SetCtrl => ioctl::s_ctrl(fd, &mut self.control),
+                Request::DequeueBuffer => ioctl::dqbuf(fd, &mut *self.bufs),
             }
+        };
+        result.map(|_| ()).map_err(|e| CaptureError::ioctl(request.name(), e))
+    }
+
+    /// Has the camdriver module issue the request `io_type` on the structs
+    /// registered with `setup_module`. The module does not report whether the
+    /// driver took it.
+    pub fn start_ioctl(&mut self, io_type: u64) -> Result<(), CaptureError> {
+        let f = self.module()?;
+        let mut result: Vec<u8> = Vec::new();
+        let cmd_bytes = 2u64.to_ne_bytes();
+        for byte in cmd_bytes {
+            result.push(byte);
+        }
+        let io_bytes = io_type.to_ne_bytes();
+        for byte in io_bytes {
+            result.push(byte);
         }
+        debug!("Start IOCTL with io_type: {}", io_type);
+        f.write_all(&result)?;
+        f.flush()?;
+        Ok(())
     }
 
+    /// Negotiates `config` with the driver, then maps `buffer_count` buffers and
+    /// starts streaming. Returns the format actually granted.
     pub fn prep_stream(
         &mut self, 
         buffer_count: Option<usize>,
-        fps: Option<usize>
-    ) -> (u32, u32) {
-        println!("Started SETUP MODULE");
-        self.setup_module();
-        println!("Started QUERY_CAP");
-        self.query_cap();
-        println!("Started CHECK_FORMAT");
-        self.check_img_format();
-        let (frame_width, frame_height) = match self.format.fmt {
-            Fmt::Pix(pix_format) => {
-                (pix_format.width, pix_format.height)
+        config: &CaptureConfig
+    ) -> Result<CaptureFormat, CaptureError> {
+        self.stream_config = Some((buffer_count, config.clone()));
+        if self.playback.is_some() {
+            let granted = self.configure(config)?;
+            debug!("Playing back {}x{} at {} fps", granted.width, granted.height, self.fps);
+            return Ok(granted);
+        }
+        if self.backend == CaptureBackend::Camdriver {
+            debug!("Started SETUP MODULE");
+            self.setup_module()?;
+        }
+        debug!("Started QUERY_CAP");
+        self.query_cap()?;
+        debug!("Started CONFIGURE");
+        let granted = self.configure(config)?;
+        debug!("Started REQ_BUFS");
+        let size = self.request_buffer(buffer_count.unwrap_or(MAX_V4L_BUFFERS) as u32)?;
+        debug!("{} buffers are requested", size);
+        debug!("Started QUERY_BUF");
+        self.query_buffer()?;
+        debug!("Started STREAM");
+        self.start_stream()?;
+        debug!("Started QUEUE_BUFFER");
+        self.queue_buffer()?;
+
+        Ok(granted)
+    }
+
+    /// Asks the driver for `config` and reads back what it granted. Each format of
+    /// `config.formats` is tried in turn at the requested size; when the driver
+    /// takes none of them, capture carries on in whatever format the device is in.
+    /// Fails with `UnsupportedFormat` only if that format cannot be handled either.
+    pub fn configure(&mut self, config: &CaptureConfig) -> Result<CaptureFormat, CaptureError> {
+        if let Some(playback) = &mut self.playback {
+            let fps = config.fps.unwrap_or(DEFAULT_STREAM_FPS as u32);
+            playback.set_fps(fps);
+            self.fps = fps;
+            let [width, height] = playback.size();
+            return Ok(CaptureFormat {
+                format: PixelFormat::Yuyv,
+                width,
+                height,
+                stride: width * 2,
+                image_size: width * height * 2,
+                interval: v4l2_fract { numerator: 1, denominator: fps },
+            });
+        }
+
+        self.check_img_format()?;
+        let current = self.format.fmt;
+        // Formats the device does not list are not worth a round trip. Drivers
+        // without VIDIOC_ENUM_FMT get every one tried.
+        let offered = self.formats().ok()
+            .map(|formats| formats.iter().map(|desc| desc.fourcc).collect::<Vec<_>>());
+
+        let mut granted = None;
+        for &format in &config.formats {
+            if offered.as_ref().is_some_and(|offered| !offered.contains(&format.fourcc())) {
+                debug!("{:?} is not offered. SKIP.", format);
+                continue;
             }
-        };
-        println!("Started SWITCH");
-        self.switch_to_yuyv();
-        println!("Started SET_FPS");
-        self.set_fps(fps.unwrap_or(DEFAULT_STREAM_FPS) as u32);
-        println!("Started REQ_BUFS");
-        match self.request_buffer(buffer_count.unwrap_or(MAX_V4L_BUFFERS) as u32) {
-            Ok(size) => {
-                println!("{} buffers are requested", size);
+            self.format.fmt = current;
+            let Fmt::Pix(pix_format) = &mut self.format.fmt;
+            pix_format.pixelformat = format.fourcc();
+            pix_format.width = config.width.unwrap_or(pix_format.width);
+            pix_format.height = config.height.unwrap_or(pix_format.height);
+            // Derived from the rest by the driver.
+            pix_format.bytesperline = 0;
+            pix_format.sizeimage = 0;
+            match self.ioctl(Request::SetFormat) {
+                Ok(()) => {}
+                Err(CaptureError::DeviceLost) => return Err(CaptureError::DeviceLost),
+                Err(_) => continue,
             }
-            Err(e) => {
-                panic!("request buffer error {}", e);
+            // The camdriver backend does not report failures, the read back does.
+            self.check_img_format()?;
+            let Fmt::Pix(pix_format) = self.format.fmt;
+            if pix_format.pixelformat == format.fourcc() {
+                granted = Some(format);
+                break;
+            }
+            debug!("{:?} was not granted. [FAILED]", format);
+        }
+
+        let granted = match granted {
+            Some(format) => format,
+            None => {
+                debug!("No preferred format granted, keeping the current one.");
+                // A rejected attempt may still have switched the device over.
+                self.check_img_format()?;
+                let Fmt::Pix(pix_format) = self.format.fmt;
+                PixelFormat::from_fourcc(pix_format.pixelformat)
+                    .ok_or(CaptureError::UnsupportedFormat(pix_format.pixelformat))?
+            }
+        };
+
+        let interval = match config.fps {
+            Some(fps) => self.set_fps(fps),
+            None => self.get_fps(),
+        };
+        // Cameras without frame rate control still capture, at their own pace.
+        let interval = match interval {
+            Ok(interval) => interval,
+            Err(CaptureError::DeviceLost) => return Err(CaptureError::DeviceLost),
+            Err(_) => {
+                debug!("Frame rate unavailable. SKIP.");
+                self.fps = 0;
+                v4l2_fract::default()
             }
         };
-        println!("Started QUERY_BUF");
-        self.query_buffer().unwrap();
-        println!("Started STREAM");
-        self.start_stream();
-        println!("Started QUEUE_BUFFER");
-        self.queue_buffer().unwrap();
 
-        (frame_width, frame_height)
+        let Fmt::Pix(pix_format) = self.format.fmt;
+        let min_stride = granted.min_stride(pix_format.width).unwrap_or(0) as u32;
+        let granted = CaptureFormat {
+            format: granted,
+            width: pix_format.width,
+            height: pix_format.height,
+            stride: pix_format.bytesperline.max(min_stride),
+            image_size: pix_format.sizeimage,
+            interval,
+        };
+        debug!("Granted {:?}", granted);
+        Ok(granted)
     }
 
-    pub fn query_cap(&mut self) {
+    pub fn query_cap(&mut self) -> Result<(), CaptureError> {
         if !self.is_open {
-            println!("File is opened.");
-            return;
+            return Err(CaptureError::Closed);
         }
 
-        self.start_ioctl(0);
+        self.ioctl(Request::QueryCap)?;
 
         let info = &self.cap;
 
-        println!("driver: {:?}", str::from_utf8(&info.driver));
-        println!("card: {:?}", str::from_utf8(&info.card));
-        println!("bus_info: {:?}", str::from_utf8(&info.bus_info));
+        debug!("driver: {:?}", str::from_utf8(&info.driver));
+        debug!("card: {:?}", str::from_utf8(&info.card));
+        debug!("bus_info: {:?}", str::from_utf8(&info.bus_info));
+        Ok(())
     }
 
-    pub fn check_img_format(&mut self) {
+    pub fn check_img_format(&mut self) -> Result<(), CaptureError> {
         self.format.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
-        self.start_ioctl(1);
+        self.ioctl(Request::GetFormat)?;
 
         match self.format.fmt {
             Fmt::Pix(pix_format) => {
-                println!("width: {}", pix_format.width);
-                println!("height: {}", pix_format.height);
+                debug!("width: {}", pix_format.width);
+                debug!("height: {}", pix_format.height);
             }
         }
+        Ok(())
     }
 
-    pub fn switch_to_yuyv(&mut self) {
+    /// Colorimetry of the negotiated format, as reported by the driver.
+    pub fn colorimetry(&self) -> Colorimetry {
+        if let Some(playback) = &self.playback {
+            return playback.colorimetry();
+        }
         match self.format.fmt {
-            Fmt::Pix(mut pix_format) => {
-                pix_format.pixelformat = 0x56595559;
-                self.start_ioctl(2);
-            }
+            Fmt::Pix(pix_format) => Colorimetry::from_v4l2(
+                pix_format.colorspace,
+                pix_format.ycbcr_enc,
+                pix_format.quantization
+            )
+        }
+    }
+
+    // What the driver reported with the last buffer read. Drivers without
+    // monotonic timestamps get the frame stamped on arrival.
+    fn capture_info(&self) -> CaptureInfo {
+        let buffer = &self.dequeued;
+        let monotonic = match buffer.flags & V4L2_BUF_FLAG_TIMESTAMP_MASK {
+            V4L2_BUF_FLAG_TIMESTAMP_MONOTONIC => buffer.timestamp.as_duration(),
+            _ => monotonic_now(),
+        };
+        CaptureInfo {
+            monotonic,
+            sequence: buffer.sequence,
+            bytes_used: buffer.bytesused as usize,
         }
     }
 
-    pub fn set_fps(&mut self, value: u32) {
+    /// Wraps bytes read from the driver in a `Frame` described by the negotiated
+    /// format, carrying the driver's timestamp and sequence number.
+    fn to_frame(&self, mut data: Vec<u8>) -> Result<Frame, CaptureError> {
+        let Fmt::Pix(pix_format) = self.format.fmt;
+        let format = PixelFormat::from_fourcc(pix_format.pixelformat)
+            .ok_or(CaptureError::UnsupportedFormat(pix_format.pixelformat))?;
+        let capture = self.capture_info();
+        // Compressed frames end where the driver stopped writing.
+        if format.min_stride(pix_format.width).is_none() && capture.bytes_used > 0 {
+            data.truncate(capture.bytes_used);
+        }
+        let min_stride = format.min_stride(pix_format.width).unwrap_or(0);
+        let stride = (pix_format.bytesperline as usize).max(min_stride);
+        let frame = Frame::with_stride(data, pix_format.width, pix_format.height, stride, format)?;
+
+        // Moved onto the wall clock by how long ago the driver took it.
+        let age = monotonic_now().saturating_sub(capture.monotonic);
+        let timestamp = frame::now_millis().saturating_sub(age.as_millis());
+        Ok(frame
+            .with_colorimetry(self.colorimetry())
+            .with_timestamp(timestamp)
+            .with_sequence(capture.sequence as u64)
+            .with_capture_info(capture))
+    }
+
+    /// Frames received and dropped by the driver so far, from sequence numbers.
+    pub fn drop_stats(&self) -> DropStats {
+        self.drops
+    }
+
+    pub fn switch_to_yuyv(&mut self) -> Result<(), CaptureError> {
+        let Fmt::Pix(pix_format) = &mut self.format.fmt;
+        pix_format.pixelformat = PixelFormat::Yuyv.fourcc();
+        self.ioctl(Request::SetFormat)
+    }
+
+    /// Asks for `value` frames per second, returning the interval granted.
+    pub fn set_fps(&mut self, value: u32) -> Result<v4l2_fract, CaptureError> {
         self.streamparm.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
         self.streamparm.numerator = 1;
         self.streamparm.denominator = value;
 
-        self.start_ioctl(3);
-        self.start_ioctl(4);
+        self.ioctl(Request::SetParm)?;
+        self.get_fps()
+    }
 
-        println!("FPS: {}/{}", self.streamparm.denominator, self.streamparm.numerator);
-        self.fps = self.streamparm.denominator;
+    /// The current frame interval, seconds per frame.
+    pub fn get_fps(&mut self) -> Result<v4l2_fract, CaptureError> {
+        self.streamparm.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
+        self.ioctl(Request::GetParm)?;
+
+        debug!("FPS: {}/{}", self.streamparm.denominator, self.streamparm.numerator);
+        self.fps = match self.streamparm.numerator {
+            0 => 0,
+            numerator => self.streamparm.denominator / numerator,
+        };
+        Ok(v4l2_fract {
+            numerator: self.streamparm.numerator,
+            denominator: self.streamparm.denominator,
+        })
     }
 
-    pub fn request_buffer(&mut self, count: u32) -> Result<usize, Errno> {
-        self.reqbuffers.count = 1;
+    /// Requests `count` buffers, at least one. The driver may grant a different number.
+    pub fn request_buffer(&mut self, count: u32) -> Result<usize, CaptureError> {
+        self.reqbuffers.count = count.max(1);
         self.reqbuffers.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
         self.reqbuffers.memory = V4L2_MEMORY_MMAP;
 
-        self.start_ioctl(5);
+        self.ioctl(Request::RequestBuffers)?;
 
-        // if reqbufs.count < 5 {
-        //     println!("request buffers: not enough buffer available ({} available) [FAILED] ", reqbufs.count);
-        //     return Err(Errno::EADDRNOTAVAIL);
-        // }
-
-        // println!("reqbufs count: {}", reqbufs.count);
-        // println!("reqbufs memory: {}", reqbufs.memory);
-
-        println!("reqbufs count: {}", self.reqbuffers.count);
-        println!("reqbufs memory: {}", self.reqbuffers.memory);
+        debug!("reqbufs count: {}", self.reqbuffers.count);
+        debug!("reqbufs memory: {}", self.reqbuffers.memory);
         
         let buf_count = self.reqbuffers.count as usize;
         self.buffers = vec![Default::default(); buf_count];
         Ok(buf_count)
     }
 
-    pub fn query_buffer(&mut self) -> Result<(), Errno> {
+    /// Maps every requested buffer.
+    pub fn query_buffer(&mut self) -> Result<(), CaptureError> {
+        for index in 0..self.buffers.len() {
+            self.map_buffer(index)?;
+        }
+        debug!("query buffers [OK]");
+        Ok(())
+    }
+
+    fn map_buffer(&mut self, index: usize) -> Result<(), CaptureError> {
         self.bufs.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
         self.bufs.memory = V4L2_MEMORY_MMAP;
-        self.bufs.index = 0 as u32;
+        self.bufs.index = index as u32;
 
-        self.start_ioctl(6);
+        self.ioctl(Request::QueryBuffer)?;
 
-        println!("buffer[{}] length: {}", 0, self.bufs.length);
-        println!("buffer[{}] offset: {}", 0, self.bufs.offset);
+        debug!("buffer[{}] length: {}", index, self.bufs.length);
+        debug!("buffer[{}] offset: {}", index, self.bufs.offset);
 
         unsafe {
             let data = mman::mmap(
@@ -558,192 +1855,543 @@ impl VideoCapture {
             match data {
                 Ok(val) => {
                     let addr = val as *const _ as u64;
-                    // println!("");
-                    // println!("Mapped address start: {:#20x}", addr);
-                    // println!("Mapped address end: {:#20x}", addr + buf.length as u64);
-                    let pfns = pagemap::get_pagemap(addr, self.bufs.length as u64);
-                    self.inform_pfns(pfns, 0);
-                    self.buffers[0].memories = Memory {
+                    self.buffers[index].memories = Memory {
                         start: val as *const _ as u64,
                         length: self.bufs.length as usize,
                     };
-                    self.buffers[0].bytesused = self.bufs.bytesused;
-                    self.buffers[0].buffer = self.bufs;
-                }
-                Err(e) => {
-                    println!("mem map failed for buffer {}. {}", 0, e);
-                    return Err(Errno::EADDRNOTAVAIL)
+                    self.buffers[index].bytesused = self.bufs.bytesused;
+                    self.buffers[index].buffer = *self.bufs;
+                    // Only the module needs to find the buffer in physical memory.
+                    if self.backend == CaptureBackend::Camdriver {
+                        let pfns = pagemap::get_pagemap(addr, self.bufs.length as u64)?;
+                        self.inform_pfns(pfns, index)?;
+                    }
                 }
+                Err(errno) => return Err(CaptureError::Mmap { index, errno }),
             }
         }
-        
-        println!("query buffers [OK]");
         Ok(())
     }
 
-    fn inform_pfns(&mut self, pfns: Vec<i64>, index: usize) {
-        match &mut self.dev_file {
-            Ok(f) => {
-                f.seek(SeekFrom::Start(index as u64)).unwrap();
-                let mut result: Vec<u8> = Vec::new();
-                let cmd_bytes = 1u64.to_ne_bytes();
-                for byte in cmd_bytes {
-                    result.push(byte);
-                }
-                for pfn in pfns {
-                    let pfn_bytes = pfn.to_ne_bytes();
-                    for byte in pfn_bytes {
-                        result.push(byte);
-                    }
-                }
-                // let pfns: Vec<u8> = pfns.iter().flat_map(|x| x.to_ne_bytes()).collect();
-                f.write_all(&result).unwrap();
-                f.flush().unwrap();
-            }
-            Err(_) => {
-                println!("Failed to open camdriver device file. SKIP.")
+    fn inform_pfns(&mut self, pfns: Vec<i64>, index: usize) -> Result<(), CaptureError> {
+        let f = self.module()?;
+        f.seek(SeekFrom::Start(index as u64))?;
+        let mut result: Vec<u8> = Vec::new();
+        let cmd_bytes = 1u64.to_ne_bytes();
+        for byte in cmd_bytes {
+            result.push(byte);
+        }
+        for pfn in pfns {
+            let pfn_bytes = pfn.to_ne_bytes();
+            for byte in pfn_bytes {
+                result.push(byte);
             }
         }
+        // let pfns: Vec<u8> = pfns.iter().flat_map(|x| x.to_ne_bytes()).collect();
+        f.write_all(&result)?;
+        f.flush()?;
+        Ok(())
+    }
+
+    pub fn start_stream(&mut self) -> Result<(), CaptureError> {
+        self.ioctl(Request::StreamOn)
     }
 
-    pub fn start_stream(&mut self) {
-        self.start_ioctl(8);
+    pub fn stop_stream(&mut self) -> Result<(), CaptureError> {
+        self.ioctl(Request::StreamOff)
     }
 
-    pub fn stop_stream(&mut self) {
-        self.start_ioctl(9);
+    /// Queues every buffer not yet queued, as done once before streaming.
+    /// Frames held with `dequeue` are handed back as well.
+    pub fn queue_buffer(&mut self) -> Result<(), CaptureError> {
+        for index in 0..self.buffers.len() {
+            if self.buffers[index].state != BufferState::Queued {
+                self.enqueue(index)?;
+            }
+        }
+        Ok(())
     }
 
-    pub fn queue_buffer(&mut self) -> Result<(), Errno> {
+    fn enqueue(&mut self, index: usize) -> Result<(), CaptureError> {
         self.bufs.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
         self.bufs.memory = V4L2_MEMORY_MMAP;
-        self.bufs.index = 0;
+        self.bufs.index = index as u32;
 
-        self.start_ioctl(7);
-        // println!("queue buffer [OK]");
+        self.ioctl(Request::QueueBuffer)?;
+        self.buffers[index].state = BufferState::Queued;
         Ok(())
     }
 
-    pub fn read_frame(&mut self) -> Result<Vec<u8>, Errno> {
-        // // ioctl_readwrite!(vidioc_dqbuf, VIDIOC_DQBUF_MAGIC, VIDIOC_DQBUF_TYPE_MODE, v4l2_buffer);
-        // ioctl_readwrite!(vidioc_qbuf, VIDIOC_QBUF_MAGIC, VIDIOC_QBUF_TYPE_MODE, v4l2_buffer);
+    /// State of every mapped buffer, by index.
+    pub fn buffer_states(&self) -> Vec<BufferState> {
+        self.buffers.iter().map(|buffer| buffer.state).collect()
+    }
 
-        // let mut buf: v4l2_buffer = Default::default();
-        // println!("Started read frame.");
+    /// Takes the next filled buffer from the driver and holds it, returning its
+    /// index. The driver keeps filling the other queued buffers meanwhile, until
+    /// the frame is handed back with `release`. With the camdriver backend the
+    /// module dequeues without copying, the frame is read from our own mapping.
+    pub fn dequeue(&mut self) -> Result<usize, CaptureError> {
         self.bufs.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
         self.bufs.memory = V4L2_MEMORY_MMAP;
+        self.ioctl(Request::DequeueBuffer)?;
+
+        let index = self.bufs.index as usize;
+        let buffer = self.buffers.get_mut(index)
+            .ok_or(CaptureError::ioctl(Request::DequeueBuffer.name(), Errno::EINVAL))?;
+        buffer.bytesused = self.bufs.bytesused;
+        buffer.buffer = *self.bufs;
+        buffer.state = BufferState::Dequeued;
+        Ok(index)
+    }
 
-        let mut result = vec![0u8; self.bufs.length as usize];
-        match &mut self.dev_file {
-            Ok(f) => {
-                match f.read(&mut result) {
-                    Ok(_) => {  },
-                    Err(_) => { return Err(Errno::EADDRNOTAVAIL); }
-                }
-            }
-            Err(_) => {
-                println!("Failed to open camdriver device file. SKIP.")
+    /// The frame in a held buffer, `None` unless `index` is dequeued.
+    pub fn held(&self, index: usize) -> Option<&[u8]> {
+        let buffer = self.buffers.get(index)?;
+        if buffer.state != BufferState::Dequeued || buffer.memories.start == 0 {
+            return None;
+        }
+        let len = (buffer.bytesused as usize).min(buffer.memories.length);
+        // The driver leaves a dequeued buffer alone until it is queued again,
+        // which takes `&mut self`.
+        Some(unsafe { std::slice::from_raw_parts(buffer.memories.start as *const u8, len) })
+    }
+
+    /// Hands a held buffer back to the driver.
+    pub fn release(&mut self, index: usize) -> Result<(), CaptureError> {
+        match self.buffers.get(index) {
+            Some(buffer) if buffer.state == BufferState::Dequeued => self.enqueue(index),
+            _ => Err(CaptureError::ioctl(Request::QueueBuffer.name(), Errno::EINVAL)),
+        }
+    }
+
+    /// Copies the next frame out and hands its buffer back to the driver. The
+    /// copy is kept by the capture and overwritten by the next read.
+    pub fn read_frame(&mut self) -> Result<&[u8], CaptureError> {
+        self.bufs.r#type = V4L2_BUF_TYPE_VIDEO_CAPTURE;
+        self.bufs.memory = V4L2_MEMORY_MMAP;
+
+        if self.backend == CaptureBackend::Direct {
+            self.dequeue_frame()?;
+            return Ok(&self.frame_buf);
+        }
+
+        let mut frame_buf = mem::take(&mut self.frame_buf);
+        frame_buf.resize(self.bufs.length as usize, 0);
+        // The module dequeues on read and passes on the driver's errno.
+        let read = self.module()?.read(&mut frame_buf);
+        self.frame_buf = frame_buf;
+        match read {
+            Ok(len) => self.frame_buf.truncate(len),
+            Err(e) => {
+                return Err(CaptureError::ioctl(Request::DequeueBuffer.name(), module_errno(&e)));
             }
         }
+        // The module dequeued into `bufs`, queued the buffer again and
+        // put back what the dequeue reported.
+        self.dequeued = *self.bufs;
+        if let Some(buffer) = self.buffers.get_mut(self.bufs.index as usize) {
+            buffer.bytesused = self.bufs.bytesused;
+        }
 
-        Ok(result)
+        Ok(&self.frame_buf)
     }
 
-    pub fn read(&mut self) -> Result<Vec<u8>, Errno> {
-        loop {
-            let mut fd_set = select::FdSet::new();
-            let mut tv = time::TimeVal::new(2, 0);
-
-            fd_set.clear();
-            fd_set.insert(self.raw_fd);
-
-            let result = select::select(
-                self.raw_fd + 1, 
-                &mut fd_set, 
-                None, 
-                None, 
-                &mut tv
-            );
+    // Copies the next filled buffer to `frame_buf` and hands it back to the
+    // driver, as the camdriver module does on `read`.
+    fn dequeue_frame(&mut self) -> Result<(), CaptureError> {
+        let index = self.dequeue()?;
+        self.dequeued = self.buffers[index].buffer;
+        let mut frame_buf = mem::take(&mut self.frame_buf);
+        frame_buf.clear();
+        frame_buf.extend_from_slice(self.held(index).unwrap_or_default());
+        self.frame_buf = frame_buf;
+        self.release(index)
+    }
 
-            match result {
-                Ok(_) => {
-                    let read_result = self.read_frame();
-                    match read_result {
-                        Ok(out) => { return Ok(out); },
-                        Err(e) => {
-                            if e == Errno::EAGAIN {
-                                continue;
-                            }
-                            println!("read frame failed in select [FAILED]");
-                            return Err(e);
-                        }
-                    }
+    /// Waits for the next frame and copies it out, failing with `Timeout` if
+    /// none arrives within `read_timeout`. A lost device fails with `DeviceLost`,
+    /// and is opened again first if set to, see `set_reconnect`.
+    pub fn read(&mut self) -> Result<Frame, CaptureError> {
+        if !self.is_open {
+            return Err(CaptureError::Closed);
+        }
+        if let Some(playback) = &mut self.playback {
+            return match playback.next_frame() {
+                Ok(Some(frame)) => {
+                    self.drops.record(frame.sequence());
+                    Ok(frame)
+                }
+                // Played to the end, as if the camera went away.
+                Ok(None) => Err(CaptureError::DeviceLost),
+                Err(e) => Err(CaptureError::Io(e)),
+            };
+        }
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+        self.reconnect_if_lost(deadline)?;
+        loop {
+            self.wait_readable(deadline).map_err(|e| self.lost(e))?;
+            // Copied again, as the frame outlives the next read.
+            let read_result = self.read_frame().map(<[u8]>::to_vec);
+            match read_result {
+                Ok(out) => {
+                    let frame = self.to_frame(out)?;
+                    self.drops.record(frame.sequence());
+                    return Ok(frame);
                 },
-                Err(e) => {
-                    if e == Errno::EINTR {
-                        continue;
-                    }
-                    println!("select failed [FAILED]");
-                    return Err(e);
+                Err(e) if e.errno() == Some(Errno::EAGAIN) => continue,
+                Err(e) => return Err(self.lost(e)),
+            }
+        }
+    }
+
+    /// Waits for the next frame and lends it where the driver put it, without
+    /// copying. The buffer goes back to the driver when the guard is dropped,
+    /// hold it no longer than the other buffers take to fill. A virtual camera
+    /// lends the frame it played back.
+    pub fn read_mapped(&mut self) -> Result<FrameGuard<'_>, CaptureError> {
+        if !self.is_open {
+            return Err(CaptureError::Closed);
+        }
+        if self.playback.is_some() {
+            let frame = self.read()?;
+            return Ok(FrameGuard {
+                format: frame.format(),
+                size: frame.size(),
+                stride: frame.stride(),
+                lent: Lent::Owned(frame),
+                capture: self,
+            });
+        }
+        let deadline = self.read_timeout.map(|timeout| Instant::now() + timeout);
+        self.reconnect_if_lost(deadline)?;
+        let index = loop {
+            self.wait_readable(deadline).map_err(|e| self.lost(e))?;
+            match self.dequeue() {
+                Ok(index) => break index,
+                Err(e) if e.errno() == Some(Errno::EAGAIN) => continue,
+                Err(e) => return Err(self.lost(e)),
+            }
+        };
+        self.dequeued = self.buffers[index].buffer;
+
+        let Fmt::Pix(pix_format) = self.format.fmt;
+        let Some(format) = PixelFormat::from_fourcc(pix_format.pixelformat) else {
+            self.release(index)?;
+            return Err(CaptureError::UnsupportedFormat(pix_format.pixelformat));
+        };
+        self.drops.record(self.dequeued.sequence as u64);
+        let min_stride = format.min_stride(pix_format.width).unwrap_or(0);
+        Ok(FrameGuard {
+            format,
+            size: [pix_format.width, pix_format.height],
+            stride: (pix_format.bytesperline as usize).max(min_stride),
+            lent: Lent::Mapped(index),
+            capture: self,
+        })
+    }
+
+    // Blocks until the device has a frame, failing with `Timeout` once past
+    // `deadline`. Retries after a spurious wake up wait out what is left of it.
+    fn wait_readable(&self, deadline: Option<Instant>) -> Result<(), CaptureError> {
+        loop {
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    left.as_micros().div_ceil(1000).min(c_int::MAX as u128) as c_int
                 }
+                None => -1,
+            };
+            let mut fds = [PollFd::new(self.raw_fd, PollFlags::POLLIN)];
+
+            match poll::poll(&mut fds, timeout) {
+                Ok(0) => return Err(self.timed_out()),
+                Ok(_) => {}
+                Err(Errno::EINTR) => continue,
+                Err(e) => return Err(CaptureError::Io(Error::from(e))),
+            }
+            let revents = fds[0].revents().unwrap_or(PollFlags::empty());
+            if revents.intersects(PollFlags::POLLHUP | PollFlags::POLLNVAL) {
+                return Err(CaptureError::DeviceLost);
             }
+            // Drivers also raise errors for a queue streaming on another file,
+            // as the camdriver module's is, so only a device that no longer
+            // answers counts as lost. Reading reports any other failure. With
+            // camdriver that is every wait, reading blocks in the module instead,
+            // see `set_read_timeout`.
+            if revents.contains(PollFlags::POLLERR) && self.device_gone() {
+                return Err(CaptureError::DeviceLost);
+            }
+            return Ok(());
         }
     }
 
-    pub fn read_out_data(&self, start: u64, size: u32) -> Vec<u8> {
-        let size = size as usize;
-        let mut out = vec![0u8; size];
-        let src = start as *mut c_int as *mut c_void;
-        unsafe {
-            ptr::copy_nonoverlapping(
-                src, 
-                out.as_mut_ptr() as *mut c_void, 
-                size
-            )
+    fn device_gone(&self) -> bool {
+        let mut cap = v4l2_capability::default();
+        let result = unsafe { ioctl::querycap(self.raw_fd, &mut cap) };
+        result == Err(Errno::ENODEV)
+    }
+}
+
+enum Lent {
+    Mapped(usize),
+    Owned(Frame),
+}
+
+/// A frame lent by `VideoCapture::read_mapped`. Derefs to its bytes, which
+/// for a device are the mapped buffer itself, and requeues the buffer on drop.
+pub struct FrameGuard<'a> {
+    capture: &'a mut VideoCapture,
+    lent: Lent,
+    format: PixelFormat,
+    size: [u32; 2],
+    stride: usize,
+}
+
+impl FrameGuard<'_> {
+    /// Index of the lent buffer, `None` for a virtual camera's frame.
+    pub fn index(&self) -> Option<usize> {
+        match self.lent {
+            Lent::Mapped(index) => Some(index),
+            Lent::Owned(_) => None,
         }
-        out
     }
-    
-    pub fn write_to_file(&self, start: u64, size: u32) {
-        let size = size as usize;
 
-        let file = File::options()
-                                .write(true)
-                                .read(true)
-                                .create(true)
-                                .open("result_frame")
-                                .unwrap();
-        unsafe {
-            let data = start as *mut c_int as *mut c_void;
-            let result = libc::write(
-                file.as_raw_fd(), 
-                data, 
-                size
-            );
-            println!("Write data with len: {}", result);
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// `[width, height]`.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    /// Bytes between the starts of two rows, 0 for compressed formats.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn capture_info(&self) -> Option<CaptureInfo> {
+        match &self.lent {
+            Lent::Mapped(_) => Some(self.capture.capture_info()),
+            Lent::Owned(frame) => frame.capture_info(),
+        }
+    }
+
+    /// Copies the frame out, to keep it past the guard.
+    pub fn to_frame(&self) -> Result<Frame, CaptureError> {
+        match &self.lent {
+            Lent::Mapped(_) => self.capture.to_frame(self.to_vec()),
+            Lent::Owned(frame) => Ok(frame.clone()),
+        }
+    }
+}
+
+impl std::ops::Deref for FrameGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.lent {
+            Lent::Mapped(index) => self.capture.held(*index).unwrap_or(&[]),
+            Lent::Owned(frame) => frame.data(),
+        }
+    }
+}
+
+impl Drop for FrameGuard<'_> {
+    fn drop(&mut self) {
+        if let Lent::Mapped(index) = self.lent {
+            if let Err(e) = self.capture.release(index) {
+                eprintln!("Failed to requeue buffer {}. [FAILED] {}", index, e);
+            }
+        }
+    }
+}
+
+/// Frames as `read` returns them, ending once the device is lost for good, a
+/// virtual camera played to its end, or the capture closed. Timeouts and other
+/// failures are yielded, iterating further tries again.
+impl Iterator for VideoCapture {
+    type Item = Result<Frame, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.read() {
+            Err(CaptureError::DeviceLost) if self.is_reconnecting() => {
+                Some(Err(CaptureError::DeviceLost))
+            }
+            Err(CaptureError::DeviceLost | CaptureError::Closed) => None,
+            result => Some(result),
         }
     }
 }
 
 impl Drop for VideoCapture {
     fn drop(&mut self) {
-        self.stop_stream();
-        for buffer in &self.buffers {
-            let mem = buffer.memories;
-            unsafe {
-                let result = mman::munmap(
-                    mem.start as *mut c_int as *mut c_void, 
-                    mem.length
-                );
-                match result {
-                    Ok(()) => {},
-                    Err(e) => {
-                        println!("Failed to munmap. [FAILED] {}", e);
+        if let Err(e) = self.close() {
+            eprintln!("Failed to stop stream. [FAILED] {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::sync::{Arc, Mutex};
+
+    use shared::playback::PlaybackSource;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    #[test]
+    fn reconnect_delays_double_up_to_the_maximum() {
+        let policy = ReconnectPolicy {
+            initial_delay: 10 * MS,
+            max_delay: 75 * MS,
+            max_attempts: None,
+        };
+        let delays: Vec<Duration> = (0..6).map(|failed| policy.delay(failed)).collect();
+        assert_eq!(delays, [10 * MS, 20 * MS, 40 * MS, 75 * MS, 75 * MS, 75 * MS]);
+        assert_eq!(policy.delay(u32::MAX), 75 * MS);
+
+        let unbounded = ReconnectPolicy { max_delay: Duration::MAX, ..policy };
+        assert_eq!(unbounded.delay(16), unbounded.delay(u32::MAX));
+        assert_eq!(ReconnectPolicy::default().delay(0), 500 * MS);
+    }
+
+    #[test]
+    fn reconnect_gives_up_after_max_attempts() {
+        let forever = ReconnectPolicy::default();
+        assert!(!forever.gives_up(0) && !forever.gives_up(u32::MAX));
+        let policy = ReconnectPolicy { max_attempts: Some(3), ..forever };
+        assert!((0..3).all(|failed| !policy.gives_up(failed)));
+        assert!(policy.gives_up(3) && policy.gives_up(4));
+        let never = ReconnectPolicy { max_attempts: Some(0), ..forever };
+        assert!(never.gives_up(0));
+    }
+
+    // What a listener saw, without the errors themselves.
+    #[derive(Debug, PartialEq)]
+    enum Seen {
+        Disconnected,
+        Failed { attempt: u32, opened: bool, retry_in: Option<Duration> },
+        Reconnected,
+    }
+
+    fn scratch_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("{}-{}", name, std::process::id()))
+    }
+
+    // A plain file stands in for the device. It opens like one, but answers
+    // no ioctl, so reconnecting fails once it is there again.
+    #[test]
+    fn lost_devices_are_retried_and_listeners_told() {
+        let path = scratch_path("fake-video");
+        fs::write(&path, b"").unwrap();
+        let mut cam = VideoCapture::open(&path, CaptureBackend::Direct).unwrap();
+        cam.set_reconnect(Some(ReconnectPolicy {
+            initial_delay: 5 * MS,
+            max_delay: 12 * MS,
+            max_attempts: Some(3),
+        }));
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        cam.add_listener({
+            let (seen, path) = (Arc::clone(&seen), path.clone());
+            move |event| {
+                let event = match event {
+                    CaptureEvent::Disconnected => Seen::Disconnected,
+                    CaptureEvent::ReconnectFailed { attempt, error, retry_in } => {
+                        // Back after the first attempt.
+                        fs::write(&path, b"").unwrap();
+                        let opened = !matches!(error, CaptureError::Open { .. });
+                        Seen::Failed { attempt: *attempt, opened, retry_in: *retry_in }
                     }
-                }
+                    CaptureEvent::Reconnected(_) => Seen::Reconnected,
+                };
+                seen.lock().unwrap().push(event);
             }
-        }
+        });
+
+        // Unplugged, as `wait_readable` reports it.
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(cam.lost(CaptureError::DeviceLost), CaptureError::DeviceLost));
+        assert_eq!(*seen.lock().unwrap(), [Seen::Disconnected]);
+        assert!(cam.is_reconnecting());
+        assert_eq!(cam.raw_fd, -1);
+
+        // Reads time out until the first attempt is due.
+        cam.set_read_timeout(Some(MS)).unwrap();
+        assert!(matches!(cam.read(), Err(CaptureError::Timeout(_))));
+        assert_eq!(seen.lock().unwrap().len(), 1);
+
+        cam.set_read_timeout(None).unwrap();
+        assert!(matches!(cam.read(), Err(CaptureError::DeviceLost)));
+        assert_eq!(*seen.lock().unwrap(), [
+            Seen::Disconnected,
+            Seen::Failed { attempt: 1, opened: false, retry_in: Some(10 * MS) },
+            Seen::Failed { attempt: 2, opened: true, retry_in: Some(12 * MS) },
+            Seen::Failed { attempt: 3, opened: true, retry_in: None },
+        ]);
+        assert!(!cam.is_reconnecting());
+        assert_eq!(cam.raw_fd, -1);
+
+        // Given up for good, nothing more is tried.
+        assert!(matches!(cam.read(), Err(CaptureError::DeviceLost)));
+        assert_eq!(seen.lock().unwrap().len(), 4);
+        drop(cam);
+        let _ = fs::remove_file(&path);
+    }
 
-        println!("munmap. [OK]");
+    #[test]
+    fn other_failures_and_virtual_cameras_never_reconnect() {
+        let path = scratch_path("fake-video-errors");
+        fs::write(&path, b"").unwrap();
+        let mut cam = VideoCapture::open(&path, CaptureBackend::Direct).unwrap();
+        cam.set_reconnect(Some(ReconnectPolicy::default()));
+        let timeout = cam.lost(CaptureError::Timeout(MS));
+        assert!(matches!(timeout, CaptureError::Timeout(_)));
+        assert!(!cam.is_reconnecting());
+        assert_ne!(cam.raw_fd, -1);
+        // Nothing streams on the stand-in, so closing fails to stop it.
+        assert!(matches!(cam.close(), Err(CaptureError::Ioctl { .. })));
+        assert!(matches!(cam.read(), Err(CaptureError::Closed)));
+        drop(cam);
+        let _ = fs::remove_file(&path);
+
+        let recording = scratch_path("fake_4x2.yuyv");
+        fs::write(&recording, [128u8; 4 * 2 * 2]).unwrap();
+        let source = PlaybackSource::Recording { path: recording.clone(), size: [4, 2] };
+        let playback = Playback::open(source, Colorimetry::default()).unwrap().with_fps(0);
+        let mut cam = VideoCapture::with_playback(playback);
+        cam.set_reconnect(Some(ReconnectPolicy::default()));
+        let events = Arc::new(Mutex::new(0));
+        cam.add_listener({
+            let events = Arc::clone(&events);
+            move |_| *events.lock().unwrap() += 1
+        });
+        assert!(cam.read().is_ok());
+        // Played to its end, as if the camera went away.
+        assert!(matches!(cam.read(), Err(CaptureError::DeviceLost)));
+        assert!(!cam.is_reconnecting());
+        assert_eq!(*events.lock().unwrap(), 0);
+        let _ = fs::remove_file(&recording);
+    }
+
+    #[test]
+    fn camdriver_reads_refuse_a_timeout() {
+        let path = scratch_path("fake-video-camdriver");
+        fs::write(&path, b"").unwrap();
+        // Opened directly, the module is not there to be opened too.
+        let mut cam = VideoCapture::open(&path, CaptureBackend::Direct).unwrap();
+        cam.backend = CaptureBackend::Camdriver;
+        cam.set_read_timeout(None).unwrap();
+        let Err(CaptureError::Io(e)) = cam.set_read_timeout(Some(MS)) else {
+            panic!("camdriver took a read timeout");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(cam.read_timeout(), None);
+        drop(cam);
+        let _ = fs::remove_file(&path);
     }
 }
@@ -8,6 +8,7 @@ use kernel::{
     io_buffer::{IoBufferReader, IoBufferWriter}, 
     miscdev,
     sync::{Ref, RefBorrow, smutex::Mutex},
+    user_ptr::UserSlicePtr,
 };
 use core::ffi::{self, c_int, c_void, c_ulong};
 use core::result::Result::Ok;
@@ -220,6 +221,36 @@ pub struct v4l2_requestbuffers {
     pub reserved: [u32; 1]
 }
 
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_queryctrl {
+    pub id: u32,
+    pub r#type: u32,
+    pub name: [u8; 32],
+    pub minimum: i32,
+    pub maximum: i32,
+    pub step: i32,
+    pub default_value: i32,
+    pub flags: u32,
+    pub reserved: [u32; 2]
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_querymenu {
+    pub id: u32,
+    pub index: u32,
+    pub name: [u8; 32],
+    pub reserved: u32
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct v4l2_control {
+    pub id: u32,
+    pub value: i32
+}
+
 const V4L2_BUF_TYPE_VIDEO_CAPTURE: u32 = 1;
 const V4L2_MEMORY_MMAP: u32 = 1;
 
@@ -358,7 +389,53 @@ const dqbuf_cmd: u32 = _IOWR(
     size_of::<v4l2_buffer>() as u32
 );
 
+const gctrl_cmd: u32 = _IOWR(
+    'V' as u32, 
+    27 as u32, 
+    size_of::<v4l2_control>() as u32
+);
+
+const sctrl_cmd: u32 = _IOWR(
+    'V' as u32, 
+    28 as u32, 
+    size_of::<v4l2_control>() as u32
+);
+
+const queryctrl_cmd: u32 = _IOWR(
+    'V' as u32, 
+    36 as u32, 
+    size_of::<v4l2_queryctrl>() as u32
+);
+
+const querymenu_cmd: u32 = _IOWR(
+    'V' as u32, 
+    37 as u32, 
+    size_of::<v4l2_querymenu>() as u32
+);
+
 const MEM_SIZE: usize = 512 * 1024;
+const DEFAULT_VIDEO_PATH: &str = "/dev/video0";
+// Longest device path cmd_type 3 accepts.
+const MAX_PATH_LEN: usize = 256;
+
+// Opens a video device for the ioctls, returning its `*mut bindings::file`.
+fn open_video(path: &str) -> Result<u64> {
+    let video_path = CString::try_from_fmt(fmt!("{}", path))?;
+    let filp = unsafe {
+        filp_open(
+            video_path.as_char_ptr(),
+            O_RDWR.try_into().unwrap(),
+            0
+        )
+    };
+    // On failure the pointer holds a negative errno.
+    let value = filp as isize;
+    if (-4095..0).contains(&value) {
+        pr_alert!("Failed to open {}. ecode: {}\n", path, value);
+        return Err(Error::from_kernel_errno(value as c_int));
+    }
+    Ok(filp as u64)
+}
 
 struct SharedMemSpace {
     mem: Mutex<Vec<u8>>,
@@ -366,22 +443,29 @@ struct SharedMemSpace {
     buffers: Mutex<Vec<Vec<u64>>>,
     write_info: Mutex<bool>,
     info_uaddr: Mutex<c_ulong>,
-    fd: u64,
+    // The open video device, 0 when none could be opened.
+    fd: Mutex<u64>,
     cap: Mutex<c_ulong>,
     format: Mutex<c_ulong>,
     streamparm: Mutex<c_ulong>,
     reqbuffers: Mutex<c_ulong>,
     bufs: Mutex<c_ulong>,
     start_cap_type: Mutex<c_ulong>,
-    stop_cap_type: Mutex<c_ulong>
+    stop_cap_type: Mutex<c_ulong>,
+    queryctrl: Mutex<c_ulong>,
+    querymenu: Mutex<c_ulong>,
+    control: Mutex<c_ulong>
 }
 
 impl Drop for SharedMemSpace {
     fn drop(&mut self) {
-        unsafe {
-            filp_close(
-                self.fd as *mut bindings::file, ptr::null_mut()
-            );
+        let fd = *self.fd.lock();
+        if fd != 0 {
+            unsafe {
+                filp_close(
+                    fd as *mut bindings::file, ptr::null_mut()
+                );
+            }
         }
         pr_info!("Dropped SharedMemSpace\n");
     }
@@ -394,19 +478,9 @@ impl SharedMemSpace {
             mem_space.try_push(0)?;
         }
 
-        let video_path = CString::try_from_fmt(
-            fmt!("/dev/video0")
-        ).unwrap();
-
-        let fd_mut_ptr = unsafe { 
-            filp_open(
-                video_path.as_char_ptr(),
-                O_RDWR.try_into().unwrap(), 
-                0
-            ) 
-        };
-
-        pr_info!("init fd ptr: {:?}\n", fd_mut_ptr);
+        // A client can still open another device later with cmd_type 3.
+        let fd = open_video(DEFAULT_VIDEO_PATH).unwrap_or(0);
+        pr_info!("init fd ptr: {:#x}\n", fd);
         
         Ref::try_new(SharedMemSpace {
             mem: Mutex::new(mem_space),
@@ -414,18 +488,54 @@ impl SharedMemSpace {
             buffers: Mutex::new(Vec::new()),
             write_info: Mutex::new(false),
             info_uaddr: Mutex::new(0),
-            fd: fd_mut_ptr as *const _ as u64,
+            fd: Mutex::new(fd),
             cap: Mutex::new(0),
             format: Mutex::new(0),
             streamparm: Mutex::new(0),
             reqbuffers: Mutex::new(0),
             bufs: Mutex::new(0),
             start_cap_type: Mutex::new(0),
-            stop_cap_type: Mutex::new(0)
+            stop_cap_type: Mutex::new(0),
+            queryctrl: Mutex::new(0),
+            querymenu: Mutex::new(0),
+            control: Mutex::new(0)
         })
     }
 }
 
+// Closes a device gone from under us, unplugged or reset, rather than keep
+// issuing ioctls on its stale file. The client opens it anew with cmd_type 3.
+fn forget_device(shared: &SharedMemSpace) {
+    let mut fd = shared.fd.lock();
+    if *fd != 0 {
+        pr_alert!("Video device lost, closing it.\n");
+        unsafe {
+            filp_close(*fd as *mut bindings::file, ptr::null_mut());
+        }
+        *fd = 0;
+    }
+    // Pfns were those of the lost device's buffers.
+    shared.buffers.lock().clear();
+}
+
+// Copies the whole `v4l2_buffer` DQBUF filled in out of client memory, for
+// its index and bytesused and to put it back once QBUF has overwritten it.
+fn read_dequeued(uaddr: c_ulong) -> Result<[u8; size_of::<v4l2_buffer>()]> {
+    let mut buffer = [0u8; size_of::<v4l2_buffer>()];
+    unsafe { UserSlicePtr::new(uaddr as *mut c_void, buffer.len()) }
+        .reader()
+        .read_slice(&mut buffer)?;
+    Ok(buffer)
+}
+
+// Puts back what DQBUF reported, so the client sees the timestamp, sequence
+// and bytesused of its frame rather than what QBUF left.
+fn restore_dequeued(uaddr: c_ulong, buffer: &[u8]) -> Result {
+    unsafe { UserSlicePtr::new(uaddr as *mut c_void, buffer.len()) }
+        .writer()
+        .write_slice(buffer)
+}
+
 struct WrappedData {
     mem_space: Ref<SharedMemSpace>,
 }
@@ -454,39 +564,61 @@ impl file::Operations for RustFile {
         let shared = shared.mem_space.clone();
         let mut total_len: usize = 0;
 
-        let fd = (shared.fd) as *mut bindings::file;
+        let fd = *shared.fd.lock() as *mut bindings::file;
+        if fd.is_null() {
+            return Err(ENODEV);
+        }
         let buf_addr = shared.bufs.lock();
         let result = unsafe { 
             bindings::vfs_ioctl(fd, dqbuf_cmd, *buf_addr) 
         };
         if result < 0 {
             pr_alert!("Failed to dqbuf. ecode: {}\n", result);
-        } else {
-            let buf_idx: usize = 0;
-
+            // Nothing was dequeued, the client gets to tell why.
+            let error = Error::from_kernel_errno(result as c_int);
+            drop(buf_addr);
+            if error == ENODEV {
+                forget_device(&shared);
+            }
+            return Err(error);
+        }
+        let dequeued = read_dequeued(*buf_addr).ok();
+        let mut copied = Ok(());
+        {
+            // Nothing is copied for an unknown buffer, but it still gets queued again.
+            let buf_idx = dequeued
+                .map(|buffer| u32::from_ne_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]))
+                .map_or(usize::MAX, |index| index as usize);
+            let no_pfns = Vec::new();
             let buffers = shared.buffers.lock();
-            let buffer_to_read: &Vec<u64> = &buffers[buf_idx];
-            let psize = PAGE_SIZE as u32;
-            let vec_size = buffer_to_read.len() * (psize as usize);
-            let mut out = Vec::<u8>::new();
-            out.try_resize(vec_size, 0u8)?;
-            
-            let mut written: usize = 0;
+            let buffer_to_read: &Vec<u64> = buffers.get(buf_idx).unwrap_or_else(|| {
+                pr_alert!("No pfns for dequeued buffer {}.\n", buf_idx);
+                &no_pfns
+            });
+            // Page by page straight into the reader's buffer, no further than
+            // the frame reaches.
+            let bytes_used = dequeued.map_or(0, |buffer| {
+                u32::from_ne_bytes([buffer[8], buffer[9], buffer[10], buffer[11]])
+            });
+            let mut remaining = match bytes_used {
+                0 => data.len(),
+                bytes_used => min(bytes_used as usize, data.len()),
+            };
             for pfn in buffer_to_read {
-                let size_to_write = psize as usize;
+                if remaining == 0 {
+                    break;
+                }
+                let size_to_write = min(PAGE_SIZE, remaining);
                 let kaddr = pfn_to_virt(*pfn as i64);
 
-                unsafe {
-                    ptr::copy_nonoverlapping(
-                        kaddr, 
-                        out[written..(written + size_to_write)].as_mut_ptr() as *mut c_void, 
-                        size_to_write
-                    );
+                if let Err(e) = unsafe { data.write_raw(kaddr as *const u8, size_to_write) } {
+                    pr_alert!("Failed to copy out buffer {}.\n", buf_idx);
+                    copied = Err(e);
+                    break;
                 }
-                written += size_to_write;
+                remaining -= size_to_write;
+                total_len += size_to_write;
             }
-            total_len = written;
-            data.write_slice(&out.as_slice())?;
         }
 
         let result = unsafe { 
@@ -496,6 +628,10 @@ impl file::Operations for RustFile {
         if result < 0 {
             pr_alert!("qbuf failed. ecode: {}\n", result);
         }
+        if let Some(buffer) = dequeued {
+            restore_dequeued(*buf_addr, &buffer)?;
+        }
+        copied?;
 
         Ok(total_len)
     }
@@ -541,6 +677,12 @@ impl file::Operations for RustFile {
                 (*shared.start_cap_type.lock()) = uaddr;
             } else if set_type == 6 { // set stop_cap_type
                 (*shared.stop_cap_type.lock()) = uaddr;
+            } else if set_type == 7 { // set queryctrl
+                (*shared.queryctrl.lock()) = uaddr;
+            } else if set_type == 8 { // set querymenu
+                (*shared.querymenu.lock()) = uaddr;
+            } else if set_type == 9 { // set control
+                (*shared.control.lock()) = uaddr;
             } else {
                 pr_info!("Undentified type detected. {}\n", set_type);
             }
@@ -567,7 +709,10 @@ impl file::Operations for RustFile {
             let buffers_0_size = shared.buffers.lock()[0].len();
         } else if cmd_type == 2 { // prep driver
             pr_info!("In cmd_type 2.\n");
-            let fd = (shared.fd) as *mut bindings::file;
+            let fd = *shared.fd.lock() as *mut bindings::file;
+            if fd.is_null() {
+                return Err(ENODEV);
+            }
 
             let mut io_type_buf = [0u8; 8];
             data.read_slice(&mut io_type_buf[..])?;
@@ -704,7 +849,52 @@ impl file::Operations for RustFile {
                 } else {
                     pr_info!("Success to stream off.\n");
                 }
+            } else if (10..=14).contains(&io_type) {
+                // ----- CONTROLS, DQBUF ----- //
+                // Failures are returned, the client tells a missing control
+                // from a refused value, or an empty queue, by them. A buffer
+                // dequeued here is read through the client's own mapping and
+                // queued again with io_type 7.
+                let (cmd, addr) = match io_type {
+                    10 => (queryctrl_cmd, *shared.queryctrl.lock()),
+                    11 => (querymenu_cmd, *shared.querymenu.lock()),
+                    12 => (gctrl_cmd, *shared.control.lock()),
+                    13 => (sctrl_cmd, *shared.control.lock()),
+                    _ => (dqbuf_cmd, *shared.bufs.lock()),
+                };
+
+                let result = unsafe { 
+                    bindings::vfs_ioctl(fd, cmd, addr) 
+                };
+
+                if result < 0 {
+                    let error = Error::from_kernel_errno(result as c_int);
+                    if error == ENODEV {
+                        forget_device(&shared);
+                    }
+                    return Err(error);
+                }
+            }
+        } else if cmd_type == 3 { // open device
+            let path_len = len - 8;
+            if path_len == 0 || path_len > MAX_PATH_LEN {
+                return Err(EINVAL);
+            }
+            let mut path_buf = [0u8; MAX_PATH_LEN];
+            data.read_slice(&mut path_buf[..path_len])?;
+            let path = core::str::from_utf8(&path_buf[..path_len]).map_err(|_| EINVAL)?;
+            pr_info!("Opening {}.\n", path);
+
+            let new_fd = open_video(path)?;
+            let mut fd = shared.fd.lock();
+            if *fd != 0 {
+                unsafe {
+                    filp_close(*fd as *mut bindings::file, ptr::null_mut());
+                }
             }
+            *fd = new_fd;
+            // Pfns were those of the previous device's buffers.
+            shared.buffers.lock().clear();
         }
         Ok(len)
     }
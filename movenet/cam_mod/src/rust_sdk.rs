@@ -1,4 +1,5 @@
 use kernel::prelude::*;
+use kernel::error::code::EINVAL;
 use kernel::sync::smutex::Mutex;
 use core::result::Result::Ok;
 use kernel::net::{
@@ -66,6 +67,19 @@ impl RustSdk {
 
         let stream = listener.accept(false)?;
 
+        // Handshake: propose the raw (YUYV) payload encoding, no parameter.
+        stream.write(&[0u8, 0u8], true)?;
+        let mut status = [0u8; 1];
+        stream.read(&mut status, true)?;
+        if status[0] != 0 {
+            pr_alert!("Server rejected the payload encoding.\n");
+            return Err(EINVAL);
+        }
+        // Model input size advertised by the server, unused as frames are
+        // always preprocessed server-side.
+        let mut input_size = [0u8; 8];
+        stream.read(&mut input_size, true)?;
+
         stream.write(&time_to_send.to_be_bytes(), true)?;
 
         stream.write(
@@ -77,6 +91,12 @@ impl RustSdk {
             frame_size[1].to_be_bytes().as_slice(),
             true
         )?;
+
+        // Pixel format: YUYV, as captured, BT.601 limited range. Not preprocessed,
+        // mirrored and upright for the model.
+        stream.write(&0x56595559u32.to_be_bytes(), true)?;
+        stream.write(&[0u8, 0u8], true)?;
+        stream.write(&[0b10u8], true)?;
         
         stream.write(
             (data.len() as u64).to_be_bytes().as_slice(),
@@ -88,6 +108,16 @@ impl RustSdk {
             true
         )?;
         
+        // Response status: 0 = keypoints, 1 = dropped, 2 = bad request.
+        let mut response_status = [0u8; 1];
+        stream.read(&mut response_status, true)?;
+        if response_status[0] == 1 {
+            return Ok(Vec::new());
+        } else if response_status[0] != 0 {
+            pr_alert!("Server rejected the frame. status: {}\n", response_status[0]);
+            return Err(EINVAL);
+        }
+
         let mut len_to_read = [0u8; 8];
         stream.read(&mut len_to_read, true)?;
         // Number of elements of an array of f32 data.
@@ -1,22 +1,49 @@
 //! Provides a interface for communicating with server-side application.
 
 use std::net::{TcpStream, SocketAddr, AddrParseError};
-use std::io::prelude::*;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{self, prelude::*};
+use std::time::Instant;
+use std::sync::Mutex;
 use std::fs;
 
+use shared::codec;
+use shared::format::{Orientation, PixelFormat};
+use shared::frame::Frame;
+use shared::metrics::CodecStats;
+use shared::preprocess;
+use shared::protocol::{self, FrameHeader, PayloadEncoding, Response, SessionInfo};
+
 use crate::error::RecogError;
 
 const ENV_FILE_PATH: &str = "moveneter_sdk/env";
 
+/// Where the frame is turned into the model input.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Preprocessing {
+    /// Ship the full captured frame, the server converts and letterboxes it.
+    #[default]
+    Server,
+    /// Convert, orient and letterbox to the input size advertised by the
+    /// server before uploading. Cuts the upload to the model input size.
+    Client,
+}
+
 pub struct Recognizer {
     socket_addr: SocketAddr,
+    encoding: PayloadEncoding,
+    preprocessing: Preprocessing,
+    orientation: Orientation,
+    metrics: Mutex<CodecStats>,
 }
 
 impl Recognizer {
     pub fn try_new_with(addr: &str) -> Result<Self, AddrParseError> {
         Ok(Recognizer { 
             socket_addr: addr.parse()?,
+            encoding: PayloadEncoding::default(),
+            preprocessing: Preprocessing::default(),
+            orientation: Orientation::MIRRORED,
+            metrics: Mutex::new(CodecStats::new()),
         })
     }
 
@@ -24,66 +51,150 @@ impl Recognizer {
         let socket_addr = fs::read_to_string(ENV_FILE_PATH).unwrap();
         let socket_addr = socket_addr.trim();
         let socket_addr: SocketAddr = socket_addr.parse()?;
-        Ok(Recognizer { socket_addr })
+        Ok(Recognizer {
+            socket_addr,
+            encoding: PayloadEncoding::default(),
+            preprocessing: Preprocessing::default(),
+            orientation: Orientation::MIRRORED,
+            metrics: Mutex::new(CodecStats::new()),
+        })
+    }
+
+    /// Selects the payload encoding proposed to the server on every connection.
+    pub fn with_encoding(mut self, encoding: PayloadEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
+    pub fn encoding(&self) -> PayloadEncoding {
+        self.encoding
+    }
+
+    /// Selects whether frames are preprocessed here or on the server.
+    pub fn with_preprocessing(mut self, preprocessing: Preprocessing) -> Self {
+        self.preprocessing = preprocessing;
+        self
+    }
+
+    pub fn preprocessing(&self) -> Preprocessing {
+        self.preprocessing
+    }
+
+    /// Selects how frames are rotated and mirrored before they reach the model.
+    /// Keypoints always come back in the coordinates of the captured frame.
+    pub fn with_orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Encoding statistics accumulated over all frames sent so far.
+    pub fn metrics(&self) -> CodecStats {
+        *self.metrics.lock().unwrap()
     }
 
-    fn prepare_timestamp() -> u128 {
-        let time = SystemTime::now();
-        let since_the_epoch = time
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
-        since_the_epoch.as_millis()
+    /// Produces the oriented, letterboxed model input. YUYV frames are sampled
+    /// directly, other formats go through a full RGB frame first.
+    fn letterbox(&self, frame: &Frame, data: &[u8], input_size: [u32; 2]) -> io::Result<Vec<u8>> {
+        let [width, height] = frame.size();
+        if frame.format() == PixelFormat::Yuyv && width.is_multiple_of(2) {
+            let mut input = vec![0u8; input_size[0] as usize * input_size[1] as usize * 3];
+            preprocess::yuyv_to_tensor(
+                data, width, height, frame.colorimetry(), input_size, self.orientation, &mut input
+            );
+            return Ok(input);
+        }
+        let rgb = codec::to_rgb(data, width, height, frame.format(), frame.colorimetry())?;
+        Ok(preprocess::resize_with_padding(&rgb, width, height, input_size, self.orientation))
     }
 
-    pub fn detect(&self, data: &Vec<u8>, frame_size: [u32; 2]) -> Result<Vec<f32>, RecogError> {
-        let mut msg = "";
-        
-        let time_to_send = Recognizer::prepare_timestamp();
+    /// Packs the frame for the wire, preprocessing it first when that is done
+    /// on the client. Returns the header describing the payload alongside it.
+    fn encode(
+        &self, frame: &Frame, session: SessionInfo
+    ) -> Result<(FrameHeader, Vec<u8>), RecogError> {
+        let start = Instant::now();
+        let data = frame.packed();
+        let encoded = match self.preprocessing {
+            Preprocessing::Server => {
+                let [width, height] = frame.size();
+                codec::encode(
+                    self.encoding, &data, width, height, frame.format(), frame.colorimetry()
+                ).map(|(payload, format)| (frame.size(), false, payload, format))
+            }
+            Preprocessing::Client => {
+                self.letterbox(frame, &data, session.input_size).and_then(|input| {
+                    let [width, height] = session.input_size;
+                    codec::encode(
+                        self.encoding, &input, width, height, PixelFormat::Rgb24, frame.colorimetry()
+                    ).map(|(payload, format)| (session.input_size, true, payload, format))
+                })
+            }
+        };
+        let (size, preprocessed, payload, format) = encoded
+            .map_err(|e| RecogError::new(&format!("Failed to encode frame. {}", e)))?;
+        self.metrics.lock().unwrap().record(data.len(), payload.len(), start.elapsed());
+
+        let header = FrameHeader {
+            timestamp: frame.timestamp(),
+            width: size[0],
+            height: size[1],
+            fourcc: format.fourcc(),
+            colorimetry: frame.colorimetry(),
+            preprocessed,
+            orientation: self.orientation,
+            data_len: payload.len() as u64,
+        };
+        Ok((header, payload))
+    }
+
+    /// Runs detection on a frame, using its capture timestamp to let the
+    /// server drop stale requests.
+    pub fn detect(&self, frame: &Frame) -> Result<Vec<f32>, RecogError> {
+        let msg;
 
         if let Ok(mut stream) = TcpStream::connect(self.socket_addr) {
             stream.set_nodelay(true).unwrap();
 
-            let timestamp_write = stream.write(&time_to_send.to_be_bytes()).is_ok();
+            let session = match protocol::propose_encoding(&mut stream, self.encoding) {
+                Ok(Some(session)) => session,
+                Ok(None) => {
+                    msg = "Server rejected the payload encoding.";
+                    eprintln!("{}", msg);
+                    return Err(RecogError::new(msg));
+                }
+                Err(_) => {
+                    msg = "Failed to negotiate the payload encoding.";
+                    eprintln!("{}", msg);
+                    return Err(RecogError::new(msg));
+                }
+            };
+            let (header, data) = self.encode(frame, session)?;
 
-            let frame_width_write = stream.write(
-                frame_size[0].to_be_bytes().as_slice()
-            ).is_ok();
-            let frame_height_write = stream.write(
-                frame_size[1].to_be_bytes().as_slice()
-            ).is_ok();
-            let frame_size_write = frame_width_write && frame_height_write;
-            
-            let len_write = stream.write(
-                (data.len() as u64).to_be_bytes().as_slice()
-            ).is_ok();
+            let header_write = header.write_to(&mut stream).is_ok();
             let data_write = stream.write_all(
                 data.as_slice()
             ).is_ok();
 
             stream.flush().unwrap();
             
-            if timestamp_write && frame_size_write && len_write && data_write {
-                let mut len_to_read = [0u8; 8];
-                if stream.read_exact(&mut len_to_read).is_ok() {
-                    // Number of elements of an array of f32 data.
-                    let data_len = u64::from_be_bytes(len_to_read) as usize;
-                    let mut data_buf = vec![0u8; data_len * 4];
-                    if stream.read_exact(&mut data_buf).is_ok() {
-                        let mut data_to_process: Vec<f32> = Vec::new();
-                        for i in 0..data_len {
-                            let start_idx = i * 4;
-                            let end_idx = (i + 1) * 4;
-                            data_to_process.push(f32::from_be_bytes(
-                                data_buf[start_idx..end_idx]
-                                    .try_into()
-                                    .unwrap_or_default()
-                            ));
-                        }
-                        return Ok(data_to_process);
+            if header_write && data_write {
+                match Response::read_from(&mut stream) {
+                    Ok(Response::Keypoints(data)) => return Ok(data),
+                    // The server skipped this frame, nothing to report.
+                    Ok(Response::Dropped) => return Ok(Vec::new()),
+                    Ok(Response::BadRequest(reason)) => {
+                        let msg = format!("Server rejected the frame. {}", reason);
+                        eprintln!("{}", msg);
+                        return Err(RecogError::new(&msg));
+                    }
+                    Err(_) => {
+                        msg = "Failed to read out the response.";
+                        eprintln!("{}", msg);
                     }
-                } else {
-                    msg = "Failed to read out output data length.";
-                    eprintln!("{}", msg);
                 }
             } else {
                 msg = "Failed to write data to the server.";
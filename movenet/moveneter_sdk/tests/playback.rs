@@ -0,0 +1,113 @@
+//! Frames of a `Playback` through the `Recognizer`, against a server that
+//! hands what it decoded to the test instead of running a model.
+
+use std::io::{self, Read};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use moveneter_sdk::recognizer::{Preprocessing, Recognizer};
+use shared::codec;
+use shared::format::{Colorimetry, Orientation, PixelFormat, Rotation};
+use shared::frame::Frame;
+use shared::playback::{Playback, PlaybackSource};
+use shared::preprocess;
+use shared::protocol::{self, FrameHeader, PayloadEncoding, Response, SessionInfo};
+use shared::skeleton::SINGLE_POSE_LEN;
+use shared::utils::EasyConverter;
+
+const POSE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../resource/pose.jpg");
+const INPUT_SIZE: [u32; 2] = [192, 192];
+
+// The header of a frame and the frame decoded to RGB, as the server got them.
+type Received = (FrameHeader, Vec<u8>);
+
+// Answers `requests` connections with an empty pose, passing on what each
+// frame arrived as.
+fn fake_server(requests: usize) -> (String, Receiver<Received>, JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let (tx, received) = mpsc::channel();
+    let server = thread::spawn(move || {
+        for stream in listener.incoming().take(requests) {
+            answer(&mut stream.unwrap(), &tx).unwrap();
+        }
+    });
+    (addr, received, server)
+}
+
+fn answer(stream: &mut TcpStream, tx: &Sender<Received>) -> io::Result<()> {
+    let encoding = protocol::accept_encoding(stream, SessionInfo { input_size: INPUT_SIZE })?;
+    let header = FrameHeader::read_from(stream)?;
+    let mut payload = vec![0u8; header.data_len as usize];
+    stream.read_exact(&mut payload)?;
+    let format = PixelFormat::from_fourcc(header.fourcc).unwrap();
+    let rgb = codec::decode(
+        encoding, &payload, header.width, header.height, format, header.colorimetry
+    )?;
+    tx.send((header, rgb)).unwrap();
+    Response::Keypoints(vec![0.0; SINGLE_POSE_LEN]).write_to(stream)
+}
+
+fn max_diff(a: &[u8], b: &[u8]) -> u8 {
+    assert_eq!(a.len(), b.len());
+    a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+}
+
+fn pose() -> Playback {
+    let source = PlaybackSource::from_path(POSE).unwrap();
+    Playback::open(source, Colorimetry::default()).unwrap().with_fps(0)
+}
+
+#[test]
+fn full_frames_reach_the_server_as_captured() {
+    let mut playback = pose();
+    let frames: Vec<Frame> = (0..2).map(|_| playback.next_frame().unwrap().unwrap()).collect();
+    let (addr, received, server) = fake_server(frames.len());
+    let recognizer = Recognizer::try_new_with(&addr).unwrap();
+
+    for frame in &frames {
+        assert_eq!(recognizer.detect(frame).unwrap(), vec![0.0; SINGLE_POSE_LEN]);
+        let (header, rgb) = received.recv().unwrap();
+        assert_eq!([header.width, header.height], playback.size());
+        assert_eq!(header.timestamp, frame.timestamp());
+        assert!(!header.preprocessed);
+        assert_eq!(header.orientation, Orientation::MIRRORED);
+        assert!(rgb == EasyConverter::default().rgb(frame.data()));
+    }
+    server.join().unwrap();
+    assert_eq!(recognizer.metrics().frames, frames.len() as u64);
+}
+
+#[test]
+fn preprocessed_frames_are_the_model_input() {
+    let frame = pose().next_frame().unwrap().unwrap();
+    let [width, height] = frame.size();
+    let orientation = Orientation::new(Rotation::Cw90, true);
+    let mut expected = vec![0u8; (INPUT_SIZE[0] * INPUT_SIZE[1] * 3) as usize];
+    preprocess::yuyv_to_tensor(
+        frame.data(), width, height, frame.colorimetry(), INPUT_SIZE, orientation, &mut expected
+    );
+
+    let encodings = [
+        (PayloadEncoding::Raw, 0),
+        (PayloadEncoding::Zstd { level: 3 }, 0),
+        (PayloadEncoding::Jpeg { quality: 95 }, 24),
+    ];
+    let (addr, received, server) = fake_server(encodings.len());
+    for (encoding, tolerance) in encodings {
+        let recognizer = Recognizer::try_new_with(&addr)
+            .unwrap()
+            .with_encoding(encoding)
+            .with_preprocessing(Preprocessing::Client)
+            .with_orientation(orientation);
+        recognizer.detect(&frame).unwrap();
+        let (header, rgb) = received.recv().unwrap();
+        assert_eq!([header.width, header.height], INPUT_SIZE);
+        assert!(header.preprocessed);
+        assert_eq!(header.orientation, orientation);
+        let diff = max_diff(&rgb, &expected);
+        assert!(diff <= tolerance, "{}: max difference {}", encoding, diff);
+    }
+    server.join().unwrap();
+}
@@ -0,0 +1,61 @@
+//! Server preprocessing of a 1280x720 YUYV frame: the former OpenCV path
+//! against its pure-Rust replacement and the fused `yuyv_to_tensor`.
+//!
+//! Run with `cargo bench -p server --bench letterbox --features opencv`.
+
+use std::hint::black_box;
+
+use server::utils;
+use shared::bench::{bench, synthetic_yuyv};
+use shared::format::{Colorimetry, Orientation};
+use shared::preprocess;
+use shared::utils::EasyConverter;
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+const INPUT_SIZE: [u32; 2] = [192, 192];
+const ORIENTATION: Orientation = Orientation::MIRRORED;
+const ITERATIONS: u32 = 200;
+
+fn opencv_path(converter: &EasyConverter, yuyv: &[u8]) -> Vec<u8> {
+    let mut rgb = converter.rgb(yuyv);
+    let new_shape = [INPUT_SIZE[0] as i32, INPUT_SIZE[1] as i32];
+    utils::resize_with_padding(&mut rgb, HEIGHT as i32, WIDTH as i32, new_shape)
+}
+
+fn rust_path(converter: &EasyConverter, yuyv: &[u8]) -> Vec<u8> {
+    let rgb = converter.rgb(yuyv);
+    preprocess::resize_with_padding(&rgb, WIDTH, HEIGHT, INPUT_SIZE, ORIENTATION)
+}
+
+fn fused_path(yuyv: &[u8], tensor: &mut [u8]) {
+    preprocess::yuyv_to_tensor(
+        yuyv, WIDTH, HEIGHT, Colorimetry::default(), INPUT_SIZE, ORIENTATION, tensor
+    );
+}
+
+fn main() {
+    let yuyv = synthetic_yuyv(WIDTH, HEIGHT);
+    let converter = EasyConverter::default();
+    let mut tensor = vec![0u8; (INPUT_SIZE[0] * INPUT_SIZE[1] * 3) as usize];
+
+    bench("opencv", ITERATIONS, || {
+        black_box(opencv_path(&converter, black_box(&yuyv)));
+    });
+    bench("rust", ITERATIONS, || {
+        black_box(rust_path(&converter, black_box(&yuyv)));
+    });
+    bench("fused", ITERATIONS, || fused_path(black_box(&yuyv), &mut tensor));
+
+    let reference = opencv_path(&converter, &yuyv);
+    fused_path(&yuyv, &mut tensor);
+    for (name, output) in [("rust", rust_path(&converter, &yuyv)), ("fused", tensor)] {
+        let diffs: Vec<u32> = reference.iter().zip(&output).map(|(a, b)| a.abs_diff(*b) as u32).collect();
+        println!(
+            "{} vs opencv: max diff {}, mean {:.3}",
+            name,
+            diffs.iter().max().unwrap(),
+            diffs.iter().sum::<u32>() as f64 / diffs.len() as f64
+        );
+    }
+}
@@ -1 +1,2 @@
-pub mod utils;
\ No newline at end of file
+#[cfg(feature = "opencv")]
+pub mod utils;
@@ -4,14 +4,20 @@ use std::{
     io::{self, prelude::*}, 
     env
 };
-use server::utils;
 use tflitec::interpreter::{Interpreter, Options};
 use shared::threadpool::ThreadPool;
-use std::time::Duration;
+use shared::codec;
+use shared::preprocess;
+use shared::format::{FourCc, PixelFormat};
+use shared::metrics::CodecStats;
+use shared::protocol::{self, FrameHeader, PayloadEncoding, Response, SessionInfo};
+use shared::skeleton;
+use std::time::{Duration, Instant};
 use std::thread;
 use std::cmp::{min, max};
 
 const N_WORKERS: usize = 10;
+const MODEL_PATH: &str = "resource/lite-model_movenet_singlepose_lightning_tflite_int8_4.tflite";
 
 // in millseconds
 static TIME_INTERVAL: Mutex<u128> = Mutex::new(500);
@@ -23,6 +29,11 @@ static LATEST_TIMESTAMP: Mutex<Option<u128>> = Mutex::new(None);
 
 static COUNTER: Mutex<ExecutionCounter> = Mutex::new(ExecutionCounter::new());
 
+// Per-encoding decode statistics, indexed by `PayloadEncoding::tag`.
+static METRICS: Mutex<[CodecStats; PayloadEncoding::COUNT]> =
+    Mutex::new([CodecStats::new(); PayloadEncoding::COUNT]);
+const METRICS_REPORT_EVERY: u64 = 100;
+
 struct ExecutionCounter {
     num_running: u64,
 }
@@ -60,56 +71,139 @@ fn should_drop(sender_timestamp: u128) -> bool {
     will_drop
 }
 
+/// Decodes the payload and letterboxes it into the model input. Anything wrong
+/// with the request itself is reported as `InvalidData`.
+fn prepare_input(
+    header: &FrameHeader,
+    encoding: PayloadEncoding,
+    payload: &[u8],
+    session: SessionInfo
+) -> io::Result<Vec<u8>> {
+    let bad_request = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let format = PixelFormat::from_fourcc(header.fourcc).ok_or_else(|| {
+        bad_request(format!("unsupported pixel format '{}'", FourCc(header.fourcc)))
+    })?;
+    if header.preprocessed && [header.width, header.height] != session.input_size {
+        return Err(bad_request(format!(
+            "preprocessed frame is {}x{}, model expects {}x{}",
+            header.width, header.height, session.input_size[0], session.input_size[1]
+        )));
+    }
+
+    let decode_start = Instant::now();
+    if format == PixelFormat::Yuyv && !header.preprocessed {
+        return yuyv_to_input(header, encoding, payload, session, decode_start);
+    }
+    let data_in = codec::decode(
+        encoding, payload, header.width, header.height, format, header.colorimetry
+    )?;
+    record_metrics(encoding, data_in.len(), payload.len(), decode_start.elapsed());
+
+    if header.preprocessed {
+        return Ok(data_in);
+    }
+    Ok(preprocess::resize_with_padding(
+        &data_in, header.width, header.height, session.input_size, header.orientation
+    ))
+}
+
+/// Samples a YUYV frame straight into the model input, skipping the RGB frame.
+fn yuyv_to_input(
+    header: &FrameHeader,
+    encoding: PayloadEncoding,
+    payload: &[u8],
+    session: SessionInfo,
+    decode_start: Instant
+) -> io::Result<Vec<u8>> {
+    let frame = codec::unpack(encoding, payload, header.width, header.height, PixelFormat::Yuyv)?;
+    let expected = PixelFormat::Yuyv.frame_len(header.width, header.height);
+    if !header.width.is_multiple_of(2) || Some(frame.len()) != expected {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "YUYV frame has {} bytes, which does not match {}x{}",
+                frame.len(), header.width, header.height
+            )
+        ));
+    }
+
+    let [width, height] = session.input_size;
+    let mut data_in = vec![0u8; width as usize * height as usize * 3];
+    preprocess::yuyv_to_tensor(
+        &frame, header.width, header.height, header.colorimetry, session.input_size,
+        header.orientation, &mut data_in
+    );
+    record_metrics(encoding, frame.len(), payload.len(), decode_start.elapsed());
+    Ok(data_in)
+}
+
+/// Refuses payloads longer than the frame of the header can take, before
+/// anything is allocated for them.
+fn check_payload_len(header: &FrameHeader, encoding: PayloadEncoding) -> io::Result<usize> {
+    // Unknown formats are refused once the payload is decoded.
+    let limit = match PixelFormat::from_fourcc(header.fourcc) {
+        Some(format) => codec::max_payload_len(encoding, header.width, header.height, format),
+        None => codec::MAX_PAYLOAD_LEN,
+    };
+    if header.data_len > limit as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "payload of {} bytes, a {}x{} frame takes at most {}",
+                header.data_len, header.width, header.height, limit
+            )
+        ));
+    }
+    Ok(header.data_len as usize)
+}
+
+/// Answers `InvalidData` errors with a `BadRequest` before passing them on.
+fn reject_bad_request<T>(stream: &mut TcpStream, e: io::Error) -> io::Result<T> {
+    if e.kind() == io::ErrorKind::InvalidData {
+        Response::BadRequest(e.to_string()).write_to(stream)?;
+    }
+    Err(e)
+}
+
 fn handle_client(
-    mut stream: TcpStream
+    mut stream: TcpStream,
+    session: SessionInfo
 ) -> io::Result<()> {
-    // 1. read timestamp: `u128` as `be_bytes`.
-    // 2. read upcoming data length: `u64` as `be_bytes`
-    // 3. read all data by the data length
+    // 0. negotiate the payload encoding and advertise the model input size.
+    // 1. read the frame header (timestamp, size, pixel format, colorimetry, flags, data length).
+    // 2. read all data by the data length and decode it to RGB, answer BadRequest if impossible.
+    // 3. orient and letterbox the frame to the model input unless the client already did.
     // 4. check the timestamp to decide whether to drop this request.
     // 5. process the data with Tensorflow
     // 6. extract the data part with output_tensor.data::<f32>()
-    // 7. write data length back: `u64` as `be_bytes`
-    // 8. write data back
+    // 7. undo the orientation in the keypoints and write them back as a `Response`.
 
     // println!("Started to handle client - {}", stream.peer_addr().unwrap());
 
     stream.set_nodelay(true).unwrap();
 
-    let mut timestamp = [0u8; 16];
-    stream.read_exact(&mut timestamp)?;
-
-    let mut width = [0u8; 4];
-    stream.read_exact(&mut width)?;
-    let width = u32::from_be_bytes(width) as i32;
-
-    let mut height = [0u8; 4];
-    stream.read_exact(&mut height)?;
-    let height = u32::from_be_bytes(height) as i32;
-
-    let mut data_len = [0u8; 8];
-    stream.read_exact(&mut data_len)?;
-    let data_len = u64::from_be_bytes(data_len) as usize;
+    let encoding = protocol::accept_encoding(&mut stream, session)?;
+    let header = FrameHeader::read_from(&mut stream)
+        .or_else(|e| reject_bad_request(&mut stream, e))?;
 
+    let data_len = check_payload_len(&header, encoding)
+        .or_else(|e| reject_bad_request(&mut stream, e))?;
     let mut data_in = vec![0u8; data_len];
     stream.read_exact(&mut data_in)?;
     
-    let converter = shared::utils::EasyConverter::new();
-    let mut data_in = converter.rgb(&data_in);
-    let data_in = utils::resize_with_padding(
-        &mut data_in, height, width, [192, 192]
-    );
+    let data_in = prepare_input(&header, encoding, &data_in, session)
+        .or_else(|e| reject_bad_request(&mut stream, e))?;
 
-    if should_drop(u128::from_be_bytes(timestamp)) {
+    if should_drop(header.timestamp) {
         // println!("Dropped request.");
-        stream.write(0u64.to_be_bytes().as_slice())?;
+        Response::Dropped.write_to(&mut stream)?;
         return Ok(());
     }
 
     let mut options = Options::default();
     options.thread_count = 5;
-	let path = format!("resource/lite-model_movenet_singlepose_lightning_tflite_int8_4.tflite");
-    let interpreter = Interpreter::with_model_path(&path, Some(options)).unwrap();
+    let interpreter = Interpreter::with_model_path(MODEL_PATH, Some(options)).unwrap();
     interpreter.allocate_tensors().expect("Allocate tensors [FAILED]");
 
     interpreter.copy(&data_in[..], 0).unwrap();
@@ -118,22 +212,37 @@ fn handle_client(
     interpreter.invoke().expect("Invoke [FAILED]");
 
     let output_tensor = interpreter.output(0).unwrap();
-    let data_out = output_tensor.data::<f32>();
+    let mut data_out = output_tensor.data::<f32>().to_vec();
+    skeleton::restore_orientation(&mut data_out, header.orientation);
 
-    stream.write(
-        (data_out.len() as u64).to_be_bytes().as_slice()
-    )?;
-    
-    let data_out: Vec<u8> = data_out.iter().flat_map(|d| 
-        d.to_be_bytes()
-    ).collect();
-    stream.write_all(&data_out)?;
-    stream.flush()?;
+    Response::Keypoints(data_out).write_to(&mut stream)?;
     
     // println!("Finished handling");
     Ok(())
 }
 
+/// Reads the `[width, height]` the model expects from its input tensor (NHWC).
+fn model_input_size() -> io::Result<[u32; 2]> {
+    let to_io = |e: tflitec::Error| io::Error::new(io::ErrorKind::Other, e.to_string());
+
+    let interpreter = Interpreter::with_model_path(MODEL_PATH, None).map_err(to_io)?;
+    interpreter.allocate_tensors().map_err(to_io)?;
+    let input = interpreter.input(0).map_err(to_io)?;
+    let dims = input.shape().dimensions();
+    Ok([dims[2] as u32, dims[1] as u32])
+}
+
+fn record_metrics(
+    encoding: PayloadEncoding, raw_bytes: usize, payload_bytes: usize, decode_time: Duration
+) {
+    let mut metrics = METRICS.lock().unwrap();
+    let stats = &mut metrics[encoding.tag() as usize];
+    stats.record(raw_bytes, payload_bytes, decode_time);
+    if stats.frames % METRICS_REPORT_EVERY == 0 {
+        println!("[metrics] {}: {}", encoding, stats);
+    }
+}
+
 fn adjust_time_interval() {
     let mut current_interval = TIME_INTERVAL.lock().unwrap();
     // println!("Running: {}", COUNTER.lock().unwrap().get_num_running());
@@ -169,6 +278,9 @@ fn main() -> io::Result<()> {
     let local_addr = listener.local_addr()?;
     println!("Listening to local address: {}", local_addr);
 
+    let session = SessionInfo { input_size: model_input_size()? };
+    println!("Model input size: {}x{}", session.input_size[0], session.input_size[1]);
+
     let pool = ThreadPool::new(N_WORKERS);
 
     // accept connections and process them serially
@@ -180,7 +292,7 @@ fn main() -> io::Result<()> {
                 }
                 
                 pool.execute(move || {
-                    if let Err(e) = handle_client(s) {
+                    if let Err(e) = handle_client(s, session) {
                         println!("Potential error occurs in the server. Message: {}.", e);
                     }
                     {
@@ -5,6 +5,7 @@ use opencv::{
 	imgproc::*,
 	core::*,
 };
+use shared::letterbox::Letterbox;
 
 pub fn resize_with_padding(
 	data: &mut Vec<u8>, rows: i32, cols: i32, new_shape: [i32; 2]
@@ -23,17 +24,10 @@ pub fn resize_with_padding(
 
 	flip(&frame, &mut img, 1).expect("flip [FAILED]");
 
-	let img_shape = [img.cols(), img.rows()];
-
-	let width: i32;
-	let height: i32;
-	if img_shape[0] as f64 / img_shape[1] as f64 > new_shape[0] as f64 / new_shape[1] as f64 {
-		width = new_shape[0];
-		height = (new_shape[0] as f64 / img_shape[0] as f64 * img_shape[1] as f64) as i32;
-	} else {
-		width = (new_shape[1] as f64 / img_shape[1] as f64 * img_shape[0] as f64) as i32;
-		height = new_shape[1];
-	}
+	let letterbox = Letterbox::new(
+		[img.cols() as u32, img.rows() as u32], [new_shape[0] as u32, new_shape[1] as u32]
+	);
+	let [width, height] = letterbox.fitted().map(|v| v as i32);
 
 	let mut resized = Mat::default();
 	resize(
@@ -45,10 +39,7 @@ pub fn resize_with_padding(
 	)
 	.expect("resize_with_padding: resize [FAILED]");
 
-	let delta_w = new_shape[0] - width;
-	let delta_h = new_shape[1] - height;
-	let (top, bottom) = (delta_h / 2, delta_h - delta_h / 2);
-	let (left, right) = (delta_w / 2, delta_w - delta_w / 2);
+	let [top, bottom, left, right] = letterbox.padding().map(|v| v as i32);
 		
 	let mut rslt = Mat::default();
 	copy_make_border(
@@ -0,0 +1,55 @@
+//! The pure-Rust letterbox against the OpenCV one it replaced.
+#![cfg(feature = "opencv")]
+
+use server::utils;
+use shared::format::{Orientation, Rotation};
+use shared::preprocess;
+
+// `[width, height]` of a source frame and of the letterbox it is fitted into.
+const CASES: [([u32; 2], [u32; 2]); 7] = [
+    ([1280, 720], [192, 192]),
+    ([640, 480], [256, 256]),
+    // Exact 2x, which OpenCV hands to `INTER_AREA`.
+    ([384, 384], [192, 192]),
+    ([192, 108], [192, 192]),
+    ([101, 61], [192, 192]),
+    ([48, 96], [192, 256]),
+    ([7, 5], [192, 160]),
+];
+
+// Fine texture, so that every tap and weight shows in the output.
+fn texture(width: u32, height: u32) -> Vec<u8> {
+    (0..width * height * 3).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect()
+}
+
+fn mirrored(rgb: &[u8], width: u32) -> Vec<u8> {
+    rgb.chunks_exact(width as usize * 3)
+        .flat_map(|row| row.chunks_exact(3).rev().flatten().copied())
+        .collect()
+}
+
+#[test]
+fn letterbox_is_within_a_level_of_opencv() {
+    for ([width, height], new_shape) in CASES {
+        let rgb = texture(width, height);
+        for mirror in [false, true] {
+            // The OpenCV path always mirrors, so it is handed the frame
+            // mirrored already for an unmirrored result.
+            let mut input = if mirror { rgb.clone() } else { mirrored(&rgb, width) };
+            let expected = utils::resize_with_padding(
+                &mut input, height as i32, width as i32, new_shape.map(|v| v as i32)
+            );
+            let orientation = Orientation::new(Rotation::None, mirror);
+            let output =
+                preprocess::resize_with_padding(&rgb, width, height, new_shape, orientation);
+
+            assert_eq!(output.len(), expected.len());
+            let diff = output.iter().zip(&expected).map(|(a, b)| a.abs_diff(*b)).max().unwrap();
+            assert!(
+                diff <= 1,
+                "{}x{} into {}x{}, mirror {}: max difference {}",
+                width, height, new_shape[0], new_shape[1], mirror, diff
+            );
+        }
+    }
+}
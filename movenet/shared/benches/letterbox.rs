@@ -0,0 +1,53 @@
+//! YUYV frame to 192x192 model input: RGB frame + resize versus the fused path.
+//!
+//! Run with `cargo bench -p shared --bench letterbox`.
+
+use std::hint::black_box;
+
+use shared::bench::{bench, synthetic_yuyv};
+use shared::format::{Colorimetry, Orientation};
+use shared::preprocess::{self, TensorElement};
+use shared::utils::EasyConverter;
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+const INPUT_SIZE: [u32; 2] = [192, 192];
+const ORIENTATION: Orientation = Orientation::MIRRORED;
+const ITERATIONS: u32 = 500;
+
+fn fused<T: TensorElement + Default + Clone>(name: &str, yuyv: &[u8]) {
+    let mut tensor = vec![T::default(); (INPUT_SIZE[0] * INPUT_SIZE[1] * 3) as usize];
+    bench(name, ITERATIONS, || {
+        preprocess::yuyv_to_tensor(
+            black_box(yuyv), WIDTH, HEIGHT, Colorimetry::default(), INPUT_SIZE, ORIENTATION,
+            &mut tensor
+        );
+    });
+}
+
+fn main() {
+    let yuyv = synthetic_yuyv(WIDTH, HEIGHT);
+    let converter = EasyConverter::default();
+    println!("{}x{} YUYV -> {}x{} RGB", WIDTH, HEIGHT, INPUT_SIZE[0], INPUT_SIZE[1]);
+
+    bench("rgb + resize_with_padding", ITERATIONS, || {
+        let rgb = converter.rgb(black_box(&yuyv));
+        black_box(preprocess::resize_with_padding(&rgb, WIDTH, HEIGHT, INPUT_SIZE, ORIENTATION));
+    });
+    fused::<u8>("fused, u8", &yuyv);
+    fused::<i8>("fused, i8", &yuyv);
+    fused::<f32>("fused, f32", &yuyv);
+
+    let rgb = converter.rgb(&yuyv);
+    let reference = preprocess::resize_with_padding(&rgb, WIDTH, HEIGHT, INPUT_SIZE, ORIENTATION);
+    let mut tensor = vec![0u8; reference.len()];
+    preprocess::yuyv_to_tensor(
+        &yuyv, WIDTH, HEIGHT, Colorimetry::default(), INPUT_SIZE, ORIENTATION, &mut tensor
+    );
+    let diffs: Vec<u32> = reference.iter().zip(&tensor).map(|(a, b)| a.abs_diff(*b) as u32).collect();
+    println!(
+        "fused vs RGB path: max diff {}, mean {:.3}",
+        diffs.iter().max().unwrap(),
+        diffs.iter().sum::<u32>() as f64 / diffs.len() as f64
+    );
+}
@@ -0,0 +1,84 @@
+//! YUYV to RGB24 throughput at 1280x720, the camera's capture size.
+//!
+//! Run with `cargo bench -p shared`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use shared::convert::{self, Coefficients, Simd};
+use shared::utils::EasyConverter;
+use yuv::YUV;
+use yuv::color::{MatrixCoefficients, Range};
+use yuv::convert::RGBConvert;
+
+const WIDTH: usize = 1280;
+const HEIGHT: usize = 720;
+const ITERATIONS: u32 = 200;
+const THREADS: usize = 4;
+
+/// The previous implementation: pixel by pixel through the `yuv` crate into a growing `Vec`.
+fn yuv_crate(converter: &RGBConvert<u8>, yuyv: &[u8]) -> Vec<u8> {
+    let mut rgb = Vec::new();
+    for pair in yuyv.chunks_exact(4) {
+        for y in [pair[0], pair[2]] {
+            let pixel = converter.to_rgb(YUV { y, u: pair[1], v: pair[3] });
+            rgb.push(pixel.r);
+            rgb.push(pixel.g);
+            rgb.push(pixel.b);
+        }
+    }
+    rgb
+}
+
+fn bench<F: FnMut()>(name: &str, baseline: Option<Duration>, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let per_frame = start.elapsed() / ITERATIONS;
+    let speedup = baseline.map_or(1.0, |b| b.as_secs_f64() / per_frame.as_secs_f64());
+    println!("{:<24} {:>8.3} ms/frame  {:>6.1}x", name, per_frame.as_secs_f64() * 1000.0, speedup);
+    per_frame
+}
+
+fn main() {
+    // Deterministic noise so no kernel benefits from uniform input.
+    let mut state = 0x2545_f491u32;
+    let yuyv: Vec<u8> = (0..WIDTH * HEIGHT * 2)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let mut rgb = vec![0u8; WIDTH * HEIGHT * 3];
+
+    println!("{}x{} YUYV -> RGB24, detected {:?}", WIDTH, HEIGHT, Simd::detect());
+
+    let old = RGBConvert::<u8>::new(Range::Limited, MatrixCoefficients::BT601).unwrap();
+    let baseline = bench("yuv crate (previous)", None, || {
+        black_box(yuv_crate(&old, black_box(&yuyv)));
+    });
+    let baseline = Some(baseline);
+
+    let converter = EasyConverter::default();
+    bench("EasyConverter::rgb", baseline, || {
+        black_box(converter.rgb(black_box(&yuyv)));
+    });
+
+    let k = Coefficients::default();
+    for simd in [Simd::Scalar, Simd::Ssse3, Simd::Avx2, Simd::Neon] {
+        if !simd.is_supported() {
+            continue;
+        }
+        bench(&format!("into, {:?}", simd), baseline, || {
+            convert::yuyv_to_rgb_with(simd, &k, black_box(&yuyv), &mut rgb);
+        });
+    }
+
+    bench(&format!("into, {} threads", THREADS), baseline, || {
+        converter.rgb_into_parallel(black_box(&yuyv), &mut rgb, THREADS);
+    });
+}
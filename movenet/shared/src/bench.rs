@@ -0,0 +1,33 @@
+//! Input and timing shared by the benchmarks and tests of the workspace.
+
+use std::time::{Duration, Instant};
+
+/// A `width` x `height` YUYV frame of smooth gradients with some texture,
+/// roughly what a camera delivers. `width` must be even.
+pub fn synthetic_yuyv(width: u32, height: u32) -> Vec<u8> {
+    let mut yuyv = Vec::with_capacity((width * height * 2) as usize);
+    for j in 0..height {
+        for i in (0..width).step_by(2) {
+            let (x, y) = (i as f32 / width as f32, j as f32 / height as f32);
+            let luma = |x: f32| 40.0 + 160.0 * x * y + 20.0 * (x * 40.0).sin();
+            yuyv.push(luma(x) as u8);
+            yuyv.push((128.0 + 40.0 * (y * 6.0).sin()) as u8);
+            yuyv.push(luma(x + 1.0 / width as f32) as u8);
+            yuyv.push((128.0 + 40.0 * (x * 4.0).cos()) as u8);
+        }
+    }
+    yuyv
+}
+
+/// Runs `f` once to warm up, then `iterations` times, and prints the time per
+/// run under `name`.
+pub fn bench<F: FnMut()>(name: &str, iterations: u32, mut f: F) -> Duration {
+    f();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let per_frame = start.elapsed() / iterations;
+    println!("{:<24} {:>8.3} ms/frame", name, per_frame.as_secs_f64() * 1000.0);
+    per_frame
+}
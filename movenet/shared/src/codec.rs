@@ -0,0 +1,171 @@
+//! Packs frames into a `PayloadEncoding` and unpacks them back to RGB24.
+//!
+//! The encoding is the transport layer (compression or client-side conversion),
+//! the `PixelFormat` sent along describes the frame once the encoding is undone.
+
+use std::borrow::Cow;
+use std::io::{self, Read};
+
+use jpeg_encoder::{ColorType, Encoder};
+use jpeg_decoder::{Decoder, PixelFormat as JpegFormat};
+
+use crate::format::{Colorimetry, PixelFormat};
+use crate::protocol::PayloadEncoding;
+use crate::utils::EasyConverter;
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// Encodes a frame in `format` for the wire. Returns the payload together with
+/// the pixel format the server finds after undoing the encoding.
+/// `colorimetry` is only used if the encoding converts the frame to RGB.
+pub fn encode(
+    encoding: PayloadEncoding,
+    frame: &[u8],
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    colorimetry: Colorimetry
+) -> io::Result<(Vec<u8>, PixelFormat)> {
+    match encoding {
+        PayloadEncoding::Raw => Ok((frame.to_vec(), format)),
+        PayloadEncoding::Rgb => {
+            Ok((to_rgb(frame, width, height, format, colorimetry)?, PixelFormat::Rgb24))
+        }
+        PayloadEncoding::Jpeg { .. } if format == PixelFormat::Mjpeg => {
+            Ok((frame.to_vec(), PixelFormat::Mjpeg))
+        }
+        PayloadEncoding::Jpeg { quality } => {
+            let rgb = to_rgb(frame, width, height, format, colorimetry)?;
+            Ok((encode_jpeg(&rgb, width, height, quality)?, PixelFormat::Mjpeg))
+        }
+        PayloadEncoding::Zstd { level } => Ok((zstd::encode_all(frame, level as i32)?, format)),
+    }
+}
+
+fn encode_jpeg(rgb: &[u8], width: u32, height: u32, quality: u8) -> io::Result<Vec<u8>> {
+    let (Ok(jpeg_width), Ok(jpeg_height)) = (u16::try_from(width), u16::try_from(height)) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}x{} is too large for a JPEG", width, height)
+        ));
+    };
+    let mut out = Vec::new();
+    Encoder::new(&mut out, quality)
+        .encode(rgb, jpeg_width, jpeg_height, ColorType::Rgb)
+        .map_err(invalid_data)?;
+    Ok(out)
+}
+
+/// Decodes a wire payload into a packed RGB24 frame of `width * height` pixels.
+/// Malformed payloads are reported as `InvalidData`.
+pub fn decode(
+    encoding: PayloadEncoding,
+    payload: &[u8],
+    width: u32,
+    height: u32,
+    format: PixelFormat,
+    colorimetry: Colorimetry
+) -> io::Result<Vec<u8>> {
+    let frame = unpack(encoding, payload, width, height, format)?;
+    to_rgb(&frame, width, height, format, colorimetry)
+}
+
+/// Undoes the transport compression only, returning the frame in the pixel
+/// format of the header. Compressed payloads that unpack to more than a
+/// `width` x `height` frame in `format` are `InvalidData`, without inflating
+/// them any further.
+pub fn unpack(
+    encoding: PayloadEncoding, payload: &[u8], width: u32, height: u32, format: PixelFormat
+) -> io::Result<Cow<'_, [u8]>> {
+    match encoding {
+        PayloadEncoding::Zstd { .. } => {
+            let limit = unpacked_limit(width, height, format);
+            let mut frame = Vec::new();
+            zstd::Decoder::with_buffer(payload)
+                .map_err(invalid_data)?
+                .take(limit as u64 + 1)
+                .read_to_end(&mut frame)
+                .map_err(invalid_data)?;
+            if frame.len() > limit {
+                return Err(invalid_data(format!(
+                    "zstd payload unpacks to more than the {} bytes of a {}x{} {} frame",
+                    limit, width, height, format
+                )));
+            }
+            Ok(Cow::Owned(frame))
+        }
+        _ => Ok(Cow::Borrowed(payload)),
+    }
+}
+
+/// Most bytes any payload may have, 8K as RGB24 with room to spare.
+pub const MAX_PAYLOAD_LEN: usize = 128 << 20;
+
+/// Most bytes the payload of a `width` x `height` frame in `format` may have
+/// with `encoding`, to refuse larger ones before reading them. Uncompressed
+/// payloads are exactly a frame, compressed ones are held to `MAX_PAYLOAD_LEN`.
+pub fn max_payload_len(
+    encoding: PayloadEncoding, width: u32, height: u32, format: PixelFormat
+) -> usize {
+    match (encoding, format.frame_len(width, height)) {
+        (PayloadEncoding::Raw | PayloadEncoding::Rgb, Some(len)) => len.min(MAX_PAYLOAD_LEN),
+        _ => MAX_PAYLOAD_LEN,
+    }
+}
+
+// Most bytes a frame can unpack to. A JPEG larger than the frame as RGB24 is
+// not worth decoding.
+fn unpacked_limit(width: u32, height: u32, format: PixelFormat) -> usize {
+    format.frame_len(width, height)
+        .or_else(|| PixelFormat::Rgb24.frame_len(width, height))
+        .unwrap_or(usize::MAX)
+}
+
+/// Converts a frame in any supported `format` to packed RGB24.
+pub fn to_rgb(
+    frame: &[u8], width: u32, height: u32, format: PixelFormat, colorimetry: Colorimetry
+) -> io::Result<Vec<u8>> {
+    if let Some(expected) = format.frame_len(width, height) {
+        check_len(format, frame.len(), expected)?;
+    }
+
+    let converter = EasyConverter::with_colorimetry(colorimetry);
+    match converter.rgb_from_format(frame, width as usize, height as usize, format) {
+        Some(rgb) => Ok(rgb),
+        None => decode_jpeg(frame, width, height),
+    }
+}
+
+fn decode_jpeg(payload: &[u8], width: u32, height: u32) -> io::Result<Vec<u8>> {
+    let (pixels, actual_width, actual_height) = decode_jpeg_image(payload)?;
+    if actual_width != width || actual_height != height {
+        return Err(invalid_data(format!(
+            "JPEG is {}x{}, expected {}x{}", actual_width, actual_height, width, height
+        )));
+    }
+    Ok(pixels)
+}
+
+/// Decodes a JPEG of any size to RGB24, returning the pixels with the width and height.
+pub(crate) fn decode_jpeg_image(payload: &[u8]) -> io::Result<(Vec<u8>, u32, u32)> {
+    let mut decoder = Decoder::new(payload);
+    let pixels = decoder.decode().map_err(invalid_data)?;
+    let info = decoder.info().ok_or_else(|| invalid_data("missing JPEG header"))?;
+    let rgb = match info.pixel_format {
+        JpegFormat::RGB24 => pixels,
+        JpegFormat::L8 => pixels.iter().flat_map(|&l| [l, l, l]).collect(),
+        other => return Err(invalid_data(format!("unsupported JPEG pixel format {:?}", other))),
+    };
+    Ok((rgb, info.width as u32, info.height as u32))
+}
+
+fn check_len(format: PixelFormat, actual: usize, expected: usize) -> io::Result<()> {
+    if actual != expected {
+        return Err(invalid_data(format!(
+            "{} frame has {} bytes, expected {}", format, actual, expected
+        )));
+    }
+    Ok(())
+}
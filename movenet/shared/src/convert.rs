@@ -0,0 +1,465 @@
+//! Fixed-point YUV to RGB conversion with SIMD kernels for packed YUYV, and
+//! the scalar RGB to YUV conversion used to synthesize test input.
+//!
+//! Every kernel, including the scalar fallback, evaluates the same 16-bit
+//! fixed-point expression, so all of them produce bit-identical output.
+
+use std::thread;
+
+use crate::format::{Colorimetry, YuvRange};
+
+// Fractional bits of the coefficients. Samples are pre-shifted by
+// `SAMPLE_SHIFT` and multiplied keeping the high 16 bits (`pmulhw`), which
+// leaves `COEF_BITS + SAMPLE_SHIFT - 16` fractional bits in the result.
+const COEF_BITS: u32 = 13;
+const SAMPLE_SHIFT: u32 = 7;
+const RESULT_BITS: u32 = COEF_BITS + SAMPLE_SHIFT - 16;
+
+/// Conversion matrix of one `Colorimetry` in fixed point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coefficients {
+    y_offset: i16,
+    y: i16,
+    r_v: i16,
+    g_u: i16,
+    g_v: i16,
+    b_u: i16,
+}
+
+#[inline(always)]
+fn mulhi(a: i16, b: i16) -> i16 {
+    ((a as i32 * b as i32) >> 16) as i16
+}
+
+// Saturation to `0..=255` by lookup: LLVM turns a plain `clamp` into branches,
+// which mispredict constantly on camera noise. Converted values stay well
+// within +-1024, the range of the table.
+const CLAMP_RANGE: i32 = 1024;
+static CLAMP_TABLE: [u8; 2 * CLAMP_RANGE as usize] = clamp_table();
+
+const fn clamp_table() -> [u8; 2 * CLAMP_RANGE as usize] {
+    let mut table = [0u8; 2 * CLAMP_RANGE as usize];
+    let mut i = 0;
+    while i < table.len() {
+        let value = i as i32 - CLAMP_RANGE;
+        table[i] = if value < 0 { 0 } else if value > 255 { 255 } else { value as u8 };
+        i += 1;
+    }
+    table
+}
+
+#[inline(always)]
+fn clamp(value: i16) -> u8 {
+    let value = (value as i32 + (1 << (RESULT_BITS - 1))) >> RESULT_BITS;
+    CLAMP_TABLE[(value + CLAMP_RANGE) as usize & (2 * CLAMP_RANGE as usize - 1)]
+}
+
+impl Coefficients {
+    pub fn new(colorimetry: Colorimetry) -> Self {
+        let (kr, kb) = colorimetry.matrix.kr_kb();
+        let kg = 1.0 - kr - kb;
+        let (y_offset, y_scale, c_scale) = match colorimetry.range {
+            YuvRange::Limited => (16, 255.0 / 219.0, 255.0 / 224.0),
+            YuvRange::Full => (0, 1.0, 1.0),
+        };
+        let fixed = |k: f64| (k * (1 << COEF_BITS) as f64).round() as i16;
+
+        Self {
+            y_offset,
+            y: fixed(y_scale),
+            r_v: fixed(2.0 * (1.0 - kr) * c_scale),
+            g_u: fixed(2.0 * (1.0 - kb) * kb / kg * c_scale),
+            g_v: fixed(2.0 * (1.0 - kr) * kr / kg * c_scale),
+            b_u: fixed(2.0 * (1.0 - kb) * c_scale),
+        }
+    }
+
+    /// Converts a single sample to `[r, g, b]`.
+    #[inline(always)]
+    pub fn pixel(&self, y: u8, u: u8, v: u8) -> [u8; 3] {
+        let y = mulhi((y as i16 - self.y_offset) << SAMPLE_SHIFT, self.y);
+        let u = (u as i16 - 128) << SAMPLE_SHIFT;
+        let v = (v as i16 - 128) << SAMPLE_SHIFT;
+        [
+            clamp(y + mulhi(v, self.r_v)),
+            clamp(y - mulhi(u, self.g_u) - mulhi(v, self.g_v)),
+            clamp(y + mulhi(u, self.b_u)),
+        ]
+    }
+
+    fn yuyv_scalar(&self, src: &[u8], dst: &mut [u8]) {
+        for (yuyv, rgb) in src.chunks_exact(4).zip(dst.chunks_exact_mut(6)) {
+            let [y0, u, y1, v] = [yuyv[0], yuyv[1], yuyv[2], yuyv[3]];
+            rgb[..3].copy_from_slice(&self.pixel(y0, u, v));
+            rgb[3..].copy_from_slice(&self.pixel(y1, u, v));
+        }
+    }
+}
+
+impl Default for Coefficients {
+    fn default() -> Self {
+        Self::new(Colorimetry::default())
+    }
+}
+
+// Fractional bits of the RGB to YUV coefficients. Only used to build test
+// input, so plain 32-bit arithmetic without SIMD.
+const INVERSE_BITS: u32 = 16;
+
+/// RGB to YUV matrix of one `Colorimetry` in fixed point, the inverse of `Coefficients`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InverseCoefficients {
+    y_offset: i32,
+    y: [i32; 3],
+    u: [i32; 3],
+    v: [i32; 3],
+}
+
+impl InverseCoefficients {
+    pub fn new(colorimetry: Colorimetry) -> Self {
+        let (kr, kb) = colorimetry.matrix.kr_kb();
+        let kg = 1.0 - kr - kb;
+        let (y_offset, y_scale, c_scale) = match colorimetry.range {
+            YuvRange::Limited => (16, 219.0 / 255.0, 224.0 / 255.0),
+            YuvRange::Full => (0, 1.0, 1.0),
+        };
+        let fixed = |k: f64| (k * (1 << INVERSE_BITS) as f64).round() as i32;
+        let u_scale = c_scale / (2.0 * (1.0 - kb));
+        let v_scale = c_scale / (2.0 * (1.0 - kr));
+
+        Self {
+            y_offset,
+            y: [fixed(kr * y_scale), fixed(kg * y_scale), fixed(kb * y_scale)],
+            u: [fixed(-kr * u_scale), fixed(-kg * u_scale), fixed((1.0 - kb) * u_scale)],
+            v: [fixed((1.0 - kr) * v_scale), fixed(-kg * v_scale), fixed(-kb * v_scale)],
+        }
+    }
+
+    #[inline(always)]
+    fn dot(k: [i32; 3], rgb: [i32; 3]) -> i32 {
+        k[0] * rgb[0] + k[1] * rgb[1] + k[2] * rgb[2]
+    }
+
+    /// Luma of one pixel.
+    #[inline(always)]
+    pub fn luma(&self, rgb: [u8; 3]) -> u8 {
+        let rgb = rgb.map(i32::from);
+        let y = (Self::dot(self.y, rgb) + (1 << (INVERSE_BITS - 1))) >> INVERSE_BITS;
+        (y + self.y_offset).clamp(0, 255) as u8
+    }
+
+    /// `[u, v]` of the average of `count` pixels whose components add up to `sum`.
+    #[inline(always)]
+    pub fn chroma(&self, sum: [u32; 3], count: u32) -> [u8; 2] {
+        let sum = sum.map(|c| c as i32);
+        let count = count as i32;
+        // The bias keeps the numerator positive, so truncating division rounds.
+        let bias = ((128 << INVERSE_BITS) + (1 << (INVERSE_BITS - 1))) * count;
+        [self.u, self.v].map(|k| {
+            let c = ((Self::dot(k, sum) + bias) / count) >> INVERSE_BITS;
+            c.clamp(0, 255) as u8
+        })
+    }
+}
+
+impl Default for InverseCoefficients {
+    fn default() -> Self {
+        Self::new(Colorimetry::default())
+    }
+}
+
+/// Instruction set used for a conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Simd {
+    Scalar,
+    /// 16 pixels per step, x86 with SSSE3.
+    Ssse3,
+    /// 32 pixels per step, x86 with AVX2.
+    Avx2,
+    /// 16 pixels per step, AArch64.
+    Neon,
+}
+
+impl Simd {
+    /// Best instruction set supported by the running CPU.
+    pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Simd::Avx2;
+            }
+            if is_x86_feature_detected!("ssse3") {
+                return Simd::Ssse3;
+            }
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            return Simd::Neon;
+        }
+        #[allow(unreachable_code)]
+        Simd::Scalar
+    }
+
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Simd::Scalar => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Simd::Ssse3 => is_x86_feature_detected!("ssse3"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Simd::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "aarch64")]
+            Simd::Neon => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+}
+
+/// Number of RGB24 bytes produced from `yuyv_len` bytes of YUYV.
+/// A trailing incomplete pixel pair is ignored.
+pub fn rgb_len(yuyv_len: usize) -> usize {
+    yuyv_len / 4 * 6
+}
+
+/// Converts packed YUYV into `dst`, which must be `rgb_len(src.len())` bytes,
+/// using the best instruction set available.
+pub fn yuyv_to_rgb(k: &Coefficients, src: &[u8], dst: &mut [u8]) {
+    yuyv_to_rgb_with(Simd::detect(), k, src, dst)
+}
+
+/// Like `yuyv_to_rgb`, with an explicit instruction set. Falls back to scalar
+/// code if the CPU does not support `simd`.
+pub fn yuyv_to_rgb_with(simd: Simd, k: &Coefficients, src: &[u8], dst: &mut [u8]) {
+    assert_eq!(dst.len(), rgb_len(src.len()), "RGB buffer does not match the YUYV frame");
+    let src = &src[..src.len() / 4 * 4];
+
+    let simd = if simd.is_supported() { simd } else { Simd::Scalar };
+    let done = match simd {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Simd::Avx2 => unsafe { x86::yuyv_avx2(k, src, dst) },
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        Simd::Ssse3 => unsafe { x86::yuyv_ssse3(k, src, dst) },
+        #[cfg(target_arch = "aarch64")]
+        Simd::Neon => unsafe { neon::yuyv_neon(k, src, dst) },
+        _ => 0,
+    };
+    k.yuyv_scalar(&src[done..], &mut dst[rgb_len(done)..]);
+}
+
+/// Like `yuyv_to_rgb`, splitting the frame across up to `threads` threads.
+pub fn yuyv_to_rgb_parallel(k: &Coefficients, src: &[u8], dst: &mut [u8], threads: usize) {
+    assert_eq!(dst.len(), rgb_len(src.len()), "RGB buffer does not match the YUYV frame");
+    let pairs = src.len() / 4;
+    let pairs_per_thread = pairs.div_ceil(threads.max(1)).max(1);
+    let simd = Simd::detect();
+
+    thread::scope(|scope| {
+        let src_chunks = src[..pairs * 4].chunks(pairs_per_thread * 4);
+        for (src, dst) in src_chunks.zip(dst.chunks_mut(pairs_per_thread * 6)) {
+            scope.spawn(move || yuyv_to_rgb_with(simd, k, src, dst));
+        }
+    });
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::*;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::*;
+
+    use super::{Coefficients, RESULT_BITS, SAMPLE_SHIFT};
+
+    // `pshufb` masks spreading 16 bytes of one channel over 48 bytes of RGB24,
+    // indexed by output chunk and channel.
+    const fn interleave_mask(chunk: usize, channel: usize) -> [i8; 16] {
+        let mut mask = [-128i8; 16];
+        let mut i = 0;
+        while i < 16 {
+            let n = chunk * 16 + i;
+            if n % 3 == channel {
+                mask[i] = (n / 3) as i8;
+            }
+            i += 1;
+        }
+        mask
+    }
+
+    const INTERLEAVE: [[[i8; 16]; 3]; 3] = [
+        [interleave_mask(0, 0), interleave_mask(0, 1), interleave_mask(0, 2)],
+        [interleave_mask(1, 0), interleave_mask(1, 1), interleave_mask(1, 2)],
+        [interleave_mask(2, 0), interleave_mask(2, 1), interleave_mask(2, 2)],
+    ];
+
+    /// Writes 16 pixels given as one register per channel.
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    unsafe fn store_rgb(r: __m128i, g: __m128i, b: __m128i, dst: *mut u8) {
+        for (chunk, masks) in INTERLEAVE.iter().enumerate() {
+            let [mr, mg, mb] = masks.map(|m| _mm_loadu_si128(m.as_ptr() as *const __m128i));
+            let out = _mm_or_si128(
+                _mm_or_si128(_mm_shuffle_epi8(r, mr), _mm_shuffle_epi8(g, mg)),
+                _mm_shuffle_epi8(b, mb)
+            );
+            _mm_storeu_si128(dst.add(chunk * 16) as *mut __m128i, out);
+        }
+    }
+
+    macro_rules! convert_block {
+        ($k:expr, $yuyv:expr, $set1:ident, $and:ident, $srli:ident, $slli:ident, $srai:ident,
+         $shufflelo:ident, $shufflehi:ident, $add:ident, $sub:ident, $mulhi:ident) => {{
+            let yuyv = $yuyv;
+            let y = $and(yuyv, $set1(0xff));
+            let uv = $srli::<8>(yuyv);
+            // Duplicate each chroma sample onto both pixels of its pair.
+            let u = $shufflehi::<0b10_10_00_00>($shufflelo::<0b10_10_00_00>(uv));
+            let v = $shufflehi::<0b11_11_01_01>($shufflelo::<0b11_11_01_01>(uv));
+
+            let y = $mulhi($slli::<{ SAMPLE_SHIFT as i32 }>($sub(y, $set1($k.y_offset))), $set1($k.y));
+            let u = $slli::<{ SAMPLE_SHIFT as i32 }>($sub(u, $set1(128)));
+            let v = $slli::<{ SAMPLE_SHIFT as i32 }>($sub(v, $set1(128)));
+
+            let r = $add(y, $mulhi(v, $set1($k.r_v)));
+            let g = $sub($sub(y, $mulhi(u, $set1($k.g_u))), $mulhi(v, $set1($k.g_v)));
+            let b = $add(y, $mulhi(u, $set1($k.b_u)));
+
+            let round = $set1(1 << (RESULT_BITS - 1));
+            (
+                $srai::<{ RESULT_BITS as i32 }>($add(r, round)),
+                $srai::<{ RESULT_BITS as i32 }>($add(g, round)),
+                $srai::<{ RESULT_BITS as i32 }>($add(b, round)),
+            )
+        }};
+    }
+
+    /// Converts 8 pixels into `i16` R, G and B lanes.
+    #[inline]
+    #[target_feature(enable = "ssse3")]
+    unsafe fn convert8(k: &Coefficients, src: *const u8) -> (__m128i, __m128i, __m128i) {
+        convert_block!(
+            k, _mm_loadu_si128(src as *const __m128i), _mm_set1_epi16, _mm_and_si128,
+            _mm_srli_epi16, _mm_slli_epi16, _mm_srai_epi16, _mm_shufflelo_epi16,
+            _mm_shufflehi_epi16, _mm_add_epi16, _mm_sub_epi16, _mm_mulhi_epi16
+        )
+    }
+
+    /// Converts 16 pixels per step, returns the number of YUYV bytes consumed.
+    #[target_feature(enable = "ssse3")]
+    pub unsafe fn yuyv_ssse3(k: &Coefficients, src: &[u8], dst: &mut [u8]) -> usize {
+        let blocks = src.len() / 32;
+        for i in 0..blocks {
+            let src = src.as_ptr().add(i * 32);
+            let (r0, g0, b0) = convert8(k, src);
+            let (r1, g1, b1) = convert8(k, src.add(16));
+            store_rgb(
+                _mm_packus_epi16(r0, r1),
+                _mm_packus_epi16(g0, g1),
+                _mm_packus_epi16(b0, b1),
+                dst.as_mut_ptr().add(i * 48)
+            );
+        }
+        blocks * 32
+    }
+
+    /// Converts 16 pixels into `i16` R, G and B lanes, 8 per 128-bit half.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn convert16(k: &Coefficients, src: *const u8) -> (__m256i, __m256i, __m256i) {
+        convert_block!(
+            k, _mm256_loadu_si256(src as *const __m256i), _mm256_set1_epi16, _mm256_and_si256,
+            _mm256_srli_epi16, _mm256_slli_epi16, _mm256_srai_epi16, _mm256_shufflelo_epi16,
+            _mm256_shufflehi_epi16, _mm256_add_epi16, _mm256_sub_epi16, _mm256_mulhi_epi16
+        )
+    }
+
+    /// Packs two blocks of 16 pixels back into pixel order. `packus` works per
+    /// 128-bit lane, so the 8-pixel groups come out as 0, 2, 1, 3.
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    unsafe fn pack(a: __m256i, b: __m256i) -> __m256i {
+        _mm256_permute4x64_epi64::<0b11_01_10_00>(_mm256_packus_epi16(a, b))
+    }
+
+    /// Converts 32 pixels per step, returns the number of YUYV bytes consumed.
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn yuyv_avx2(k: &Coefficients, src: &[u8], dst: &mut [u8]) -> usize {
+        let blocks = src.len() / 64;
+        for i in 0..blocks {
+            let src = src.as_ptr().add(i * 64);
+            let (r0, g0, b0) = convert16(k, src);
+            let (r1, g1, b1) = convert16(k, src.add(32));
+            let (r, g, b) = (pack(r0, r1), pack(g0, g1), pack(b0, b1));
+
+            let dst = dst.as_mut_ptr().add(i * 96);
+            store_rgb(
+                _mm256_castsi256_si128(r),
+                _mm256_castsi256_si128(g),
+                _mm256_castsi256_si128(b),
+                dst
+            );
+            store_rgb(
+                _mm256_extracti128_si256::<1>(r),
+                _mm256_extracti128_si256::<1>(g),
+                _mm256_extracti128_si256::<1>(b),
+                dst.add(48)
+            );
+        }
+        blocks * 64
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+
+    use super::{Coefficients, RESULT_BITS, SAMPLE_SHIFT};
+
+    /// Converts 16 pixels per step, returns the number of YUYV bytes consumed.
+    pub unsafe fn yuyv_neon(k: &Coefficients, src: &[u8], dst: &mut [u8]) -> usize {
+        let y_offset = vdupq_n_s16(k.y_offset);
+        let c128 = vdupq_n_s16(128);
+        let round = vdupq_n_s16(1 << (RESULT_BITS - 1));
+        let (ky, r_v, g_u, g_v, b_u) = (
+            vdupq_n_s16(k.y), vdupq_n_s16(k.r_v), vdupq_n_s16(k.g_u), vdupq_n_s16(k.g_v),
+            vdupq_n_s16(k.b_u)
+        );
+        // `sqdmulh` doubles the product, so samples are shifted one bit less
+        // to get the same result as `pmulhw`.
+        const SHIFT: i32 = SAMPLE_SHIFT as i32 - 1;
+        let widen = |x: uint8x8_t| vreinterpretq_s16_u16(vmovl_u8(x));
+
+        let blocks = src.len() / 32;
+        for i in 0..blocks {
+            // Lanes: Y of even pixels, U, Y of odd pixels, V.
+            let yuyv = vld4_u8(src.as_ptr().add(i * 32));
+            let u = vshlq_n_s16::<SHIFT>(vsubq_s16(widen(yuyv.1), c128));
+            let v = vshlq_n_s16::<SHIFT>(vsubq_s16(widen(yuyv.3), c128));
+            let r_chroma = vqdmulhq_s16(v, r_v);
+            let g_u = vqdmulhq_s16(u, g_u);
+            let g_v = vqdmulhq_s16(v, g_v);
+            let b_chroma = vqdmulhq_s16(u, b_u);
+
+            let convert = |y: uint8x8_t| {
+                let y = vqdmulhq_s16(vshlq_n_s16::<SHIFT>(vsubq_s16(widen(y), y_offset)), ky);
+                let finish = |c: int16x8_t| {
+                    vqmovun_s16(vshrq_n_s16::<{ RESULT_BITS as i32 }>(vaddq_s16(c, round)))
+                };
+                (
+                    finish(vaddq_s16(y, r_chroma)),
+                    finish(vsubq_s16(vsubq_s16(y, g_u), g_v)),
+                    finish(vaddq_s16(y, b_chroma)),
+                )
+            };
+            let (r0, g0, b0) = convert(yuyv.0);
+            let (r1, g1, b1) = convert(yuyv.2);
+
+            let zip = |even: uint8x8_t, odd: uint8x8_t| {
+                let zipped = vzip_u8(even, odd);
+                vcombine_u8(zipped.0, zipped.1)
+            };
+            let rgb = uint8x16x3_t(zip(r0, r1), zip(g0, g1), zip(b0, b1));
+            vst3q_u8(dst.as_mut_ptr().add(i * 48), rgb);
+        }
+        blocks * 32
+    }
+}
@@ -0,0 +1,302 @@
+//! Pixel formats understood by the pipeline, identified by their V4L2 FourCC,
+//! and the colorimetry needed to turn their YUV samples into RGB.
+
+use std::fmt;
+
+/// Packs four characters into a FourCC code, like the kernel's `v4l2_fourcc`.
+pub const fn fourcc(code: &[u8; 4]) -> u32 {
+    (code[0] as u32) | (code[1] as u32) << 8 | (code[2] as u32) << 16 | (code[3] as u32) << 24
+}
+
+/// Prints a FourCC code as its four characters, e.g. `YUYV`.
+pub struct FourCc(pub u32);
+
+impl fmt::Display for FourCc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for byte in self.0.to_le_bytes() {
+            let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '?' };
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// Packed 4:2:2, `Y0 U Y1 V` per two pixels. What UVC webcams deliver.
+    #[default]
+    Yuyv,
+    /// Packed `R G B`, one byte each.
+    Rgb24,
+    /// Planar Y followed by interleaved `U V` at quarter resolution.
+    Nv12,
+    /// Planar Y, U, V with U and V at quarter resolution (V4L2 `YU12`).
+    I420,
+    /// 8-bit luma only.
+    Grey,
+    /// One baseline JPEG image per frame.
+    Mjpeg,
+}
+
+impl PixelFormat {
+    pub const ALL: [PixelFormat; 6] = [
+        PixelFormat::Yuyv,
+        PixelFormat::Rgb24,
+        PixelFormat::Nv12,
+        PixelFormat::I420,
+        PixelFormat::Grey,
+        PixelFormat::Mjpeg,
+    ];
+
+    pub const fn fourcc(&self) -> u32 {
+        match self {
+            PixelFormat::Yuyv => fourcc(b"YUYV"),
+            PixelFormat::Rgb24 => fourcc(b"RGB3"),
+            PixelFormat::Nv12 => fourcc(b"NV12"),
+            PixelFormat::I420 => fourcc(b"YU12"),
+            PixelFormat::Grey => fourcc(b"GREY"),
+            PixelFormat::Mjpeg => fourcc(b"MJPG"),
+        }
+    }
+
+    pub fn from_fourcc(code: u32) -> Option<Self> {
+        PixelFormat::ALL.into_iter().find(|format| format.fourcc() == code)
+    }
+
+    /// Bytes per row of the first plane when tightly packed, `None` for compressed formats.
+    pub fn min_stride(&self, width: u32) -> Option<usize> {
+        let w = width as usize;
+        match self {
+            PixelFormat::Yuyv => Some(w * 2),
+            PixelFormat::Rgb24 => Some(w * 3),
+            PixelFormat::Nv12 | PixelFormat::I420 | PixelFormat::Grey => Some(w),
+            PixelFormat::Mjpeg => None,
+        }
+    }
+
+    /// Exact byte length of a `width` x `height` frame, `None` for compressed formats.
+    pub fn frame_len(&self, width: u32, height: u32) -> Option<usize> {
+        let (w, h) = (width as usize, height as usize);
+        // Saturates for sizes no buffer could hold, which then never match.
+        let pixels = w.saturating_mul(h);
+        match self {
+            PixelFormat::Yuyv => Some(pixels.saturating_mul(2)),
+            PixelFormat::Rgb24 => Some(pixels.saturating_mul(3)),
+            PixelFormat::Nv12 | PixelFormat::I420 => {
+                let chroma = w.div_ceil(2).saturating_mul(h.div_ceil(2));
+                Some(pixels.saturating_add(chroma.saturating_mul(2)))
+            }
+            PixelFormat::Grey => Some(pixels),
+            PixelFormat::Mjpeg => None,
+        }
+    }
+}
+
+impl fmt::Display for PixelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", FourCc(self.fourcc()))
+    }
+}
+
+/// YCbCr to RGB conversion matrix.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum YuvMatrix {
+    /// SDTV, and what UVC webcams use.
+    #[default]
+    Bt601,
+    /// HDTV.
+    Bt709,
+    /// UHDTV, non-constant luminance.
+    Bt2020,
+}
+
+impl YuvMatrix {
+    /// Red and blue luma weights `(Kr, Kb)`; green is `1 - Kr - Kb`.
+    pub fn kr_kb(&self) -> (f64, f64) {
+        match self {
+            YuvMatrix::Bt601 => (0.299, 0.114),
+            YuvMatrix::Bt709 => (0.2126, 0.0722),
+            YuvMatrix::Bt2020 => (0.2627, 0.0593),
+        }
+    }
+}
+
+/// Quantization range of the YUV samples.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum YuvRange {
+    /// Luma 16-235, chroma 16-240.
+    #[default]
+    Limited,
+    /// 0-255 for all components (JPEG).
+    Full,
+}
+
+// `enum v4l2_colorspace`, `enum v4l2_ycbcr_encoding` and `enum v4l2_quantization`.
+const V4L2_COLORSPACE_SMPTE240M: u32 = 2;
+const V4L2_COLORSPACE_REC709: u32 = 3;
+const V4L2_COLORSPACE_JPEG: u32 = 7;
+const V4L2_COLORSPACE_BT2020: u32 = 10;
+const V4L2_COLORSPACE_DCI_P3: u32 = 12;
+const V4L2_YCBCR_ENC_DEFAULT: u32 = 0;
+const V4L2_YCBCR_ENC_709: u32 = 2;
+const V4L2_YCBCR_ENC_XV709: u32 = 4;
+const V4L2_YCBCR_ENC_BT2020: u32 = 6;
+const V4L2_YCBCR_ENC_BT2020_CONST_LUM: u32 = 7;
+const V4L2_YCBCR_ENC_SMPTE240M: u32 = 8;
+const V4L2_QUANTIZATION_DEFAULT: u32 = 0;
+const V4L2_QUANTIZATION_FULL_RANGE: u32 = 1;
+
+/// Matrix and range of a YUV frame. Defaults to BT.601 limited range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Colorimetry {
+    pub matrix: YuvMatrix,
+    pub range: YuvRange,
+}
+
+impl Colorimetry {
+    pub const fn new(matrix: YuvMatrix, range: YuvRange) -> Self {
+        Self { matrix, range }
+    }
+
+    /// Resolves the `colorspace`, `ycbcr_enc` and `quantization` fields of a
+    /// `v4l2_pix_format`, filling in defaults the way the kernel's
+    /// `V4L2_MAP_*_DEFAULT` macros do. SMPTE 240M has no matrix here and is
+    /// treated as BT.709, which it is closest to.
+    pub fn from_v4l2(colorspace: u32, ycbcr_enc: u32, quantization: u32) -> Self {
+        let ycbcr_enc = if ycbcr_enc == V4L2_YCBCR_ENC_DEFAULT {
+            match colorspace {
+                V4L2_COLORSPACE_REC709 | V4L2_COLORSPACE_DCI_P3 => V4L2_YCBCR_ENC_709,
+                V4L2_COLORSPACE_BT2020 => V4L2_YCBCR_ENC_BT2020,
+                V4L2_COLORSPACE_SMPTE240M => V4L2_YCBCR_ENC_SMPTE240M,
+                _ => V4L2_YCBCR_ENC_DEFAULT,
+            }
+        } else {
+            ycbcr_enc
+        };
+        let matrix = match ycbcr_enc {
+            V4L2_YCBCR_ENC_709 | V4L2_YCBCR_ENC_XV709 | V4L2_YCBCR_ENC_SMPTE240M => {
+                YuvMatrix::Bt709
+            }
+            V4L2_YCBCR_ENC_BT2020 | V4L2_YCBCR_ENC_BT2020_CONST_LUM => YuvMatrix::Bt2020,
+            _ => YuvMatrix::Bt601,
+        };
+
+        let full = match quantization {
+            V4L2_QUANTIZATION_DEFAULT => colorspace == V4L2_COLORSPACE_JPEG,
+            other => other == V4L2_QUANTIZATION_FULL_RANGE,
+        };
+        let range = if full { YuvRange::Full } else { YuvRange::Limited };
+
+        Self { matrix, range }
+    }
+
+    pub fn to_wire(&self) -> [u8; 2] {
+        let matrix = match self.matrix {
+            YuvMatrix::Bt601 => 0,
+            YuvMatrix::Bt709 => 1,
+            YuvMatrix::Bt2020 => 2,
+        };
+        let range = match self.range {
+            YuvRange::Limited => 0,
+            YuvRange::Full => 1,
+        };
+        [matrix, range]
+    }
+
+    pub fn from_wire(wire: [u8; 2]) -> Option<Self> {
+        let matrix = match wire[0] {
+            0 => YuvMatrix::Bt601,
+            1 => YuvMatrix::Bt709,
+            2 => YuvMatrix::Bt2020,
+            _ => return None,
+        };
+        let range = match wire[1] {
+            0 => YuvRange::Limited,
+            1 => YuvRange::Full,
+            _ => return None,
+        };
+        Some(Self { matrix, range })
+    }
+}
+
+/// Clockwise quarter turns that make a captured frame upright.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+
+impl Rotation {
+    pub fn quarter_turns(&self) -> u8 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Cw90 => 1,
+            Rotation::Cw180 => 2,
+            Rotation::Cw270 => 3,
+        }
+    }
+
+    pub fn from_quarter_turns(turns: u8) -> Self {
+        match turns % 4 {
+            0 => Rotation::None,
+            1 => Rotation::Cw90,
+            2 => Rotation::Cw180,
+            _ => Rotation::Cw270,
+        }
+    }
+}
+
+/// How a captured frame is turned into what the model sees: rotated clockwise
+/// first, then mirrored horizontally. Keypoints are mapped back before they
+/// are returned, so they always refer to the captured frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Orientation {
+    pub rotation: Rotation,
+    pub mirror: bool,
+}
+
+impl Orientation {
+    /// Mirrored and upright, how frames of a front-facing webcam have always been fed to the model.
+    pub const MIRRORED: Self = Self::new(Rotation::None, true);
+
+    pub const fn new(rotation: Rotation, mirror: bool) -> Self {
+        Self { rotation, mirror }
+    }
+
+    /// `[width, height]` of a `size` frame once oriented.
+    pub fn oriented_size(&self, size: [u32; 2]) -> [u32; 2] {
+        match self.rotation {
+            Rotation::None | Rotation::Cw180 => size,
+            Rotation::Cw90 | Rotation::Cw270 => [size[1], size[0]],
+        }
+    }
+
+    /// Pixel of the captured `size` frame that ends up at `(x, y)` once oriented.
+    #[inline(always)]
+    pub fn source_pixel(&self, x: usize, y: usize, size: [u32; 2]) -> [usize; 2] {
+        let (w, h) = (size[0] as usize, size[1] as usize);
+        let x = if self.mirror { self.oriented_size(size)[0] as usize - 1 - x } else { x };
+        match self.rotation {
+            Rotation::None => [x, y],
+            Rotation::Cw90 => [y, h - 1 - x],
+            Rotation::Cw180 => [w - 1 - x, h - 1 - y],
+            Rotation::Cw270 => [w - 1 - y, x],
+        }
+    }
+
+    /// Maps a point normalized to the letterboxed, oriented model input back
+    /// onto the captured frame letterboxed the same way. Both letterboxes are
+    /// squares around the same center, so this only turns about it.
+    pub fn restore_point(&self, x: f32, y: f32) -> [f32; 2] {
+        let x = if self.mirror { 1.0 - x } else { x };
+        match self.rotation {
+            Rotation::None => [x, y],
+            Rotation::Cw90 => [y, 1.0 - x],
+            Rotation::Cw180 => [1.0 - x, 1.0 - y],
+            Rotation::Cw270 => [1.0 - y, x],
+        }
+    }
+}
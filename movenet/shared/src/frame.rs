@@ -0,0 +1,230 @@
+//! A captured frame together with everything needed to interpret its bytes.
+
+use std::borrow::Cow;
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::format::{Colorimetry, PixelFormat};
+use crate::utils::EasyConverter;
+
+/// One plane of an uncompressed frame: `rows` rows of `row_len` bytes, `stride` bytes apart.
+struct Plane {
+    row_len: usize,
+    rows: usize,
+    stride: usize,
+}
+
+// Plane layout of a frame whose first plane has rows `stride` bytes apart.
+// Chroma planes follow V4L2: NV12 shares the luma stride, I420 halves it.
+// Empty for compressed formats.
+fn planes(format: PixelFormat, width: u32, height: u32, stride: usize) -> Vec<Plane> {
+    let (w, h) = (width as usize, height as usize);
+    let Some(row_len) = format.min_stride(width) else {
+        return Vec::new();
+    };
+    let luma = Plane { row_len, rows: h, stride };
+    match format {
+        PixelFormat::Nv12 => {
+            let chroma_len = w.div_ceil(2) * 2;
+            let rows = h.div_ceil(2);
+            vec![luma, Plane { row_len: chroma_len, rows, stride: stride.max(chroma_len) }]
+        }
+        PixelFormat::I420 => {
+            let (row_len, rows) = (w.div_ceil(2), h.div_ceil(2));
+            let chroma = || Plane { row_len, rows, stride: stride.div_ceil(2) };
+            vec![luma, chroma(), chroma()]
+        }
+        _ => vec![luma],
+    }
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Milliseconds since the Unix epoch, the unit of `Frame::timestamp`.
+pub fn now_millis() -> u128 {
+    SystemTime::now().duration_since(UNIX_EPOCH).expect("Time went backwards").as_millis()
+}
+
+/// What the capture driver reported along with a frame (`VIDIOC_DQBUF`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureInfo {
+    /// When the driver captured the frame, on `CLOCK_MONOTONIC`.
+    pub monotonic: Duration,
+    /// The driver's count of captured frames. Gaps are frames it dropped.
+    pub sequence: u32,
+    /// Bytes of the buffer the frame filled.
+    pub bytes_used: usize,
+}
+
+/// Frame bytes with their size, layout and capture metadata. The constructors
+/// check that the bytes actually hold a `width` x `height` frame in `format`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: PixelFormat,
+    colorimetry: Colorimetry,
+    timestamp: u128,
+    sequence: u64,
+    capture: Option<CaptureInfo>,
+}
+
+impl Frame {
+    /// Wraps tightly packed frame bytes, timestamped now.
+    pub fn new(data: Vec<u8>, width: u32, height: u32, format: PixelFormat) -> io::Result<Self> {
+        let stride = format.min_stride(width).unwrap_or(0);
+        Self::with_stride(data, width, height, stride, format)
+    }
+
+    /// Wraps frame bytes whose rows are `stride` bytes apart (V4L2 `bytesperline`),
+    /// timestamped now. Bytes past the end of the last plane are ignored.
+    pub fn with_stride(
+        data: Vec<u8>, width: u32, height: u32, stride: usize, format: PixelFormat
+    ) -> io::Result<Self> {
+        if let Some(min_stride) = format.min_stride(width) {
+            if stride < min_stride {
+                return Err(invalid_input(format!(
+                    "stride of {} bytes is too small for a {} pixel wide {} frame",
+                    stride, width, format
+                )));
+            }
+            let required: usize = planes(format, width, height, stride)
+                .iter()
+                .map(|plane| plane.stride * plane.rows)
+                .sum();
+            if data.len() < required {
+                return Err(invalid_input(format!(
+                    "{}x{} {} frame needs {} bytes, got {}",
+                    width, height, format, required, data.len()
+                )));
+            }
+        }
+
+        Ok(Self {
+            data,
+            width,
+            height,
+            stride,
+            format,
+            colorimetry: Colorimetry::default(),
+            timestamp: now_millis(),
+            sequence: 0,
+            capture: None,
+        })
+    }
+
+    pub fn with_colorimetry(mut self, colorimetry: Colorimetry) -> Self {
+        self.colorimetry = colorimetry;
+        self
+    }
+
+    /// Sets the capture time, in milliseconds since the Unix epoch.
+    pub fn with_timestamp(mut self, timestamp: u128) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    pub fn with_sequence(mut self, sequence: u64) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    pub fn with_capture_info(mut self, capture: CaptureInfo) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Takes over the timestamp, sequence number and capture info of `frame`,
+    /// for frames converted from it.
+    pub fn with_metadata_of(mut self, frame: &Frame) -> Self {
+        self.timestamp = frame.timestamp;
+        self.sequence = frame.sequence;
+        self.capture = frame.capture;
+        self
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// `[width, height]`.
+    pub fn size(&self) -> [u32; 2] {
+        [self.width, self.height]
+    }
+
+    /// Bytes between the starts of two rows of the first plane, 0 for compressed formats.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    pub fn colorimetry(&self) -> Colorimetry {
+        self.colorimetry
+    }
+
+    /// Capture time in milliseconds since the Unix epoch. For frames from a
+    /// driver, its own timestamp moved onto the wall clock.
+    pub fn timestamp(&self) -> u128 {
+        self.timestamp
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// What the driver reported when it delivered this frame, or the one it was
+    /// converted from. `None` for frames not captured from a device.
+    pub fn capture_info(&self) -> Option<CaptureInfo> {
+        self.capture
+    }
+
+    /// The bytes as stored, including any row padding.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The frame without row padding, laid out as `PixelFormat::frame_len` describes.
+    /// Borrows when the frame already is tightly packed.
+    pub fn packed(&self) -> Cow<'_, [u8]> {
+        let planes = planes(self.format, self.width, self.height, self.stride);
+        if planes.is_empty() {
+            return Cow::Borrowed(&self.data);
+        }
+        let packed_len: usize = planes.iter().map(|plane| plane.row_len * plane.rows).sum();
+        if planes.iter().all(|plane| plane.stride == plane.row_len) {
+            return Cow::Borrowed(&self.data[..packed_len]);
+        }
+
+        let mut packed = Vec::with_capacity(packed_len);
+        let mut offset = 0;
+        for plane in &planes {
+            for row in 0..plane.rows {
+                let start = offset + row * plane.stride;
+                packed.extend_from_slice(&self.data[start..start + plane.row_len]);
+            }
+            offset += plane.stride * plane.rows;
+        }
+        Cow::Owned(packed)
+    }
+
+    /// Converts to a packed RGB24 frame using the frame's own colorimetry,
+    /// keeping the timestamp, sequence number and capture info.
+    pub fn to_rgb(&self) -> io::Result<Frame> {
+        EasyConverter::with_colorimetry(self.colorimetry).convert(self)
+    }
+}
@@ -0,0 +1,334 @@
+//! Reading and writing frames as BMP, PNG and PPM files, plus reading JPEG.
+//!
+//! Everything is read into packed RGB24 frames. Frames in other formats are
+//! converted to RGB with their own colorimetry before writing.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::codec;
+use crate::format::PixelFormat;
+use crate::frame::Frame;
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    /// The bytes are not a well-formed image.
+    Malformed(String),
+    /// A valid image using a variant this module does not handle.
+    Unsupported(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(e) => write!(f, "{}", e),
+            ImageError::Malformed(msg) => write!(f, "malformed image: {}", msg),
+            ImageError::Unsupported(msg) => write!(f, "unsupported image: {}", msg),
+        }
+    }
+}
+
+impl Error for ImageError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ImageError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ImageError {
+    fn from(e: io::Error) -> Self {
+        ImageError::Io(e)
+    }
+}
+
+impl From<ImageError> for io::Error {
+    fn from(e: ImageError) -> Self {
+        match e {
+            ImageError::Io(e) => e,
+            ImageError::Malformed(_) => io::Error::new(io::ErrorKind::InvalidData, e.to_string()),
+            ImageError::Unsupported(_) => io::Error::new(io::ErrorKind::Unsupported, e.to_string()),
+        }
+    }
+}
+
+fn malformed<S: ToString>(msg: S) -> ImageError {
+    ImageError::Malformed(msg.to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+    /// Binary PPM (`P6`) when writing, PPM or PGM (`P5`) when reading.
+    Ppm,
+    /// Read only.
+    Jpeg,
+}
+
+impl ImageFormat {
+    /// Guesses the format from a file extension, ignoring case.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "bmp" => Some(ImageFormat::Bmp),
+            "png" => Some(ImageFormat::Png),
+            "ppm" | "pgm" | "pnm" => Some(ImageFormat::Ppm),
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+            _ => None,
+        }
+    }
+
+    /// Recognizes the format from the first bytes of a file.
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [b'B', b'M', ..] => Some(ImageFormat::Bmp),
+            [0x89, b'P', b'N', b'G', ..] => Some(ImageFormat::Png),
+            [b'P', b'5' | b'6', ..] => Some(ImageFormat::Ppm),
+            [0xff, 0xd8, ..] => Some(ImageFormat::Jpeg),
+            _ => None,
+        }
+    }
+}
+
+/// Reads an image file, recognizing its format by content.
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Frame, ImageError> {
+    decode(&fs::read(path)?)
+}
+
+/// Writes a frame in the format named by the file extension.
+pub fn write<P: AsRef<Path>>(path: P, frame: &Frame) -> Result<(), ImageError> {
+    let path = path.as_ref();
+    let format = ImageFormat::from_path(path).ok_or_else(|| {
+        ImageError::Unsupported(format!("no image format for {}", path.display()))
+    })?;
+    write_as(path, frame, format)
+}
+
+pub fn write_as<P: AsRef<Path>>(path: P, frame: &Frame, format: ImageFormat) -> Result<(), ImageError> {
+    fs::write(path, encode(frame, format)?)?;
+    Ok(())
+}
+
+/// Decodes an image held in memory to an RGB24 frame.
+pub fn decode(bytes: &[u8]) -> Result<Frame, ImageError> {
+    let (rgb, width, height) = match ImageFormat::detect(bytes) {
+        Some(ImageFormat::Bmp) => decode_bmp(bytes)?,
+        Some(ImageFormat::Png) => decode_png(bytes)?,
+        Some(ImageFormat::Ppm) => decode_ppm(bytes)?,
+        Some(ImageFormat::Jpeg) => codec::decode_jpeg_image(bytes).map_err(malformed)?,
+        None => return Err(ImageError::Unsupported("not a BMP, PNG, PPM or JPEG image".into())),
+    };
+    Ok(Frame::new(rgb, width, height, PixelFormat::Rgb24)?)
+}
+
+/// Encodes a frame as an image file in memory.
+pub fn encode(frame: &Frame, format: ImageFormat) -> Result<Vec<u8>, ImageError> {
+    let rgb = frame.to_rgb()?;
+    let [width, height] = rgb.size();
+    match format {
+        ImageFormat::Bmp => encode_bmp(rgb.data(), width, height),
+        ImageFormat::Png => encode_png(rgb.data(), width, height),
+        ImageFormat::Ppm => Ok(encode_ppm(rgb.data(), width, height)),
+        ImageFormat::Jpeg => Err(ImageError::Unsupported("JPEG images are read only".into())),
+    }
+}
+
+const BMP_HEADER_LEN: usize = 14 + 40;
+
+// Rows are padded to a multiple of 4 bytes.
+fn bmp_stride(width: usize, bits: usize) -> usize {
+    (width * bits).div_ceil(32) * 4
+}
+
+// Sizes from a header are not trusted, `None` where they overflow.
+fn checked_len(stride: usize, rows: usize, offset: usize) -> Option<usize> {
+    stride.checked_mul(rows)?.checked_add(offset)
+}
+
+fn encode_bmp(rgb: &[u8], width: u32, height: u32) -> Result<Vec<u8>, ImageError> {
+    let (w, h) = (width as usize, height as usize);
+    let stride = bmp_stride(w, 24);
+    let file_len = u32::try_from(BMP_HEADER_LEN + stride * h)
+        .ok()
+        .filter(|_| i32::try_from(width).is_ok() && i32::try_from(height).is_ok())
+        .ok_or_else(|| {
+            ImageError::Unsupported(format!("{}x{} is too large for a BMP", width, height))
+        })?;
+
+    let mut bmp = Vec::with_capacity(file_len as usize);
+    bmp.extend_from_slice(b"BM");
+    bmp.extend_from_slice(&file_len.to_le_bytes());
+    bmp.extend_from_slice(&[0; 4]);
+    bmp.extend_from_slice(&(BMP_HEADER_LEN as u32).to_le_bytes());
+    // BITMAPINFOHEADER: uncompressed, 24 bits, one plane.
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&width.to_le_bytes());
+    bmp.extend_from_slice(&height.to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&24u16.to_le_bytes());
+    bmp.extend_from_slice(&[0; 24]);
+
+    // Bottom row first, BGR.
+    let padding = stride - w * 3;
+    for row in rgb[..w * h * 3].chunks_exact(w * 3).rev() {
+        for pixel in row.chunks_exact(3) {
+            bmp.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+        }
+        bmp.extend_from_slice(&[0; 3][..padding]);
+    }
+    Ok(bmp)
+}
+
+/// Reads an uncompressed 8-bit paletted, 24-bit or 32-bit BMP, bottom-up or top-down.
+fn decode_bmp(bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), ImageError> {
+    let u16_at = |at: usize| bytes.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]]));
+    let u32_at = |at: usize| bytes.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+    let truncated = || malformed("truncated BMP header");
+
+    let offset = u32_at(10).ok_or_else(truncated)? as usize;
+    let header_len = u32_at(14).ok_or_else(truncated)? as usize;
+    let width = u32_at(18).ok_or_else(truncated)? as i32;
+    let height = u32_at(22).ok_or_else(truncated)? as i32;
+    let bits = u16_at(28).ok_or_else(truncated)? as usize;
+    let compression = u32_at(30).ok_or_else(truncated)?;
+    let colors_used = u32_at(46).ok_or_else(truncated)? as usize;
+    if width <= 0 || height == 0 {
+        return Err(malformed(format!("BMP is {}x{}", width, height)));
+    }
+    // BI_RGB, or BI_BITFIELDS with the usual BGRA masks.
+    if !matches!((bits, compression), (8 | 24 | 32, 0) | (32, 3)) {
+        return Err(ImageError::Unsupported(format!(
+            "BMP with {} bits per pixel and compression {}", bits, compression
+        )));
+    }
+
+    let palette = if bits == 8 {
+        let start = 14 + header_len;
+        let count = if colors_used == 0 { 256 } else { colors_used.min(256) };
+        let palette = checked_len(4, count, start)
+            .and_then(|end| bytes.get(start..end))
+            .ok_or_else(truncated)?;
+        palette.chunks_exact(4).map(|bgra| [bgra[2], bgra[1], bgra[0]]).collect()
+    } else {
+        Vec::new()
+    };
+
+    let (w, h) = (width as usize, height.unsigned_abs() as usize);
+    let stride = bmp_stride(w, bits);
+    let pixels = checked_len(stride, h, offset)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| malformed("truncated BMP pixel data"))?;
+
+    let mut rgb = Vec::with_capacity(w * h * 3);
+    for row in 0..h {
+        // Positive heights store the bottom row first.
+        let src_row = if height > 0 { h - 1 - row } else { row };
+        let line = &pixels[src_row * stride..src_row * stride + w * bits / 8];
+        if bits == 8 {
+            for &index in line {
+                let color = palette.get(index as usize)
+                    .ok_or_else(|| malformed(format!("BMP palette has no entry {}", index)))?;
+                rgb.extend_from_slice(color);
+            }
+        } else {
+            for pixel in line.chunks_exact(bits / 8) {
+                rgb.extend_from_slice(&[pixel[2], pixel[1], pixel[0]]);
+            }
+        }
+    }
+    Ok((rgb, w as u32, h as u32))
+}
+
+fn encode_png(rgb: &[u8], width: u32, height: u32) -> Result<Vec<u8>, ImageError> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(png_error)?;
+    writer.write_image_data(&rgb[..width as usize * height as usize * 3]).map_err(png_error)?;
+    writer.finish().map_err(png_error)?;
+    Ok(png)
+}
+
+fn png_error(e: png::EncodingError) -> ImageError {
+    match e {
+        png::EncodingError::IoError(e) => ImageError::Io(e),
+        other => ImageError::Unsupported(other.to_string()),
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), ImageError> {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(malformed)?;
+    let mut pixels = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(malformed)?;
+    pixels.truncate(info.buffer_size());
+
+    let rgb = match info.color_type {
+        png::ColorType::Rgb => pixels,
+        png::ColorType::Rgba => pixels.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&l| [l, l, l]).collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|p| [p[0]; 3]).collect(),
+        other => return Err(ImageError::Unsupported(format!("PNG color type {:?}", other))),
+    };
+    Ok((rgb, info.width, info.height))
+}
+
+fn encode_ppm(rgb: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let mut ppm = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    ppm.extend_from_slice(&rgb[..width as usize * height as usize * 3]);
+    ppm
+}
+
+/// Reads a binary PPM (`P6`) or PGM (`P5`) with at most 8 bits per sample.
+fn decode_ppm(bytes: &[u8]) -> Result<(Vec<u8>, u32, u32), ImageError> {
+    let channels = if bytes.starts_with(b"P6") { 3 } else { 1 };
+    let mut pos = 2;
+    let mut fields = [0u32; 3];
+    for field in &mut fields {
+        // Whitespace and `#` comments separate the header fields.
+        loop {
+            match bytes.get(pos) {
+                Some(b) if b.is_ascii_whitespace() => pos += 1,
+                Some(b'#') => {
+                    while bytes.get(pos).is_some_and(|&b| b != b'\n') {
+                        pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+        let digits = bytes[pos..].iter().take_while(|b| b.is_ascii_digit()).count();
+        *field = std::str::from_utf8(&bytes[pos..pos + digits])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| malformed("bad PPM header"))?;
+        pos += digits;
+    }
+    // Exactly one whitespace byte ends the header.
+    pos += 1;
+
+    let [width, height, max_value] = fields;
+    if max_value == 0 || max_value > 255 {
+        return Err(ImageError::Unsupported(format!("PPM with maximum value {}", max_value)));
+    }
+    let samples = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .and_then(|len| bytes.get(pos..pos.checked_add(len)?))
+        .ok_or_else(|| malformed("truncated PPM pixel data"))?;
+    let scale = |s: u8| ((s as u32 * 255 + max_value / 2) / max_value).min(255) as u8;
+    let rgb = if channels == 3 {
+        samples.iter().map(|&s| scale(s)).collect()
+    } else {
+        samples.iter().flat_map(|&s| [scale(s); 3]).collect()
+    };
+    Ok((rgb, width, height))
+}
@@ -0,0 +1,80 @@
+//! The aspect-preserving fit of a frame into the model input, centered and
+//! padded with black, and the mapping of points between the two.
+//!
+//! Points are continuous pixel coordinates: `[0.0, 0.0]` is the top-left
+//! corner of the top-left pixel, whose center is `[0.5, 0.5]`.
+
+/// Where a `source` frame lands once letterboxed into a `target` one, both
+/// as `[width, height]`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    source: [u32; 2],
+    target: [u32; 2],
+    fitted: [u32; 2],
+    offset: [u32; 2],
+    scale: [f32; 2],
+}
+
+impl Letterbox {
+    /// Fits `source` into `target` with the same rounding as the OpenCV
+    /// `resize` + `copy_make_border` pipeline: the resized side is truncated,
+    /// and the odd pixel of padding goes to the bottom or right.
+    pub fn new(source: [u32; 2], target: [u32; 2]) -> Self {
+        let (src_w, src_h) = (source[0] as f64, source[1] as f64);
+        let (dst_w, dst_h) = (target[0] as f64, target[1] as f64);
+        let fitted = if src_w / src_h > dst_w / dst_h {
+            [target[0], (dst_w / src_w * src_h) as u32]
+        } else {
+            [(dst_h / src_h * src_w) as u32, target[1]]
+        };
+        let offset = [(target[0] - fitted[0]) / 2, (target[1] - fitted[1]) / 2];
+        let scale = [0, 1].map(|i| {
+            if source[i] == 0 { 0.0 } else { (fitted[i] as f64 / source[i] as f64) as f32 }
+        });
+        Self { source, target, fitted, offset, scale }
+    }
+
+    pub fn source(&self) -> [u32; 2] {
+        self.source
+    }
+
+    pub fn target(&self) -> [u32; 2] {
+        self.target
+    }
+
+    /// `[width, height]` of the resized frame inside the target. Either side
+    /// is zero when the source is too thin to cover a single target pixel.
+    pub fn fitted(&self) -> [u32; 2] {
+        self.fitted
+    }
+
+    /// `[left, top]` corner of the resized frame inside the target.
+    pub fn offset(&self) -> [u32; 2] {
+        self.offset
+    }
+
+    /// `[top, bottom, left, right]` padding, as `copy_make_border` takes it.
+    pub fn padding(&self) -> [u32; 4] {
+        let [left, top] = self.offset;
+        let bottom = self.target[1] - self.fitted[1] - top;
+        let right = self.target[0] - self.fitted[0] - left;
+        [top, bottom, left, right]
+    }
+
+    /// Target pixels per source pixel, `[horizontal, vertical]`. The two only
+    /// differ by the truncation of the resized side.
+    pub fn scale(&self) -> [f32; 2] {
+        self.scale
+    }
+
+    /// Maps a point of the source frame into the target.
+    pub fn forward(&self, point: [f32; 2]) -> [f32; 2] {
+        [0, 1].map(|i| point[i] * self.scale[i] + self.offset[i] as f32)
+    }
+
+    /// Maps a point of the target back into the source frame. Points in the
+    /// padding land outside of it. Meaningless when `fitted` has a zero side.
+    pub fn inverse(&self, point: [f32; 2]) -> [f32; 2] {
+        [0, 1].map(|i| (point[i] - self.offset[i] as f32) / self.scale[i])
+    }
+}
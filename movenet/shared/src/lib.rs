@@ -1,2 +1,15 @@
+pub mod bench;
+pub mod codec;
+pub mod convert;
+pub mod format;
+pub mod frame;
+pub mod image;
+pub mod letterbox;
+pub mod metrics;
+pub mod playback;
+pub mod preprocess;
+pub mod protocol;
+pub mod render;
+pub mod skeleton;
 pub mod threadpool;
-pub mod utils;
\ No newline at end of file
+pub mod utils;
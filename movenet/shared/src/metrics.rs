@@ -0,0 +1,121 @@
+//! Bandwidth / CPU bookkeeping for frame payloads.
+
+use std::fmt;
+use std::time::Duration;
+
+/// Running totals for frames packed (client) or unpacked (server) with one encoding.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CodecStats {
+    pub frames: u64,
+    /// Bytes of the frame before encoding / after decoding.
+    pub raw_bytes: u64,
+    /// Bytes actually sent over the wire.
+    pub payload_bytes: u64,
+    /// Time spent encoding or decoding.
+    pub codec_time: Duration,
+}
+
+impl CodecStats {
+    pub const fn new() -> Self {
+        Self { frames: 0, raw_bytes: 0, payload_bytes: 0, codec_time: Duration::ZERO }
+    }
+
+    pub fn record(&mut self, raw_bytes: usize, payload_bytes: usize, codec_time: Duration) {
+        self.frames += 1;
+        self.raw_bytes += raw_bytes as u64;
+        self.payload_bytes += payload_bytes as u64;
+        self.codec_time += codec_time;
+    }
+
+    /// Payload size relative to the raw frame, `1.0` meaning no savings.
+    pub fn ratio(&self) -> f64 {
+        if self.raw_bytes == 0 {
+            return 1.0;
+        }
+        self.payload_bytes as f64 / self.raw_bytes as f64
+    }
+
+    pub fn mean_payload_bytes(&self) -> u64 {
+        self.payload_bytes.checked_div(self.frames).unwrap_or(0)
+    }
+
+    pub fn mean_codec_time(&self) -> Duration {
+        if self.frames == 0 {
+            return Duration::ZERO;
+        }
+        self.codec_time / self.frames as u32
+    }
+}
+
+impl fmt::Display for CodecStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, {} B/frame on the wire ({:.1}% of raw), {:.2} ms/frame codec",
+            self.frames,
+            self.mean_payload_bytes(),
+            self.ratio() * 100.0,
+            self.mean_codec_time().as_secs_f64() * 1000.0
+        )
+    }
+}
+
+// V4L2 buffer sequence numbers are 32 bits. Going from close below the wrap
+// to close above zero is taken as wrapping rather than a restart.
+const SEQUENCE_WRAP: u64 = 1 << 32;
+const MAX_WRAPPED_GAP: u64 = 1 << 16;
+
+/// Frames the capture driver dropped, counted from gaps in its sequence numbers.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DropStats {
+    /// Frames received.
+    pub frames: u64,
+    /// Frames missing between those received.
+    pub dropped: u64,
+    last_sequence: Option<u64>,
+}
+
+impl DropStats {
+    pub const fn new() -> Self {
+        Self { frames: 0, dropped: 0, last_sequence: None }
+    }
+
+    /// Counts a received frame. A sequence number not past the last one means
+    /// streaming restarted, which drops nothing, unless it is the 32 bit
+    /// sequence of V4L2 wrapping around.
+    pub fn record(&mut self, sequence: u64) {
+        self.frames += 1;
+        if let Some(last) = self.last_sequence {
+            if sequence > last {
+                self.dropped += sequence - last - 1;
+            } else if last < SEQUENCE_WRAP {
+                let gap = sequence + SEQUENCE_WRAP - last - 1;
+                if gap < MAX_WRAPPED_GAP {
+                    self.dropped += gap;
+                }
+            }
+        }
+        self.last_sequence = Some(sequence);
+    }
+
+    /// Share of the frames the driver captured that never arrived.
+    pub fn drop_rate(&self) -> f64 {
+        let captured = self.frames + self.dropped;
+        if captured == 0 {
+            return 0.0;
+        }
+        self.dropped as f64 / captured as f64
+    }
+}
+
+impl fmt::Display for DropStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} frames, {} dropped ({:.1}%)",
+            self.frames,
+            self.dropped,
+            self.drop_rate() * 100.0
+        )
+    }
+}
@@ -0,0 +1,204 @@
+//! A virtual camera playing back files, to run the whole pipeline on machines
+//! without a webcam. Frames come out as YUYV, the way webcams deliver them,
+//! paced to a frame rate and stamped as if they had just been captured.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::format::{Colorimetry, PixelFormat};
+use crate::frame::{self, Frame};
+use crate::image::ImageFormat;
+use crate::utils;
+
+pub const DEFAULT_FPS: u32 = 30;
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// What a `Playback` plays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaybackSource {
+    /// Every image of a directory that `image::read` understands, in file name
+    /// order. All of them must have the size of the first one.
+    Directory(PathBuf),
+    /// Raw YUYV frames of `[width, height]` stored back to back, as
+    /// `ffmpeg -f v4l2 -i /dev/video0 -f rawvideo out.yuyv` records them.
+    Recording { path: PathBuf, size: [u32; 2] },
+    /// One image, repeated forever.
+    Still(PathBuf),
+}
+
+impl PlaybackSource {
+    /// Picks the source for `path`: a directory of images, a `.yuyv` recording
+    /// named after its frame size such as `walk_640x480.yuyv`, or an image.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            return Ok(PlaybackSource::Directory(path.to_path_buf()));
+        }
+        if ImageFormat::from_path(path).is_some() {
+            return Ok(PlaybackSource::Still(path.to_path_buf()));
+        }
+        let size = path.extension()
+            .filter(|extension| extension.eq_ignore_ascii_case("yuyv"))
+            .and_then(|_| path.file_stem()?.to_str()?.rsplit('_').next())
+            .and_then(|suffix| {
+                let (width, height) = suffix.split_once('x')?;
+                Some([width.parse().ok()?, height.parse().ok()?])
+            })
+            .ok_or_else(|| invalid_input(format!(
+                "{} is neither a directory, an image nor a recording named like name_640x480.yuyv",
+                path.display()
+            )))?;
+        Ok(PlaybackSource::Recording { path: path.to_path_buf(), size })
+    }
+}
+
+enum Frames {
+    Images { paths: Vec<PathBuf>, next: usize },
+    Recording { file: File },
+    Still(Frame),
+}
+
+/// Plays a `PlaybackSource` back at a steady frame rate.
+pub struct Playback {
+    frames: Frames,
+    size: [u32; 2],
+    colorimetry: Colorimetry,
+    interval: Duration,
+    looping: bool,
+    start: Option<Instant>,
+    sequence: u64,
+}
+
+impl Playback {
+    /// Opens `source`, encoding images to YUYV in `colorimetry`. Recordings
+    /// are assumed to be in `colorimetry` already.
+    pub fn open(source: PlaybackSource, colorimetry: Colorimetry) -> io::Result<Self> {
+        let (frames, size) = match source {
+            PlaybackSource::Directory(dir) => {
+                let mut paths = fs::read_dir(&dir)?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<io::Result<Vec<_>>>()?;
+                paths.retain(|path| path.is_file() && ImageFormat::from_path(path).is_some());
+                paths.sort();
+                let first = paths.first()
+                    .ok_or_else(|| invalid_input(format!("no images in {}", dir.display())))?;
+                let size = utils::load_yuyv(first, colorimetry)?.size();
+                (Frames::Images { paths, next: 0 }, size)
+            }
+            PlaybackSource::Recording { path, size } => {
+                if size[0] == 0 || !size[0].is_multiple_of(2) || size[1] == 0 {
+                    return Err(invalid_input(format!(
+                        "cannot play back {}x{} YUYV frames", size[0], size[1]
+                    )));
+                }
+                (Frames::Recording { file: File::open(path)? }, size)
+            }
+            PlaybackSource::Still(path) => {
+                let frame = utils::load_yuyv(path, colorimetry)?;
+                let size = frame.size();
+                (Frames::Still(frame), size)
+            }
+        };
+        Ok(Self {
+            frames,
+            size,
+            colorimetry,
+            interval: Duration::from_secs(1) / DEFAULT_FPS,
+            looping: false,
+            start: None,
+            sequence: 0,
+        })
+    }
+
+    /// Frames per second to deliver. Zero delivers them as fast as they are read.
+    pub fn with_fps(mut self, fps: u32) -> Self {
+        self.set_fps(fps);
+        self
+    }
+
+    pub fn set_fps(&mut self, fps: u32) {
+        self.interval = if fps == 0 { Duration::ZERO } else { Duration::from_secs(1) / fps };
+        self.start = None;
+    }
+
+    /// Starts over at the end of a directory or recording instead of ending.
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// `[width, height]` of every frame.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn colorimetry(&self) -> Colorimetry {
+        self.colorimetry
+    }
+
+    /// Waits until the next frame is due and returns it, `None` once a
+    /// directory or recording played to its end without looping. Frames are
+    /// never skipped: a slow reader gets them late, with their delivery time.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let Some(frame) = self.load_next()? else {
+            return Ok(None);
+        };
+
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let due = start + self.interval * self.sequence.min(u32::MAX as u64) as u32;
+        if let Some(wait) = due.checked_duration_since(Instant::now()) {
+            thread::sleep(wait);
+        }
+        self.sequence += 1;
+        Ok(Some(frame.with_timestamp(frame::now_millis()).with_sequence(self.sequence)))
+    }
+
+    fn load_next(&mut self) -> io::Result<Option<Frame>> {
+        let frame = match &mut self.frames {
+            Frames::Images { paths, next } => {
+                if *next == paths.len() {
+                    if !self.looping {
+                        return Ok(None);
+                    }
+                    *next = 0;
+                }
+                let path = &paths[*next];
+                *next += 1;
+                let frame = utils::load_yuyv(path, self.colorimetry)?;
+                if frame.size() != self.size {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                        "{} is {}x{}, the first image {}x{}",
+                        path.display(), frame.width(), frame.height(), self.size[0], self.size[1]
+                    )));
+                }
+                frame
+            }
+            Frames::Recording { file } => {
+                let [width, height] = self.size;
+                let mut data = vec![0u8; width as usize * height as usize * 2];
+                match file.read_exact(&mut data) {
+                    Ok(()) => {}
+                    // A partial last frame ends the recording as well.
+                    Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                        if !self.looping {
+                            return Ok(None);
+                        }
+                        file.seek(SeekFrom::Start(0))?;
+                        file.read_exact(&mut data)?;
+                    }
+                    Err(e) => return Err(e),
+                }
+                Frame::new(data, width, height, PixelFormat::Yuyv)?
+                    .with_colorimetry(self.colorimetry)
+            }
+            Frames::Still(frame) => frame.clone(),
+        };
+        Ok(Some(frame))
+    }
+}
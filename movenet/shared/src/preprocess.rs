@@ -0,0 +1,224 @@
+//! Pure-Rust frame preprocessing, so a client can produce the model input
+//! itself instead of shipping the full frame to the server.
+
+use crate::convert::Coefficients;
+use crate::format::{Colorimetry, Orientation, Rotation};
+use crate::letterbox::Letterbox;
+
+// Fixed-point precision of the interpolation weights, same as OpenCV's
+// `INTER_RESIZE_COEF_BITS`.
+const COEF_BITS: u32 = 11;
+const COEF_SCALE: i32 = 1 << COEF_BITS;
+
+/// One source tap pair of the bilinear filter: two indices and their weights.
+#[derive(Clone, Copy)]
+struct Tap {
+    index: [usize; 2],
+    weight: [i32; 2],
+}
+
+// Pixel-center aligned taps, matching `cv::resize` with `INTER_LINEAR`.
+// `reverse` reads the source back to front.
+fn taps(src_len: u32, dst_len: u32, reverse: bool) -> Vec<Tap> {
+    let scale = src_len as f64 / dst_len as f64;
+    let last = src_len as usize - 1;
+    (0..dst_len)
+        .map(|d| {
+            let pos = (d as f64 + 0.5) * scale - 0.5;
+            let mut index = pos.floor() as isize;
+            let mut frac = pos - index as f64;
+            if index < 0 {
+                index = 0;
+                frac = 0.0;
+            }
+            let mut index = index as usize;
+            if index >= last {
+                index = last;
+                frac = 0.0;
+            }
+            let next = (index + 1).min(last);
+            let w1 = (frac * COEF_SCALE as f64).round() as i32;
+            let index = if reverse { [last - index, last - next] } else { [index, next] };
+            Tap { index, weight: [COEF_SCALE - w1, w1] }
+        })
+        .collect()
+}
+
+/// Element of a model input tensor, produced from an 8-bit RGB component.
+pub trait TensorElement: Copy {
+    fn from_rgb(value: u8) -> Self;
+}
+
+impl TensorElement for u8 {
+    fn from_rgb(value: u8) -> Self {
+        value
+    }
+}
+
+/// Quantized with zero point -128, as int8 models expect.
+impl TensorElement for i8 {
+    fn from_rgb(value: u8) -> Self {
+        (value ^ 0x80) as i8
+    }
+}
+
+/// Unnormalized `0.0..=255.0`, as float MoveNet models expect.
+impl TensorElement for f32 {
+    fn from_rgb(value: u8) -> Self {
+        value as f32
+    }
+}
+
+/// Orients, aspect-fit resizes with bilinear filtering and zero-pads a packed
+/// RGB24 frame to `new_shape` (`[width, height]`), centering the image like
+/// the OpenCV `resize` + `copy_make_border` pipeline does.
+/// Output is within one level of OpenCV's `INTER_LINEAR`, exact 2x downscales
+/// included, which OpenCV silently performs with `INTER_AREA` to the same
+/// effect.
+pub fn resize_with_padding(
+    rgb: &[u8], width: u32, height: u32, new_shape: [u32; 2], orientation: Orientation
+) -> Vec<u8> {
+    assert!(rgb.len() >= width as usize * height as usize * 3, "frame is smaller than its size");
+
+    let stride = width as usize * 3;
+    let mut out = vec![0u8; new_shape[0] as usize * new_shape[1] as usize * 3];
+    let sample = |x: usize, y: usize| {
+        let i = y * stride + x * 3;
+        [rgb[i], rgb[i + 1], rgb[i + 2]]
+    };
+    letterbox([width, height], new_shape, orientation, &mut out, sample, |rgb| rgb);
+    out
+}
+
+/// Same as `resize_with_padding`, sampling straight from a YUYV frame into an
+/// RGB tensor of `new_shape[0] * new_shape[1] * 3` elements without building
+/// an RGB frame. The filter runs on the YUV samples and only the output pixels
+/// are converted, so results may differ from the RGB path by a level or two
+/// when downscaling a camera frame, and by a few more between neighbours of
+/// very different color, as in small frames of fine texture. `width` must be
+/// even.
+pub fn yuyv_to_tensor<T: TensorElement>(
+    yuyv: &[u8],
+    width: u32,
+    height: u32,
+    colorimetry: Colorimetry,
+    new_shape: [u32; 2],
+    orientation: Orientation,
+    out: &mut [T]
+) {
+    assert!(width.is_multiple_of(2), "YUYV frame width must be even");
+    assert!(yuyv.len() >= width as usize * height as usize * 2, "frame is smaller than its size");
+
+    let k = Coefficients::new(colorimetry);
+    let stride = width as usize * 2;
+    let sample = |x: usize, y: usize| {
+        let pair = y * stride + (x & !1) * 2;
+        [yuyv[pair + (x & 1) * 2], yuyv[pair + 1], yuyv[pair + 3]]
+    };
+    letterbox(
+        [width, height], new_shape, orientation, out, sample, |[y, u, v]| k.pixel(y, u, v)
+    );
+}
+
+// Shared body of the letterboxing functions. `read(x, y)` reads the three
+// components of a pixel of the `size` source frame, `finish` turns filtered
+// components into RGB. Filtering happens on the oriented frame, whose axes
+// are those of the source, possibly reversed and, for quarter turns, swapped.
+fn letterbox<T, S, F>(
+    size: [u32; 2],
+    new_shape: [u32; 2],
+    orientation: Orientation,
+    out: &mut [T],
+    read: S,
+    finish: F
+)
+    where T: TensorElement, S: Fn(usize, usize) -> [u8; 3], F: Fn([u8; 3]) -> [u8; 3]
+{
+    let out_stride = new_shape[0] as usize * 3;
+    assert_eq!(out.len(), out_stride * new_shape[1] as usize, "tensor does not match new_shape");
+    out.fill(T::from_rgb(0));
+
+    let [width, height] = orientation.oriented_size(size);
+    let letterbox = Letterbox::new([width, height], new_shape);
+    let [fit_w, fit_h] = letterbox.fitted();
+    let [left, top] = letterbox.offset().map(|v| v as usize);
+    if fit_w == 0 || fit_h == 0 {
+        return;
+    }
+
+    // See `Orientation::source_pixel`.
+    let (reverse_x, reverse_y, transpose) = match orientation.rotation {
+        Rotation::None => (false, false, false),
+        Rotation::Cw90 => (true, false, true),
+        Rotation::Cw180 => (true, true, false),
+        Rotation::Cw270 => (false, true, true),
+    };
+    let x_taps = taps(width, fit_w, reverse_x ^ orientation.mirror);
+    let y_taps = taps(height, fit_h, reverse_y);
+    let geometry = Geometry { out_stride, left, top, fit_w: fit_w as usize };
+    // Monomorphized separately so the sampling stays branch-free.
+    if transpose {
+        resample(out, &geometry, &x_taps, &y_taps, |x, y| read(y, x), finish);
+    } else {
+        resample(out, &geometry, &x_taps, &y_taps, read, finish);
+    }
+}
+
+// Where the fitted image goes in the output.
+struct Geometry {
+    out_stride: usize,
+    left: usize,
+    top: usize,
+    fit_w: usize,
+}
+
+fn resample<T, S, F>(
+    out: &mut [T], geometry: &Geometry, x_taps: &[Tap], y_taps: &[Tap], sample: S, finish: F
+)
+    where T: TensorElement, S: Fn(usize, usize) -> [u8; 3], F: Fn([u8; 3]) -> [u8; 3]
+{
+    let Geometry { out_stride, left, top, fit_w } = *geometry;
+
+    // Horizontally filtered rows, cached for the two source rows in use.
+    let mut rows = [vec![[0i32; 3]; fit_w], vec![[0i32; 3]; fit_w]];
+    let mut cached: [Option<usize>; 2] = [None, None];
+
+    for (dy, y_tap) in y_taps.iter().enumerate() {
+        for k in 0..2 {
+            let sy = y_tap.index[k];
+            if cached[k] == Some(sy) {
+                continue;
+            }
+            // Taken from the other slot, unless that one needs it too, as
+            // both taps of an edge row do.
+            let other = 1 - k;
+            if cached[other] == Some(sy) && y_tap.index[other] != sy {
+                rows.swap(k, other);
+                cached.swap(k, other);
+                continue;
+            }
+            for (acc, x_tap) in rows[k].iter_mut().zip(x_taps) {
+                let p0 = sample(x_tap.index[0], sy);
+                let p1 = sample(x_tap.index[1], sy);
+                for c in 0..3 {
+                    acc[c] = p0[c] as i32 * x_tap.weight[0] + p1[c] as i32 * x_tap.weight[1];
+                }
+            }
+            cached[k] = Some(sy);
+        }
+
+        // Both weights sum to `COEF_SCALE`, so the products stay below 2^31.
+        let out_row = &mut out[(top + dy) * out_stride + left * 3..][..fit_w * 3];
+        let filtered = rows[0].iter().zip(&rows[1]);
+        for (value, (r0, r1)) in out_row.chunks_exact_mut(3).zip(filtered) {
+            let mut pixel = [0u8; 3];
+            for c in 0..3 {
+                let sum = r0[c] * y_tap.weight[0] + r1[c] * y_tap.weight[1];
+                pixel[c] = ((sum + (1 << (2 * COEF_BITS - 1))) >> (2 * COEF_BITS)) as u8;
+            }
+            for (value, component) in value.iter_mut().zip(finish(pixel)) {
+                *value = T::from_rgb(component);
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! Wire format spoken between the SDK and the server.
+//!
+//! Every connection is a session that carries a single frame:
+//! 1. client writes the handshake: payload encoding tag `u8` + parameter `u8`.
+//! 2. server answers with a status `u8` (`STATUS_ACCEPTED` or `STATUS_UNSUPPORTED`),
+//!    followed by the model input size (`u32` width, `u32` height) when accepted.
+//! 3. client writes the `FrameHeader` and the payload.
+//! 4. server writes a `Response`: a status `u8`, then either the keypoints
+//!    (`u64` count + `f32`s) or, for a bad request, a `u64` length + UTF-8 message.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use crate::format::{Colorimetry, Orientation, Rotation};
+use crate::skeleton::{MULTI_POSE_LEN, SINGLE_POSE_LEN};
+
+pub const STATUS_ACCEPTED: u8 = 0;
+pub const STATUS_UNSUPPORTED: u8 = 1;
+
+// Most values a `Response::Keypoints` holds: the output of a multi-pose
+// model, which finds up to 6 people.
+const MAX_KEYPOINTS_LEN: usize = 6 * MULTI_POSE_LEN;
+// Most bytes of a `Response::BadRequest` reason.
+const MAX_REASON_LEN: usize = 4096;
+
+// Bits of the `FrameHeader` flags byte.
+const FLAG_PREPROCESSED: u8 = 1 << 0;
+const FLAG_MIRROR: u8 = 1 << 1;
+const ROTATION_SHIFT: u32 = 2;
+const ROTATION_MASK: u8 = 0b11 << ROTATION_SHIFT;
+const KNOWN_FLAGS: u8 = FLAG_PREPROCESSED | FLAG_MIRROR | ROTATION_MASK;
+
+/// How the frame bytes are packed on the wire.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    /// Frame bytes exactly as captured (YUYV).
+    #[default]
+    Raw,
+    /// Frame converted to packed RGB24 on the client.
+    Rgb,
+    /// RGB24 frame compressed as baseline JPEG at the given quality (1-100).
+    Jpeg { quality: u8 },
+    /// Raw frame compressed with zstd at the given level.
+    Zstd { level: i8 },
+}
+
+impl PayloadEncoding {
+    /// Number of distinct encoding tags, handy for per-encoding bookkeeping.
+    pub const COUNT: usize = 4;
+
+    pub fn tag(&self) -> u8 {
+        match self {
+            PayloadEncoding::Raw => 0,
+            PayloadEncoding::Rgb => 1,
+            PayloadEncoding::Jpeg { .. } => 2,
+            PayloadEncoding::Zstd { .. } => 3,
+        }
+    }
+
+    fn param(&self) -> u8 {
+        match *self {
+            PayloadEncoding::Raw | PayloadEncoding::Rgb => 0,
+            PayloadEncoding::Jpeg { quality } => quality,
+            PayloadEncoding::Zstd { level } => level as u8,
+        }
+    }
+
+    pub fn from_wire(tag: u8, param: u8) -> Option<Self> {
+        match tag {
+            0 => Some(PayloadEncoding::Raw),
+            1 => Some(PayloadEncoding::Rgb),
+            2 if (1..=100).contains(&param) => Some(PayloadEncoding::Jpeg { quality: param }),
+            3 => Some(PayloadEncoding::Zstd { level: param as i8 }),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for PayloadEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PayloadEncoding::Raw => write!(f, "raw"),
+            PayloadEncoding::Rgb => write!(f, "rgb"),
+            PayloadEncoding::Jpeg { quality } => write!(f, "jpeg(q={})", quality),
+            PayloadEncoding::Zstd { level } => write!(f, "zstd(l={})", level),
+        }
+    }
+}
+
+/// What the server advertises once it accepted a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionInfo {
+    /// Model input as `[width, height]`.
+    pub input_size: [u32; 2],
+}
+
+/// Client side of the handshake. Returns `None` if the server rejected the encoding.
+pub fn propose_encoding<S: Read + Write>(
+    stream: &mut S, encoding: PayloadEncoding
+) -> io::Result<Option<SessionInfo>> {
+    stream.write_all(&[encoding.tag(), encoding.param()])?;
+    stream.flush()?;
+
+    let mut status = [0u8; 1];
+    stream.read_exact(&mut status)?;
+    if status[0] != STATUS_ACCEPTED {
+        return Ok(None);
+    }
+
+    let input_size = [read_u32(stream)?, read_u32(stream)?];
+    Ok(Some(SessionInfo { input_size }))
+}
+
+/// Server side of the handshake. Unknown encodings are answered with
+/// `STATUS_UNSUPPORTED` and reported as `InvalidData`.
+pub fn accept_encoding<S: Read + Write>(
+    stream: &mut S, info: SessionInfo
+) -> io::Result<PayloadEncoding> {
+    let mut proposal = [0u8; 2];
+    stream.read_exact(&mut proposal)?;
+
+    match PayloadEncoding::from_wire(proposal[0], proposal[1]) {
+        Some(encoding) => {
+            stream.write_all(&[STATUS_ACCEPTED])?;
+            stream.write_all(&info.input_size[0].to_be_bytes())?;
+            stream.write_all(&info.input_size[1].to_be_bytes())?;
+            stream.flush()?;
+            Ok(encoding)
+        }
+        None => {
+            stream.write_all(&[STATUS_UNSUPPORTED])?;
+            stream.flush()?;
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported payload encoding {}/{}", proposal[0], proposal[1])
+            ))
+        }
+    }
+}
+
+/// Per-frame request header, written right before the payload.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    /// Capture time in milliseconds since the Unix epoch, taken from the
+    /// driver's timestamp for captured frames, see `Frame::timestamp`.
+    pub timestamp: u128,
+    pub width: u32,
+    pub height: u32,
+    /// FourCC of the frame once the payload encoding is undone, see `PixelFormat`.
+    /// Kept raw so the server can name formats it does not know.
+    pub fourcc: u32,
+    /// How YUV samples of the frame map to RGB.
+    pub colorimetry: Colorimetry,
+    /// The payload already is the letterboxed RGB model input, so the server
+    /// skips its own preprocessing.
+    pub preprocessed: bool,
+    /// How the frame is rotated and mirrored for the model. Applied by whoever
+    /// letterboxes the frame, always undone by the server in the keypoints.
+    pub orientation: Orientation,
+    /// Length of the payload that follows.
+    pub data_len: u64,
+}
+
+impl FrameHeader {
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> io::Result<()> {
+        stream.write_all(&self.timestamp.to_be_bytes())?;
+        stream.write_all(&self.width.to_be_bytes())?;
+        stream.write_all(&self.height.to_be_bytes())?;
+        stream.write_all(&self.fourcc.to_be_bytes())?;
+        stream.write_all(&self.colorimetry.to_wire())?;
+        let mut flags = self.orientation.rotation.quarter_turns() << ROTATION_SHIFT;
+        if self.preprocessed {
+            flags |= FLAG_PREPROCESSED;
+        }
+        if self.orientation.mirror {
+            flags |= FLAG_MIRROR;
+        }
+        stream.write_all(&[flags])?;
+        stream.write_all(&self.data_len.to_be_bytes())
+    }
+
+    pub fn read_from<R: Read>(stream: &mut R) -> io::Result<Self> {
+        let mut timestamp = [0u8; 16];
+        stream.read_exact(&mut timestamp)?;
+        let width = read_u32(stream)?;
+        let height = read_u32(stream)?;
+        let fourcc = read_u32(stream)?;
+
+        let mut colorimetry = [0u8; 2];
+        stream.read_exact(&mut colorimetry)?;
+        let colorimetry = Colorimetry::from_wire(colorimetry).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown colorimetry {:?}", colorimetry)
+            )
+        })?;
+
+        let mut flags = [0u8; 1];
+        stream.read_exact(&mut flags)?;
+        let flags = flags[0];
+        if flags & !KNOWN_FLAGS != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown frame flags {:#010b}", flags)
+            ));
+        }
+        let orientation = Orientation::new(
+            Rotation::from_quarter_turns((flags & ROTATION_MASK) >> ROTATION_SHIFT),
+            flags & FLAG_MIRROR != 0
+        );
+
+        let data_len = read_u64(stream)?;
+
+        Ok(FrameHeader {
+            timestamp: u128::from_be_bytes(timestamp),
+            width,
+            height,
+            fourcc,
+            colorimetry,
+            preprocessed: flags & FLAG_PREPROCESSED != 0,
+            orientation,
+            data_len,
+        })
+    }
+}
+
+const RESPONSE_OK: u8 = 0;
+const RESPONSE_DROPPED: u8 = 1;
+const RESPONSE_BAD_REQUEST: u8 = 2;
+
+/// What the server answers to a frame.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    /// Raw model output.
+    Keypoints(Vec<f32>),
+    /// The server was too busy and skipped the frame.
+    Dropped,
+    /// The request could not be understood, with the reason.
+    BadRequest(String),
+}
+
+impl Response {
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> io::Result<()> {
+        match self {
+            Response::Keypoints(data) => {
+                stream.write_all(&[RESPONSE_OK])?;
+                stream.write_all(&(data.len() as u64).to_be_bytes())?;
+                let data: Vec<u8> = data.iter().flat_map(|d| d.to_be_bytes()).collect();
+                stream.write_all(&data)?;
+            }
+            Response::Dropped => stream.write_all(&[RESPONSE_DROPPED])?,
+            Response::BadRequest(reason) => {
+                // Cut to what readers accept, on a character boundary.
+                let mut end = reason.len().min(MAX_REASON_LEN);
+                while !reason.is_char_boundary(end) {
+                    end -= 1;
+                }
+                let reason = &reason[..end];
+                stream.write_all(&[RESPONSE_BAD_REQUEST])?;
+                stream.write_all(&(reason.len() as u64).to_be_bytes())?;
+                stream.write_all(reason.as_bytes())?;
+            }
+        }
+        stream.flush()
+    }
+
+    pub fn read_from<R: Read>(stream: &mut R) -> io::Result<Self> {
+        let mut status = [0u8; 1];
+        stream.read_exact(&mut status)?;
+        match status[0] {
+            RESPONSE_OK => {
+                // Number of elements of an array of f32 data, a single pose
+                // or whole multi-pose ones.
+                let data_len = read_u64(stream)?;
+                let valid = data_len == SINGLE_POSE_LEN as u64
+                    || (data_len <= MAX_KEYPOINTS_LEN as u64
+                        && data_len % MULTI_POSE_LEN as u64 == 0);
+                if !valid {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{} keypoint values are no model output", data_len)
+                    ));
+                }
+                let mut data_buf = vec![0u8; data_len as usize * 4];
+                stream.read_exact(&mut data_buf)?;
+                let data = data_buf
+                    .chunks_exact(4)
+                    .map(|d| f32::from_be_bytes([d[0], d[1], d[2], d[3]]))
+                    .collect();
+                Ok(Response::Keypoints(data))
+            }
+            RESPONSE_DROPPED => Ok(Response::Dropped),
+            RESPONSE_BAD_REQUEST => {
+                let reason_len = read_u64(stream)?;
+                if reason_len > MAX_REASON_LEN as u64 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("bad request reason of {} bytes", reason_len)
+                    ));
+                }
+                let mut reason = vec![0u8; reason_len as usize];
+                stream.read_exact(&mut reason)?;
+                Ok(Response::BadRequest(String::from_utf8_lossy(&reason).into_owned()))
+            }
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown response status {}", other)
+            )),
+        }
+    }
+}
+
+fn read_u64<R: Read>(stream: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    stream.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+fn read_u32<R: Read>(stream: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    stream.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
@@ -0,0 +1,341 @@
+//! Dependency-free `Canvas` drawing into a packed RGB24 buffer, for headless
+//! builds and for rendering without OpenCV.
+
+use crate::skeleton::{Canvas, Rgb};
+
+/// Width and height of a glyph of `FONT`, before scaling.
+pub const GLYPH_SIZE: [u32; 2] = [5, 7];
+// Horizontal advance per character, a glyph plus one column of spacing.
+const ADVANCE: i32 = GLYPH_SIZE[0] as i32 + 1;
+
+/// 5x7 glyphs for ASCII 0x20..=0x7e, one byte per column, bit 0 at the top.
+static FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x00, 0x08, 0x14, 0x22, 0x41], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x41, 0x22, 0x14, 0x08, 0x00], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3e, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x04, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x7f, 0x20, 0x18, 0x20, 0x7f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7f, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7f, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x08, 0x14, 0x54, 0x54, 0x3c], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x00, 0x7f, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
+
+/// Glyph of `c`, `?` for characters outside printable ASCII.
+fn glyph(c: char) -> &'static [u8; 5] {
+    let index = match c {
+        ' '..='~' => c as usize - ' ' as usize,
+        _ => '?' as usize - ' ' as usize,
+    };
+    &FONT[index]
+}
+
+/// A `Canvas` over a packed RGB24 buffer. Everything is clipped to the buffer.
+pub struct RgbCanvas<'a> {
+    rgb: &'a mut [u8],
+    width: u32,
+    height: u32,
+    text_scale: u32,
+}
+
+impl<'a> RgbCanvas<'a> {
+    /// Wraps a `width` x `height` RGB24 buffer, which must hold exactly 3 bytes per pixel.
+    pub fn new(rgb: &'a mut [u8], width: u32, height: u32) -> Self {
+        assert_eq!(rgb.len(), width as usize * height as usize * 3, "RGB24 buffer size");
+        Self { rgb, width, height, text_scale: 1 }
+    }
+
+    /// Draws text `scale` times the size of the 5x7 font.
+    pub fn with_text_scale(mut self, scale: u32) -> Self {
+        self.text_scale = scale.max(1);
+        self
+    }
+
+    pub fn fill(&mut self, color: Rgb) {
+        for pixel in self.rgb.chunks_exact_mut(3) {
+            pixel.copy_from_slice(&color);
+        }
+    }
+
+    pub fn pixel(&mut self, x: i32, y: i32, color: Rgb) {
+        if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+            let idx = (y as usize * self.width as usize + x as usize) * 3;
+            self.rgb[idx..idx + 3].copy_from_slice(&color);
+        }
+    }
+
+    /// Fills the pixels with `x0 <= x < x1` and `y0 <= y < y1`.
+    pub fn fill_rect(&mut self, [x0, y0]: [i32; 2], [x1, y1]: [i32; 2], color: Rgb) {
+        let clip = |v: i32, max: u32| v.clamp(0, max as i32) as usize;
+        let (x0, x1) = (clip(x0, self.width), clip(x1, self.width));
+        let (y0, y1) = (clip(y0, self.height), clip(y1, self.height));
+        for y in y0..y1 {
+            let row = y * self.width as usize * 3;
+            for pixel in self.rgb[row + x0 * 3..row + x1 * 3].chunks_exact_mut(3) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    // Cohen-Sutherland: the part of the segment on the canvas, with its ends
+    // rounded to pixels, `None` if the segment misses the canvas.
+    fn clip(&self, from: [i32; 2], to: [i32; 2]) -> Option<([i32; 2], [i32; 2])> {
+        const LEFT: u8 = 1;
+        const RIGHT: u8 = 2;
+        const TOP: u8 = 4;
+        const BOTTOM: u8 = 8;
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let (x_max, y_max) = (self.width as f64 - 1.0, self.height as f64 - 1.0);
+        let outcode = |[x, y]: [f64; 2]| {
+            let mut code = 0;
+            if x < 0.0 {
+                code |= LEFT;
+            } else if x > x_max {
+                code |= RIGHT;
+            }
+            if y < 0.0 {
+                code |= TOP;
+            } else if y > y_max {
+                code |= BOTTOM;
+            }
+            code
+        };
+
+        let (mut a, mut b) = (from.map(f64::from), to.map(f64::from));
+        let (mut code_a, mut code_b) = (outcode(a), outcode(b));
+        loop {
+            if code_a | code_b == 0 {
+                return Some((a.map(|v| v.round() as i32), b.map(|v| v.round() as i32)));
+            }
+            if code_a & code_b != 0 {
+                return None;
+            }
+            let code = if code_a != 0 { code_a } else { code_b };
+            let ([x0, y0], [x1, y1]) = (a, b);
+            let at_x = |x: f64| [x, y0 + (y1 - y0) * (x - x0) / (x1 - x0)];
+            let at_y = |y: f64| [x0 + (x1 - x0) * (y - y0) / (y1 - y0), y];
+            let point = if code & LEFT != 0 {
+                at_x(0.0)
+            } else if code & RIGHT != 0 {
+                at_x(x_max)
+            } else if code & TOP != 0 {
+                at_y(0.0)
+            } else {
+                at_y(y_max)
+            };
+            if code_a != 0 {
+                a = point;
+                code_a = outcode(a);
+            } else {
+                b = point;
+                code_b = outcode(b);
+            }
+        }
+    }
+
+    // Bresenham, one pixel wide, between the ends clipped to the canvas.
+    fn thin_line(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb) {
+        let Some(([x0, y0], [x1, y1])) = self.clip(from, to) else {
+            return;
+        };
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y, mut err) = (x0, y0, dx + dy);
+        loop {
+            self.pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+
+    // Every pixel whose center lies within `thickness / 2` of the segment,
+    // which also rounds the ends.
+    fn thick_line(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb, thickness: u32) {
+        let half = thickness as f32 / 2.0;
+        let reach = half.ceil() as i32;
+        let (x_min, x_max) = (from[0].min(to[0]), from[0].max(to[0]));
+        let (y_min, y_max) = (from[1].min(to[1]), from[1].max(to[1]));
+        let (x_min, x_max) = (x_min.saturating_sub(reach), x_max.saturating_add(reach));
+        let (y_min, y_max) = (y_min.saturating_sub(reach), y_max.saturating_add(reach));
+        let (x_min, x_max) = (x_min.max(0), x_max.min(self.width as i32 - 1));
+        let (y_min, y_max) = (y_min.max(0), y_max.min(self.height as i32 - 1));
+
+        let (ax, ay) = (from[0] as f32, from[1] as f32);
+        let (dx, dy) = (to[0] as f32 - ax, to[1] as f32 - ay);
+        let length_sq = dx * dx + dy * dy;
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let (px, py) = (x as f32 - ax, y as f32 - ay);
+                let t = if length_sq > 0.0 {
+                    ((px * dx + py * dy) / length_sq).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (ex, ey) = (px - t * dx, py - t * dy);
+                if ex * ex + ey * ey <= half * half {
+                    self.pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+impl Canvas for RgbCanvas<'_> {
+    fn size(&self) -> [u32; 2] {
+        [self.width, self.height]
+    }
+
+    fn line(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb, thickness: u32) {
+        if thickness <= 1 {
+            self.thin_line(from, to, color);
+        } else {
+            self.thick_line(from, to, color, thickness);
+        }
+    }
+
+    fn circle(&mut self, [cx, cy]: [i32; 2], radius: u32, color: Rgb) {
+        // In i64, so the squares cannot overflow. No canvas is large enough
+        // to tell a radius beyond `i32::MAX` apart.
+        let (cx, cy, r) = (cx as i64, cy as i64, radius.min(i32::MAX as u32) as i64);
+        // `r * r + r` rather than `r * r` avoids single-pixel nubs at the poles.
+        let limit = r * r + r;
+        let width = self.width as i64;
+        let clip = |x: i64| x.clamp(-1, width) as i32;
+        for y in (cy - r).max(0)..=(cy + r).min(self.height as i64 - 1) {
+            let dy = y - cy;
+            let dx = ((limit - dy * dy) as f64).sqrt() as i64;
+            let y = y as i32;
+            self.fill_rect([clip(cx - dx), y], [clip(cx + dx + 1), y + 1], color);
+        }
+    }
+
+    fn rectangle(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb, thickness: u32) {
+        let (x0, x1) = (from[0].min(to[0]), from[0].max(to[0]).saturating_add(1));
+        let (y0, y1) = (from[1].min(to[1]), from[1].max(to[1]).saturating_add(1));
+        let t = thickness.clamp(1, i32::MAX as u32) as i32;
+        self.fill_rect([x0, y0], [x1, y0.saturating_add(t)], color);
+        self.fill_rect([x0, y1.saturating_sub(t)], [x1, y1], color);
+        self.fill_rect([x0, y0], [x0.saturating_add(t), y1], color);
+        self.fill_rect([x1.saturating_sub(t), y0], [x1, y1], color);
+    }
+
+    fn text(&mut self, [x, y]: [i32; 2], text: &str, color: Rgb) {
+        // In i64, text may start anywhere and run off the canvas.
+        let scale = self.text_scale as i64;
+        let clip = |v: i64| v.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+        for (i, c) in text.chars().enumerate() {
+            let left = x as i64 + i as i64 * ADVANCE as i64 * scale;
+            if left >= self.width as i64 {
+                break;
+            }
+            for (col, bits) in glyph(c).iter().enumerate() {
+                for row in 0..GLYPH_SIZE[1] as i64 {
+                    if bits >> row & 1 == 1 {
+                        let px = left + col as i64 * scale;
+                        let py = y as i64 + row * scale;
+                        let (x0, y0) = (clip(px), clip(py));
+                        self.fill_rect([x0, y0], [clip(px + scale), clip(py + scale)], color);
+                    }
+                }
+            }
+        }
+    }
+}
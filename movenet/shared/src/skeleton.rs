@@ -0,0 +1,318 @@
+//! COCO skeleton topology, pose parsing and rendering onto any `Canvas`.
+//!
+//! Keypoints come from the model normalized to its square, letterboxed input:
+//! `[y, x, score]` per keypoint, in the order of `KEYPOINT_NAMES`.
+
+use crate::format::Orientation;
+use crate::letterbox::Letterbox;
+
+/// An RGB color.
+pub type Rgb = [u8; 3];
+
+pub const KEYPOINT_COUNT: usize = 17;
+/// Values per person of single-pose models: `[y, x, score]` per keypoint.
+pub const SINGLE_POSE_LEN: usize = KEYPOINT_COUNT * 3;
+/// Values per person of multi-pose models: the keypoints, then
+/// `[y_min, x_min, y_max, x_max, score]`.
+pub const MULTI_POSE_LEN: usize = SINGLE_POSE_LEN + 5;
+
+pub const KEYPOINT_NAMES: [&str; KEYPOINT_COUNT] = [
+    "nose",
+    "left_eye",
+    "right_eye",
+    "left_ear",
+    "right_ear",
+    "left_shoulder",
+    "right_shoulder",
+    "left_elbow",
+    "right_elbow",
+    "left_wrist",
+    "right_wrist",
+    "left_hip",
+    "right_hip",
+    "left_knee",
+    "right_knee",
+    "left_ankle",
+    "right_ankle",
+];
+
+/// Pairs of keypoint indices joined by a limb.
+pub const EDGES: [(usize, usize); 18] = [
+    (0, 1), (0, 2), (1, 3), (2, 4),
+    (0, 5), (0, 6), (5, 6),
+    (5, 7), (7, 9), (6, 8), (8, 10),
+    (5, 11), (6, 12), (11, 12),
+    (11, 13), (13, 15), (12, 14), (14, 16),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+    Center,
+}
+
+impl Side {
+    /// Side of a keypoint: odd indices are left, even ones right, the nose is central.
+    pub fn of_keypoint(index: usize) -> Self {
+        match index {
+            0 => Side::Center,
+            i if i % 2 == 1 => Side::Left,
+            _ => Side::Right,
+        }
+    }
+
+    /// Side of a limb: the side of its ends when they agree, central otherwise.
+    pub fn of_edge(edge: (usize, usize)) -> Self {
+        match (Side::of_keypoint(edge.0), Side::of_keypoint(edge.1)) {
+            (a, b) if a == b => a,
+            _ => Side::Center,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Keypoint {
+    pub x: f32,
+    pub y: f32,
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub keypoints: [Keypoint; KEYPOINT_COUNT],
+    /// Person score of multi-pose models, the mean keypoint score otherwise.
+    pub score: f32,
+    /// `[x_min, y_min, x_max, y_max]` reported by multi-pose models.
+    pub bbox: Option<[f32; 4]>,
+}
+
+impl Pose {
+    /// Parses one person of `SINGLE_POSE_LEN` or `MULTI_POSE_LEN` values.
+    pub fn parse(values: &[f32]) -> Option<Self> {
+        if values.len() != SINGLE_POSE_LEN && values.len() != MULTI_POSE_LEN {
+            return None;
+        }
+        let mut keypoints = [Keypoint::default(); KEYPOINT_COUNT];
+        for (keypoint, v) in keypoints.iter_mut().zip(values.chunks_exact(3)) {
+            *keypoint = Keypoint { x: v[1], y: v[0], score: v[2] };
+        }
+        let (score, bbox) = match values.get(SINGLE_POSE_LEN..) {
+            Some(&[y_min, x_min, y_max, x_max, score]) => {
+                (score, Some([x_min, y_min, x_max, y_max]))
+            }
+            _ => {
+                let total: f32 = keypoints.iter().map(|k| k.score).sum();
+                (total / KEYPOINT_COUNT as f32, None)
+            }
+        };
+        Some(Self { keypoints, score, bbox })
+    }
+
+    /// Parses a model output: one single-pose person, or any number of
+    /// multi-pose ones. Anything else yields no poses.
+    pub fn parse_all(output: &[f32]) -> Vec<Self> {
+        let stride = if output.len() == SINGLE_POSE_LEN { SINGLE_POSE_LEN } else { MULTI_POSE_LEN };
+        if output.is_empty() || !output.len().is_multiple_of(stride) {
+            return Vec::new();
+        }
+        output.chunks_exact(stride).filter_map(Pose::parse).collect()
+    }
+
+    /// `[x_min, y_min, x_max, y_max]`, from the model when it reports one,
+    /// otherwise around the keypoints scoring above `threshold`.
+    pub fn bounding_box(&self, threshold: f32) -> Option<[f32; 4]> {
+        if self.bbox.is_some() {
+            return self.bbox;
+        }
+        self.keypoints
+            .iter()
+            .filter(|k| k.score > threshold)
+            .fold(None, |bbox, k| {
+                let [x0, y0, x1, y1] = bbox.unwrap_or([k.x, k.y, k.x, k.y]);
+                Some([x0.min(k.x), y0.min(k.y), x1.max(k.x), y1.max(k.y)])
+            })
+    }
+}
+
+/// Maps the coordinates of a raw model output, single- or multi-pose, from the
+/// oriented model input back onto the captured frame. Bounding boxes are
+/// kept as `[y_min, x_min, y_max, x_max]`. Outputs of any other length are
+/// left as they are.
+pub fn restore_orientation(output: &mut [f32], orientation: Orientation) {
+    if orientation == Orientation::default() {
+        return;
+    }
+    let stride = if output.len() == SINGLE_POSE_LEN { SINGLE_POSE_LEN } else { MULTI_POSE_LEN };
+    if output.is_empty() || !output.len().is_multiple_of(stride) {
+        return;
+    }
+    for person in output.chunks_exact_mut(stride) {
+        let (keypoints, rest) = person.split_at_mut(SINGLE_POSE_LEN);
+        for k in keypoints.chunks_exact_mut(3) {
+            let [x, y] = orientation.restore_point(k[1], k[0]);
+            k[0] = y;
+            k[1] = x;
+        }
+        if let [y_min, x_min, y_max, x_max, _] = rest {
+            let [x0, y0] = orientation.restore_point(*x_min, *y_min);
+            let [x1, y1] = orientation.restore_point(*x_max, *y_max);
+            *y_min = y0.min(y1);
+            *x_min = x0.min(x1);
+            *y_max = y0.max(y1);
+            *x_max = x0.max(x1);
+        }
+    }
+}
+
+/// Maps normalized model coordinates back onto a `width` x `height` frame,
+/// undoing the letterbox that padded the frame to a square.
+pub fn project(x: f32, y: f32, width: u32, height: u32) -> [i32; 2] {
+    let base = width.max(height);
+    let letterbox = Letterbox::new([width, height], [base, base]);
+    letterbox.inverse([x * base as f32, y * base as f32]).map(|v| v.round() as i32)
+}
+
+/// Drawing primitives a renderer needs. Coordinates are in pixels and may lie
+/// outside the canvas; implementations clip.
+pub trait Canvas {
+    /// `[width, height]` in pixels.
+    fn size(&self) -> [u32; 2];
+    fn line(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb, thickness: u32);
+    /// A filled disc.
+    fn circle(&mut self, center: [i32; 2], radius: u32, color: Rgb);
+    /// An outlined rectangle between two opposite corners.
+    fn rectangle(&mut self, from: [i32; 2], to: [i32; 2], color: Rgb, thickness: u32);
+    /// A single line of text with its top-left corner at `origin`.
+    fn text(&mut self, origin: [i32; 2], text: &str, color: Rgb);
+}
+
+/// How `draw` renders poses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkeletonStyle {
+    /// Keypoints scoring at or below this are not drawn, nor are limbs touching them.
+    pub threshold: f32,
+    pub joint_radius: u32,
+    pub limb_thickness: u32,
+    pub left: Rgb,
+    pub right: Rgb,
+    pub center: Rgb,
+    /// One color per person when several are drawn, cycled. Left and right
+    /// are then told apart by lightening and darkening it.
+    pub person_colors: Vec<Rgb>,
+    /// Darkens joints and limbs the lower their score.
+    pub shade_by_confidence: bool,
+    /// Names the keypoints.
+    pub labels: bool,
+    /// Frames each person with their score.
+    pub bounding_box: bool,
+}
+
+impl Default for SkeletonStyle {
+    fn default() -> Self {
+        Self {
+            threshold: 0.25,
+            joint_radius: 4,
+            limb_thickness: 2,
+            left: [0, 255, 0],
+            right: [255, 128, 0],
+            center: [0, 200, 255],
+            person_colors: vec![
+                [230, 25, 75],
+                [60, 180, 75],
+                [255, 225, 25],
+                [0, 130, 200],
+                [245, 130, 48],
+                [145, 30, 180],
+            ],
+            shade_by_confidence: true,
+            labels: false,
+            bounding_box: false,
+        }
+    }
+}
+
+fn mix(color: Rgb, target: u8, amount: f32) -> Rgb {
+    color.map(|c| (c as f32 + (target as f32 - c as f32) * amount).round() as u8)
+}
+
+impl SkeletonStyle {
+    /// Color of a joint or limb of `side` scoring `score`, for person `person`
+    /// of `people`.
+    pub fn color(&self, side: Side, score: f32, person: usize, people: usize) -> Rgb {
+        let base = if people > 1 && !self.person_colors.is_empty() {
+            let color = self.person_colors[person % self.person_colors.len()];
+            match side {
+                Side::Left => mix(color, 255, 0.35),
+                Side::Right => mix(color, 0, 0.35),
+                Side::Center => color,
+            }
+        } else {
+            match side {
+                Side::Left => self.left,
+                Side::Right => self.right,
+                Side::Center => self.center,
+            }
+        };
+        if !self.shade_by_confidence {
+            return base;
+        }
+        // Full color at a score of 1, 40% of it just above the threshold.
+        let confidence = ((score - self.threshold) / (1.0 - self.threshold).max(f32::EPSILON))
+            .clamp(0.0, 1.0);
+        mix(base, 0, 0.6 * (1.0 - confidence))
+    }
+
+    fn person_color(&self, person: usize, people: usize) -> Rgb {
+        if people > 1 && !self.person_colors.is_empty() {
+            self.person_colors[person % self.person_colors.len()]
+        } else {
+            self.center
+        }
+    }
+}
+
+/// Draws the skeletons of `poses` in `style`. People a multi-pose model scores
+/// at or below the style's threshold are skipped.
+pub fn draw<C: Canvas + ?Sized>(canvas: &mut C, poses: &[Pose], style: &SkeletonStyle) {
+    let [width, height] = canvas.size();
+    let people = poses.len();
+    for (person, pose) in poses.iter().enumerate() {
+        if pose.bbox.is_some() && pose.score <= style.threshold {
+            continue;
+        }
+        let points = pose.keypoints.map(|k| project(k.x, k.y, width, height));
+        let visible = pose.keypoints.map(|k| k.score > style.threshold);
+
+        if style.bounding_box {
+            if let Some([x0, y0, x1, y1]) = pose.bounding_box(style.threshold) {
+                let color = style.person_color(person, people);
+                let from = project(x0, y0, width, height);
+                canvas.rectangle(from, project(x1, y1, width, height), color, 1);
+                let label = format!("#{} {:.2}", person, pose.score);
+                canvas.text([from[0], from[1] - 12], &label, color);
+            }
+        }
+
+        for &(a, b) in &EDGES {
+            if visible[a] && visible[b] {
+                let score = pose.keypoints[a].score.min(pose.keypoints[b].score);
+                let color = style.color(Side::of_edge((a, b)), score, person, people);
+                canvas.line(points[a], points[b], color, style.limb_thickness);
+            }
+        }
+
+        for (index, keypoint) in pose.keypoints.iter().enumerate() {
+            if !visible[index] {
+                continue;
+            }
+            let color = style.color(Side::of_keypoint(index), keypoint.score, person, people);
+            canvas.circle(points[index], style.joint_radius, color);
+            if style.labels {
+                let offset = style.joint_radius as i32 + 2;
+                let origin = [points[index][0] + offset, points[index][1] + offset];
+                canvas.text(origin, KEYPOINT_NAMES[index], color);
+            }
+        }
+    }
+}
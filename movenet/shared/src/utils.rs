@@ -1,111 +1,261 @@
-use std::fs::File;
-use std::io::Write;
-
-use yuv::YUV;
-use yuv::convert::RGBConvert;
-use yuv::color::{Range, MatrixCoefficients};
-
-pub fn save_bmp(filename: &str, buffer: &[u8], w: usize, h: usize) {
-	let mut file = File::options()
-							.write(true)
-							.read(true)
-							.create(true)
-							.open(filename)
-							.unwrap();
-
-	let filesize = 54 + 3 * w * h;
-	let mut img = vec![0u8; 3 * w * h];
-
-	for i in 0..w {
-		for j in 0..h {
-			let x = i;
-			let y = j;
-
-			img[(x + y * w) * 3 + 2] = buffer[(j * w + i) * 3 + 0];
-            img[(x + y * w) * 3 + 1] = buffer[(j * w + i) * 3 + 1];
-            img[(x + y * w) * 3 + 0] = buffer[(j * w + i) * 3 + 2];
-		}
+use std::io;
+use std::path::Path;
+
+use crate::codec;
+use crate::convert::{self, Coefficients, InverseCoefficients};
+use crate::format::{Colorimetry, PixelFormat, YuvMatrix, YuvRange};
+use crate::frame::Frame;
+use crate::image;
+
+pub struct EasyConverter {
+	colorimetry: Colorimetry,
+	coefficients: Coefficients,
+	inverse: InverseCoefficients
+}
+
+impl EasyConverter {
+	pub fn new(matrix: YuvMatrix, range: YuvRange) -> Self {
+		Self::with_colorimetry(Colorimetry::new(matrix, range))
 	}
 
-	let mut bmpfileheader: [u8; 14] = [ 'B' as u8, 'M' as u8, 0, 0, 0, 0, 0, 0, 0, 0, 54, 0, 0, 0 ];
-	let mut bmpinfoheader: [u8; 40] = [0u8; 40];
-	let val_to_change: [u8; 16] = [ 40, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 24, 0 ];
-	for (i, val) in val_to_change.iter().enumerate() {
-		bmpinfoheader[i] = *val;
+	pub fn with_colorimetry(colorimetry: Colorimetry) -> Self {
+		Self {
+			colorimetry,
+			coefficients: Coefficients::new(colorimetry),
+			inverse: InverseCoefficients::new(colorimetry)
+		}
 	}
-	let bmppad: [u8; 3] = [ 0, 0, 0 ];
 
-	bmpfileheader[2] = (filesize) as u8;
-    bmpfileheader[3] = (filesize >> 8) as u8;
-    bmpfileheader[4] = (filesize >> 16) as u8;
-    bmpfileheader[5] = (filesize >> 24) as u8;
+	pub fn colorimetry(&self) -> Colorimetry {
+		self.colorimetry
+	}
 
-    bmpinfoheader[4] = (w) as u8;
-    bmpinfoheader[5] = (w >> 8) as u8;
-    bmpinfoheader[6] = (w >> 16) as u8;
-    bmpinfoheader[7] = (w >> 24) as u8;
-    bmpinfoheader[8] = (h) as u8;
-    bmpinfoheader[9] = (h >> 8) as u8;
-    bmpinfoheader[10] = (h >> 16) as u8;
-    bmpinfoheader[11] = (h >> 24) as u8;
+	fn convert_and_push(&self, y: u8, u: u8, v: u8, result_arr: &mut Vec<u8>) {
+		result_arr.extend_from_slice(&self.coefficients.pixel(y, u, v));
+	}
 
-	file.write_all(&bmpfileheader).unwrap();
-	file.write_all(&bmpinfoheader).unwrap();
+	pub fn rgb(&self, result: &[u8]) -> Vec<u8> {	
+		let mut rgb_result = vec![0u8; convert::rgb_len(result.len())];
+		self.rgb_into(result, &mut rgb_result);
+		rgb_result
+	}
 
-	for i in 0..h {
-		let start_index = w * (h - i - 1) * 3;
-		let end_index = start_index + 3 * w;
-		file.write_all(&img[start_index..end_index]).unwrap();
-		file.write_all(&bmppad[0..((4 - (w * 3) % 4) % 4)]).unwrap();
+	/// Converts a YUYV frame into `rgb` without allocating. `rgb` must hold
+	/// exactly 3 bytes per pixel.
+	pub fn rgb_into(&self, yuyv: &[u8], rgb: &mut [u8]) {
+		convert::yuyv_to_rgb(&self.coefficients, yuyv, rgb);
 	}
-}
 
-pub struct EasyConverter {
-	converter: RGBConvert
-}
+	/// Like `rgb_into`, splitting the frame across `threads` threads.
+	pub fn rgb_into_parallel(&self, yuyv: &[u8], rgb: &mut [u8], threads: usize) {
+		convert::yuyv_to_rgb_parallel(&self.coefficients, yuyv, rgb, threads);
+	}
 
-impl EasyConverter {
-	pub fn new() -> Self {
-		let converter = RGBConvert::<u8>::new(
-			Range::Full, 
-			MatrixCoefficients::Identity
-		).unwrap();
-		Self { converter }
-	}
-
-	fn convert_and_push(&self, yuv_pix: YUV<u8>, result_arr: &mut Vec<u8>) {
-		let rgb_pix = self.converter.to_rgb(yuv_pix);
-		result_arr.push(rgb_pix.r);
-		result_arr.push(rgb_pix.g);
-		result_arr.push(rgb_pix.b);
-	}
-
-	pub fn rgb(&self, result: &Vec<u8>) -> Vec<u8> {	
-		let mut rgb_result = Vec::<u8>::new();
-	
-		let arr_len = result.len();
-		let step: usize = 4;
-		for i in (0..arr_len).step_by(step) {
-			let y1 = result[i];
-	
-			if i + 1 >= arr_len { break; }
-			let u = result[i + 1];
-			if i + 2 >= arr_len { break; }
-			let y2 = result[i + 2];
-			if i + 3 >= arr_len { break; }
-			let v = result[i + 3];
-	
-			let yuv1 = YUV { y: y1, u, v };
-			let yuv2 = YUV { y: y2, u, v };
-	
-			self.convert_and_push(yuv1, &mut rgb_result);
-			self.convert_and_push(yuv2, &mut rgb_result);
+	/// Converts a planar 4:2:0 frame, `chroma_at(x, y)` giving the offsets of
+	/// the U and V samples of the chroma block at `(x, y)`.
+	fn rgb_420<F>(&self, data: &[u8], width: usize, height: usize, chroma_at: F) -> Vec<u8>
+		where F: Fn(usize, usize) -> (usize, usize)
+	{
+		let mut rgb_result = Vec::<u8>::with_capacity(width * height * 3);
+		for j in 0..height {
+			for i in 0..width {
+				let (u_idx, v_idx) = chroma_at(i / 2, j / 2);
+				self.convert_and_push(data[j * width + i], data[u_idx], data[v_idx], &mut rgb_result);
+			}
 		}
 		rgb_result
 	}
 
-	pub fn convert_write_bmp(&self, filename: &str, result: &Vec<u8>) {
-		let out = self.rgb(result);
-		save_bmp(filename, &out, 1280, 720);
+	/// Converts an NV12 frame: a Y plane followed by one interleaved `U V` plane.
+	pub fn rgb_from_nv12(&self, data: &[u8], width: usize, height: usize) -> Vec<u8> {
+		let chroma_start = width * height;
+		let chroma_stride = width.div_ceil(2) * 2;
+		self.rgb_420(data, width, height, |x, y| {
+			let idx = chroma_start + y * chroma_stride + x * 2;
+			(idx, idx + 1)
+		})
+	}
+
+	/// Converts an I420 (`YU12`) frame: Y, U and V planes one after another.
+	pub fn rgb_from_i420(&self, data: &[u8], width: usize, height: usize) -> Vec<u8> {
+		let chroma_stride = width.div_ceil(2);
+		let u_start = width * height;
+		let v_start = u_start + chroma_stride * height.div_ceil(2);
+		self.rgb_420(data, width, height, |x, y| {
+			let offset = y * chroma_stride + x;
+			(u_start + offset, v_start + offset)
+		})
+	}
+
+	/// Converts a luma-only frame to grey RGB. V4L2 `GREY` is full range
+	/// whatever the colorimetry, so each sample is copied as is.
+	pub fn rgb_from_grey(&self, data: &[u8]) -> Vec<u8> {
+		data.iter().flat_map(|&y| [y, y, y]).collect()
+	}
+
+	/// Converts an uncompressed frame in `format` to packed RGB24, `None` for
+	/// compressed formats. `data` must be tightly packed.
+	pub fn rgb_from_format(
+		&self, data: &[u8], width: usize, height: usize, format: PixelFormat
+	) -> Option<Vec<u8>> {
+		match format {
+			PixelFormat::Yuyv => Some(self.rgb(data)),
+			PixelFormat::Rgb24 => Some(data.to_vec()),
+			PixelFormat::Nv12 => Some(self.rgb_from_nv12(data, width, height)),
+			PixelFormat::I420 => Some(self.rgb_from_i420(data, width, height)),
+			PixelFormat::Grey => Some(self.rgb_from_grey(data)),
+			PixelFormat::Mjpeg => None,
+		}
+	}
+
+	/// Converts a frame to packed RGB24 with this converter's colorimetry,
+	/// keeping its timestamp, sequence number and capture info.
+	pub fn convert(&self, frame: &Frame) -> io::Result<Frame> {
+		let data = frame.packed();
+		let [width, height] = frame.size();
+		let (w, h) = (width as usize, height as usize);
+		let rgb = match self.rgb_from_format(&data, w, h, frame.format()) {
+			Some(rgb) => rgb,
+			None => codec::to_rgb(&data, width, height, frame.format(), frame.colorimetry())?,
+		};
+		Ok(Frame::new(rgb, width, height, PixelFormat::Rgb24)?.with_metadata_of(frame))
 	}
+
+	/// Encodes packed RGB24 as YUYV, averaging the chroma of each pixel pair.
+	/// `width` must be even.
+	pub fn yuyv_from_rgb(&self, rgb: &[u8], width: usize, height: usize) -> Vec<u8> {
+		assert!(width.is_multiple_of(2), "YUYV needs an even width, got {}", width);
+		let mut yuyv = Vec::<u8>::with_capacity(width * height * 2);
+		for pair in rgb[..width * height * 3].chunks_exact(6) {
+			let (left, right) = ([pair[0], pair[1], pair[2]], [pair[3], pair[4], pair[5]]);
+			let sum = [0, 1, 2].map(|c| left[c] as u32 + right[c] as u32);
+			let [u, v] = self.inverse.chroma(sum, 2);
+			yuyv.extend_from_slice(&[self.inverse.luma(left), u, self.inverse.luma(right), v]);
+		}
+		yuyv
+	}
+
+	/// Encodes packed RGB24 as a Y plane followed by 4:2:0 chroma, calling
+	/// `put_chroma(x, y, [u, v])` for each chroma block. Blocks on an odd edge
+	/// average the pixels they cover.
+	fn rgb_to_420<F>(&self, rgb: &[u8], width: usize, height: usize, mut put_chroma: F) -> Vec<u8>
+		where F: FnMut(&mut [u8], usize, usize, [u8; 2])
+	{
+		let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+		let format_len = PixelFormat::I420.frame_len(width as u32, height as u32).unwrap();
+		let mut out = vec![0u8; format_len];
+		for (luma, pixel) in out[..width * height].iter_mut().zip(rgb.chunks_exact(3)) {
+			*luma = self.inverse.luma([pixel[0], pixel[1], pixel[2]]);
+		}
+		for y in 0..chroma_height {
+			for x in 0..chroma_width {
+				let mut sum = [0u32; 3];
+				let mut count = 0;
+				for j in 2 * y..(2 * y + 2).min(height) {
+					for i in 2 * x..(2 * x + 2).min(width) {
+						let idx = (j * width + i) * 3;
+						for c in 0..3 {
+							sum[c] += rgb[idx + c] as u32;
+						}
+						count += 1;
+					}
+				}
+				put_chroma(&mut out[width * height..], x, y, self.inverse.chroma(sum, count));
+			}
+		}
+		out
+	}
+
+	/// Encodes packed RGB24 as NV12, the inverse of `rgb_from_nv12`.
+	pub fn nv12_from_rgb(&self, rgb: &[u8], width: usize, height: usize) -> Vec<u8> {
+		let chroma_stride = width.div_ceil(2) * 2;
+		self.rgb_to_420(rgb, width, height, |chroma, x, y, [u, v]| {
+			let idx = y * chroma_stride + x * 2;
+			chroma[idx] = u;
+			chroma[idx + 1] = v;
+		})
+	}
+
+	/// Encodes packed RGB24 as I420, the inverse of `rgb_from_i420`.
+	pub fn i420_from_rgb(&self, rgb: &[u8], width: usize, height: usize) -> Vec<u8> {
+		let chroma_stride = width.div_ceil(2);
+		let v_start = chroma_stride * height.div_ceil(2);
+		self.rgb_to_420(rgb, width, height, |chroma, x, y, [u, v]| {
+			chroma[y * chroma_stride + x] = u;
+			chroma[v_start + y * chroma_stride + x] = v;
+		})
+	}
+
+	/// Full range luma of packed RGB24, the inverse of `rgb_from_grey`.
+	pub fn grey_from_rgb(&self, rgb: &[u8]) -> Vec<u8> {
+		let full = Colorimetry::new(self.colorimetry.matrix, YuvRange::Full);
+		let inverse = InverseCoefficients::new(full);
+		rgb.chunks_exact(3).map(|pixel| inverse.luma([pixel[0], pixel[1], pixel[2]])).collect()
+	}
+
+	/// Encodes packed RGB24 in an uncompressed `format`, `None` for compressed formats.
+	pub fn rgb_to_format(
+		&self, rgb: &[u8], width: usize, height: usize, format: PixelFormat
+	) -> Option<Vec<u8>> {
+		match format {
+			PixelFormat::Yuyv => Some(self.yuyv_from_rgb(rgb, width, height)),
+			PixelFormat::Rgb24 => Some(rgb.to_vec()),
+			PixelFormat::Nv12 => Some(self.nv12_from_rgb(rgb, width, height)),
+			PixelFormat::I420 => Some(self.i420_from_rgb(rgb, width, height)),
+			PixelFormat::Grey => Some(self.grey_from_rgb(rgb)),
+			PixelFormat::Mjpeg => None,
+		}
+	}
+
+	/// Converts a frame to an uncompressed `format` with this converter's
+	/// colorimetry, keeping its timestamp, sequence number and capture info.
+	pub fn encode(&self, frame: &Frame, format: PixelFormat) -> io::Result<Frame> {
+		let rgb = self.convert(frame)?;
+		let [width, height] = frame.size();
+		if format == PixelFormat::Yuyv && !width.is_multiple_of(2) {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("YUYV needs an even width, got {}", width)
+			));
+		}
+		let data = self.rgb_to_format(rgb.data(), width as usize, height as usize, format)
+			.ok_or_else(|| io::Error::new(
+				io::ErrorKind::Unsupported,
+				format!("cannot encode {} frames", format)
+			))?;
+		Ok(Frame::new(data, width, height, format)?
+			.with_colorimetry(self.colorimetry)
+			.with_metadata_of(frame))
+	}
+
+	/// Writes a frame as BMP after converting it with this converter's colorimetry.
+	pub fn convert_write_bmp(&self, filename: &str, frame: &Frame) -> io::Result<()> {
+		Ok(image::write_as(filename, &self.convert(frame)?, image::ImageFormat::Bmp)?)
+	}
+}
+
+impl Default for EasyConverter {
+	/// BT.601 limited range, what UVC webcams emit.
+	fn default() -> Self {
+		Self::with_colorimetry(Colorimetry::default())
+	}
+}
+
+/// Loads an image as a YUYV frame in `colorimetry`, the way a webcam would
+/// deliver it. An odd rightmost column is dropped, YUYV needs an even width.
+pub fn load_yuyv<P: AsRef<Path>>(path: P, colorimetry: Colorimetry) -> io::Result<Frame> {
+	let rgb = image::read(path)?;
+	let [width, height] = rgb.size();
+	let even_width = width & !1;
+	let rgb = if even_width == width {
+		rgb
+	} else {
+		let cropped = rgb.data()
+			.chunks_exact(width as usize * 3)
+			.flat_map(|row| &row[..even_width as usize * 3])
+			.copied()
+			.collect();
+		Frame::new(cropped, even_width, height, PixelFormat::Rgb24)?
+	};
+	EasyConverter::with_colorimetry(colorimetry).encode(&rgb, PixelFormat::Yuyv)
 }
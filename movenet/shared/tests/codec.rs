@@ -0,0 +1,168 @@
+//! Round trips of frames through every payload encoding.
+
+use std::io;
+
+use shared::codec;
+use shared::format::{Colorimetry, PixelFormat, YuvMatrix, YuvRange};
+use shared::protocol::PayloadEncoding;
+use shared::utils::EasyConverter;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+// Smooth gradients, which JPEG keeps close to the original.
+fn gradient() -> Vec<u8> {
+    (0..HEIGHT)
+        .flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+        .flat_map(|(x, y)| [(x * 4) as u8, (y * 5) as u8, ((x + y) * 2) as u8])
+        .collect()
+}
+
+fn yuyv() -> Vec<u8> {
+    EasyConverter::default().yuyv_from_rgb(&gradient(), WIDTH as usize, HEIGHT as usize)
+}
+
+fn round_trip(encoding: PayloadEncoding, frame: &[u8], format: PixelFormat) -> Vec<u8> {
+    let colorimetry = Colorimetry::default();
+    let (payload, wire_format) =
+        codec::encode(encoding, frame, WIDTH, HEIGHT, format, colorimetry).unwrap();
+    codec::decode(encoding, &payload, WIDTH, HEIGHT, wire_format, colorimetry).unwrap()
+}
+
+fn max_diff(a: &[u8], b: &[u8]) -> u8 {
+    assert_eq!(a.len(), b.len());
+    a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+}
+
+#[test]
+fn lossless_encodings_decode_to_the_frame_as_rgb() {
+    let yuyv = yuyv();
+    let expected = EasyConverter::default().rgb(&yuyv);
+    let rgb = gradient();
+    let lossless = [PayloadEncoding::Raw, PayloadEncoding::Rgb, PayloadEncoding::Zstd { level: 3 }];
+    for encoding in lossless {
+        assert_eq!(round_trip(encoding, &yuyv, PixelFormat::Yuyv), expected, "{}", encoding);
+        assert_eq!(round_trip(encoding, &rgb, PixelFormat::Rgb24), rgb, "{}", encoding);
+    }
+}
+
+#[test]
+fn zstd_unpacks_to_the_original_bytes() {
+    let yuyv = yuyv();
+    let encoding = PayloadEncoding::Zstd { level: 19 };
+    let colorimetry = Colorimetry::default();
+    let (payload, format) =
+        codec::encode(encoding, &yuyv, WIDTH, HEIGHT, PixelFormat::Yuyv, colorimetry).unwrap();
+    assert_eq!(format, PixelFormat::Yuyv);
+    assert!(payload.len() < yuyv.len());
+    let unpacked = codec::unpack(encoding, &payload, WIDTH, HEIGHT, format).unwrap();
+    assert_eq!(unpacked, yuyv);
+}
+
+#[test]
+fn jpeg_stays_close_to_the_frame() {
+    let rgb = gradient();
+    let decoded = round_trip(PayloadEncoding::Jpeg { quality: 95 }, &rgb, PixelFormat::Rgb24);
+    let diff = max_diff(&decoded, &rgb);
+    assert!(diff <= 16, "max difference {}", diff);
+
+    let yuyv = yuyv();
+    let expected = EasyConverter::default().rgb(&yuyv);
+    let decoded = round_trip(PayloadEncoding::Jpeg { quality: 95 }, &yuyv, PixelFormat::Yuyv);
+    let diff = max_diff(&decoded, &expected);
+    assert!(diff <= 16, "max difference {}", diff);
+}
+
+#[test]
+fn mjpeg_is_sent_as_is() {
+    let colorimetry = Colorimetry::default();
+    let encoding = PayloadEncoding::Jpeg { quality: 80 };
+    let rgb = gradient();
+    let (jpeg, _) =
+        codec::encode(encoding, &rgb, WIDTH, HEIGHT, PixelFormat::Rgb24, colorimetry).unwrap();
+    let (payload, format) =
+        codec::encode(encoding, &jpeg, WIDTH, HEIGHT, PixelFormat::Mjpeg, colorimetry).unwrap();
+    assert_eq!((payload, format), (jpeg, PixelFormat::Mjpeg));
+}
+
+#[test]
+fn zstd_bombs_are_rejected() {
+    // 16 MiB of zeros compress to a few hundred bytes.
+    let bomb = zstd::encode_all(&vec![0u8; 16 << 20][..], 3).unwrap();
+    let encoding = PayloadEncoding::Zstd { level: 3 };
+    for format in [PixelFormat::Yuyv, PixelFormat::Mjpeg] {
+        let err = codec::unpack(encoding, &bomb, WIDTH, HEIGHT, format).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", format);
+    }
+    // Exactly a frame is fine.
+    let frame = vec![0u8; PixelFormat::Yuyv.frame_len(WIDTH, HEIGHT).unwrap()];
+    let payload = zstd::encode_all(&frame[..], 3).unwrap();
+    assert!(codec::unpack(encoding, &payload, WIDTH, HEIGHT, PixelFormat::Yuyv).is_ok());
+}
+
+#[test]
+fn malformed_payloads_are_invalid_data() {
+    let colorimetry = Colorimetry::default();
+    let short = vec![0u8; 10];
+    for (encoding, format) in [
+        (PayloadEncoding::Raw, PixelFormat::Yuyv),
+        (PayloadEncoding::Zstd { level: 3 }, PixelFormat::Yuyv),
+        (PayloadEncoding::Jpeg { quality: 80 }, PixelFormat::Mjpeg),
+    ] {
+        let err = codec::decode(encoding, &short, WIDTH, HEIGHT, format, colorimetry).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{}", encoding);
+    }
+    let err = codec::decode(
+        PayloadEncoding::Raw, &short, u32::MAX, u32::MAX, PixelFormat::Rgb24, colorimetry
+    ).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn grey_is_full_range_whatever_the_colorimetry() {
+    let grey: Vec<u8> = (0..WIDTH * HEIGHT).map(|i| (i % 256) as u8).collect();
+    let expected: Vec<u8> = grey.iter().flat_map(|&y| [y, y, y]).collect();
+    for colorimetry in [
+        Colorimetry::default(),
+        Colorimetry::new(YuvMatrix::Bt709, YuvRange::Full),
+        Colorimetry::new(YuvMatrix::Bt2020, YuvRange::Limited),
+    ] {
+        let rgb = codec::decode(
+            PayloadEncoding::Raw, &grey, WIDTH, HEIGHT, PixelFormat::Grey, colorimetry
+        ).unwrap();
+        assert_eq!(rgb, expected, "{:?}", colorimetry);
+        let converter = EasyConverter::with_colorimetry(colorimetry);
+        assert_eq!(converter.grey_from_rgb(&expected), grey, "{:?}", colorimetry);
+    }
+}
+
+#[test]
+fn jpeg_refuses_frames_past_its_size_limit() {
+    let colorimetry = Colorimetry::default();
+    let encoding = PayloadEncoding::Jpeg { quality: 80 };
+    let wide = vec![0u8; 70_000 * 3];
+    for (width, height) in [(70_000, 1), (1, 70_000)] {
+        let err = codec::encode(encoding, &wide, width, height, PixelFormat::Rgb24, colorimetry)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}x{}", width, height);
+    }
+    let edge = vec![0u8; 65_535 * 3];
+    assert!(codec::encode(encoding, &edge, 65_535, 1, PixelFormat::Rgb24, colorimetry).is_ok());
+}
+
+#[test]
+fn payloads_are_bounded_by_the_frame() {
+    let yuyv_len = PixelFormat::Yuyv.frame_len(WIDTH, HEIGHT).unwrap();
+    let max = |encoding, width, height, format| {
+        codec::max_payload_len(encoding, width, height, format)
+    };
+    assert_eq!(max(PayloadEncoding::Raw, WIDTH, HEIGHT, PixelFormat::Yuyv), yuyv_len);
+    assert_eq!(max(PayloadEncoding::Rgb, WIDTH, HEIGHT, PixelFormat::Rgb24), yuyv_len / 2 * 3);
+    for encoding in [PayloadEncoding::Zstd { level: 3 }, PayloadEncoding::Jpeg { quality: 80 }] {
+        assert_eq!(max(encoding, WIDTH, HEIGHT, PixelFormat::Yuyv), codec::MAX_PAYLOAD_LEN);
+    }
+    let mjpeg = max(PayloadEncoding::Raw, WIDTH, HEIGHT, PixelFormat::Mjpeg);
+    assert_eq!(mjpeg, codec::MAX_PAYLOAD_LEN);
+    let huge = max(PayloadEncoding::Raw, u32::MAX, u32::MAX, PixelFormat::Rgb24);
+    assert_eq!(huge, codec::MAX_PAYLOAD_LEN);
+}
@@ -0,0 +1,119 @@
+//! YUV to RGB coefficients against the `yuv` crate, and colorimetry as a V4L2
+//! driver reports it.
+
+use shared::convert::Coefficients;
+use shared::format::{Colorimetry, YuvMatrix, YuvRange};
+use yuv::YUV;
+use yuv::color::{MatrixCoefficients, Range};
+use yuv::convert::RGBConvert;
+
+const MATRICES: [(YuvMatrix, MatrixCoefficients); 3] = [
+    (YuvMatrix::Bt601, MatrixCoefficients::BT601),
+    (YuvMatrix::Bt709, MatrixCoefficients::BT709),
+    (YuvMatrix::Bt2020, MatrixCoefficients::BT2020NCL),
+];
+const RANGES: [(YuvRange, Range); 2] = [
+    (YuvRange::Limited, Range::Limited),
+    (YuvRange::Full, Range::Full),
+];
+
+#[test]
+fn coefficients_match_the_yuv_crate() {
+    for (matrix, reference_matrix) in MATRICES {
+        for (range, reference_range) in RANGES {
+            let coefficients = Coefficients::new(Colorimetry::new(matrix, range));
+            let reference = RGBConvert::<u8>::new(reference_range, reference_matrix).unwrap();
+            for y in (0..=255).step_by(3) {
+                for u in (0..=255).step_by(5) {
+                    for v in (0..=255).step_by(5) {
+                        let expected = reference.to_rgb(YUV { y, u, v });
+                        let actual = coefficients.pixel(y, u, v);
+                        let diff = [expected.r, expected.g, expected.b]
+                            .iter()
+                            .zip(actual)
+                            .map(|(e, a)| e.abs_diff(a))
+                            .max()
+                            .unwrap();
+                        assert!(
+                            diff <= 1,
+                            "{:?} {:?} yuv ({}, {}, {}): {:?}, expected {:?}",
+                            matrix, range, y, u, v, actual, expected
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn reference_colors() {
+    // Black, white and the primaries in BT.601 limited range, as UVC webcams send them.
+    let k = Coefficients::default();
+    assert_eq!(k.pixel(16, 128, 128), [0, 0, 0]);
+    assert_eq!(k.pixel(235, 128, 128), [255, 255, 255]);
+    assert_eq!(k.pixel(126, 128, 128), [128, 128, 128]);
+    let close = |actual: [u8; 3], expected: [u8; 3]| {
+        actual.iter().zip(expected).all(|(a, e)| a.abs_diff(e) <= 1)
+    };
+    assert!(close(k.pixel(81, 90, 240), [255, 0, 0]), "{:?}", k.pixel(81, 90, 240));
+    assert!(close(k.pixel(145, 54, 34), [0, 255, 0]), "{:?}", k.pixel(145, 54, 34));
+    assert!(close(k.pixel(41, 240, 110), [0, 0, 255]), "{:?}", k.pixel(41, 240, 110));
+
+    // Full range passes luma through.
+    let full = Coefficients::new(Colorimetry::new(YuvMatrix::Bt709, YuvRange::Full));
+    for y in [0, 1, 100, 254, 255] {
+        assert_eq!(full.pixel(y, 128, 128), [y; 3]);
+    }
+}
+
+// `enum v4l2_colorspace`, `enum v4l2_ycbcr_encoding` and `enum v4l2_quantization`.
+const COLORSPACE_DEFAULT: u32 = 0;
+const COLORSPACE_SMPTE170M: u32 = 1;
+const COLORSPACE_REC709: u32 = 3;
+const COLORSPACE_JPEG: u32 = 7;
+const COLORSPACE_SRGB: u32 = 8;
+const COLORSPACE_BT2020: u32 = 10;
+const ENC_DEFAULT: u32 = 0;
+const ENC_601: u32 = 1;
+const ENC_709: u32 = 2;
+const ENC_BT2020: u32 = 6;
+const QUANT_DEFAULT: u32 = 0;
+const QUANT_FULL: u32 = 1;
+const QUANT_LIMITED: u32 = 2;
+
+#[test]
+fn from_v4l2_fills_in_defaults() {
+    let limited = |matrix| Colorimetry::new(matrix, YuvRange::Limited);
+    let cases = [
+        (COLORSPACE_DEFAULT, limited(YuvMatrix::Bt601)),
+        (COLORSPACE_SMPTE170M, limited(YuvMatrix::Bt601)),
+        (COLORSPACE_SRGB, limited(YuvMatrix::Bt601)),
+        (COLORSPACE_REC709, limited(YuvMatrix::Bt709)),
+        (COLORSPACE_BT2020, limited(YuvMatrix::Bt2020)),
+        (COLORSPACE_JPEG, Colorimetry::new(YuvMatrix::Bt601, YuvRange::Full)),
+    ];
+    for (colorspace, expected) in cases {
+        let actual = Colorimetry::from_v4l2(colorspace, ENC_DEFAULT, QUANT_DEFAULT);
+        assert_eq!(actual, expected, "colorspace {}", colorspace);
+    }
+}
+
+#[test]
+fn from_v4l2_honors_explicit_encoding_and_quantization() {
+    let cases = [
+        (COLORSPACE_SRGB, ENC_709, QUANT_DEFAULT, YuvMatrix::Bt709, YuvRange::Limited),
+        (COLORSPACE_REC709, ENC_601, QUANT_DEFAULT, YuvMatrix::Bt601, YuvRange::Limited),
+        (COLORSPACE_DEFAULT, ENC_BT2020, QUANT_FULL, YuvMatrix::Bt2020, YuvRange::Full),
+        (COLORSPACE_REC709, ENC_DEFAULT, QUANT_FULL, YuvMatrix::Bt709, YuvRange::Full),
+        (COLORSPACE_JPEG, ENC_DEFAULT, QUANT_LIMITED, YuvMatrix::Bt601, YuvRange::Limited),
+        (COLORSPACE_BT2020, ENC_601, QUANT_LIMITED, YuvMatrix::Bt601, YuvRange::Limited),
+    ];
+    for (colorspace, enc, quantization, matrix, range) in cases {
+        assert_eq!(
+            Colorimetry::from_v4l2(colorspace, enc, quantization),
+            Colorimetry::new(matrix, range),
+            "colorspace {}, encoding {}, quantization {}", colorspace, enc, quantization
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Every SIMD kernel and the parallel conversion against the scalar code.
+
+use shared::convert::{self, Coefficients, Simd};
+use shared::format::{Colorimetry, YuvMatrix, YuvRange};
+
+const SIMDS: [Simd; 3] = [Simd::Ssse3, Simd::Avx2, Simd::Neon];
+
+fn colorimetries() -> impl Iterator<Item = Colorimetry> {
+    [YuvMatrix::Bt601, YuvMatrix::Bt709, YuvMatrix::Bt2020].into_iter().flat_map(|matrix| {
+        [YuvRange::Limited, YuvRange::Full].map(|range| Colorimetry::new(matrix, range))
+    })
+}
+
+// Deterministic noise, which reaches the clamping at both ends.
+fn noise(len: usize) -> Vec<u8> {
+    let mut state = 0x2545_f491u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn convert(simd: Simd, k: &Coefficients, yuyv: &[u8]) -> Vec<u8> {
+    let mut rgb = vec![0u8; convert::rgb_len(yuyv.len())];
+    convert::yuyv_to_rgb_with(simd, k, yuyv, &mut rgb);
+    rgb
+}
+
+#[test]
+fn simd_matches_scalar() {
+    let supported: Vec<Simd> = SIMDS.into_iter().filter(Simd::is_supported).collect();
+    if supported.is_empty() {
+        eprintln!("no SIMD kernel supported, only the scalar code is tested");
+    }
+    // Whole and partial vector widths, and a trailing incomplete pixel pair.
+    let yuyv = noise(1280 * 2 * 3 + 2);
+    let lengths = (0..=4 * 70).chain([yuyv.len() - 2, yuyv.len()]);
+    for colorimetry in colorimetries() {
+        let k = Coefficients::new(colorimetry);
+        for len in lengths.clone() {
+            let expected = convert(Simd::Scalar, &k, &yuyv[..len]);
+            for &simd in &supported {
+                assert!(
+                    convert(simd, &k, &yuyv[..len]) == expected,
+                    "{:?} differs from scalar for {:?}, {} bytes", simd, colorimetry, len
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn scalar_converts_pixel_by_pixel() {
+    let yuyv = noise(4 * 37);
+    for colorimetry in colorimetries() {
+        let k = Coefficients::new(colorimetry);
+        let expected: Vec<u8> = yuyv
+            .chunks_exact(4)
+            .flat_map(|p| [k.pixel(p[0], p[1], p[3]), k.pixel(p[2], p[1], p[3])])
+            .flatten()
+            .collect();
+        assert_eq!(convert(Simd::Scalar, &k, &yuyv), expected, "{:?}", colorimetry);
+    }
+}
+
+#[test]
+fn unsupported_simd_falls_back_to_scalar() {
+    let k = Coefficients::default();
+    let yuyv = noise(4 * 50);
+    let expected = convert(Simd::Scalar, &k, &yuyv);
+    for simd in SIMDS.into_iter().filter(|simd| !simd.is_supported()) {
+        assert_eq!(convert(simd, &k, &yuyv), expected, "{:?}", simd);
+    }
+}
+
+#[test]
+fn parallel_matches_serial() {
+    // Pixel pair counts that split evenly and unevenly, some fewer than threads.
+    for pairs in [0, 1, 3, 17, 640 * 9 + 5, 1280 * 720 / 2] {
+        let yuyv = noise(pairs * 4);
+        let full = Colorimetry::new(YuvMatrix::Bt709, YuvRange::Full);
+        for colorimetry in [Colorimetry::default(), full] {
+            let k = Coefficients::new(colorimetry);
+            let expected = convert(Simd::Scalar, &k, &yuyv);
+            for threads in [0, 1, 2, 3, 4, 7, 16] {
+                let mut rgb = vec![0u8; expected.len()];
+                convert::yuyv_to_rgb_parallel(&k, &yuyv, &mut rgb, threads);
+                let case = format!("{} pairs on {} threads, {:?}", pairs, threads, colorimetry);
+                assert!(rgb == expected, "{}", case);
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Round trips through every image format, and the BMP and PNM variants only
+//! other programs write.
+
+use shared::format::{Colorimetry, PixelFormat};
+use shared::frame::Frame;
+use shared::image::{self, ImageError, ImageFormat};
+use shared::utils::EasyConverter;
+
+fn frame(width: u32, height: u32) -> Frame {
+    let rgb = (0..width * height)
+        .flat_map(|i| [(i * 7) as u8, (i * 13 + 5) as u8, (i * 29 + 100) as u8])
+        .collect();
+    Frame::new(rgb, width, height, PixelFormat::Rgb24).unwrap()
+}
+
+fn assert_malformed(result: Result<Frame, ImageError>) {
+    match result {
+        Err(ImageError::Malformed(_)) => {}
+        other => panic!("expected a malformed image, got {:?}", other.map(|f| f.size())),
+    }
+}
+
+#[test]
+fn round_trips() {
+    // Odd widths need row padding in a BMP, 4 wide does not.
+    let sizes = [(1, 1), (3, 2), (4, 3), (5, 4), (33, 17)];
+    for format in [ImageFormat::Bmp, ImageFormat::Png, ImageFormat::Ppm] {
+        for (width, height) in sizes {
+            let frame = frame(width, height);
+            let bytes = image::encode(&frame, format).unwrap();
+            assert_eq!(ImageFormat::detect(&bytes), Some(format));
+            let decoded = image::decode(&bytes).unwrap();
+            assert_eq!(decoded.size(), [width, height], "{:?}", format);
+            assert_eq!(decoded.format(), PixelFormat::Rgb24);
+            assert_eq!(decoded.data(), frame.data(), "{:?} {}x{}", format, width, height);
+        }
+    }
+}
+
+#[test]
+fn bmp_rows_are_padded_and_bottom_up() {
+    let frame = frame(5, 2);
+    let bytes = image::encode(&frame, ImageFormat::Bmp).unwrap();
+    // 15 bytes of pixels padded to 16 per row.
+    assert_eq!(bytes.len(), 54 + 2 * 16);
+    let first_row = &frame.data()[..3];
+    let last_stored = &bytes[54 + 16..54 + 16 + 3];
+    assert_eq!(last_stored, &[first_row[2], first_row[1], first_row[0]]);
+}
+
+#[test]
+fn yuv_frames_are_written_as_rgb() {
+    let rgb = frame(6, 4);
+    let yuyv = EasyConverter::default().encode(&rgb, PixelFormat::Yuyv).unwrap();
+    let decoded = image::decode(&image::encode(&yuyv, ImageFormat::Png).unwrap()).unwrap();
+    assert_eq!(decoded.data(), yuyv.to_rgb().unwrap().data());
+    assert_eq!(yuyv.colorimetry(), Colorimetry::default());
+}
+
+// A BMP as other programs write it: `height` negative for top-down rows,
+// `palette` for 8 bits per pixel, `rows` as stored, padding included.
+fn bmp(width: i32, height: i32, bits: u16, palette: &[[u8; 4]], rows: &[u8]) -> Vec<u8> {
+    let offset = 54 + palette.len() as u32 * 4;
+    let mut bmp = b"BM".to_vec();
+    bmp.extend_from_slice(&(offset + rows.len() as u32).to_le_bytes());
+    bmp.extend_from_slice(&[0; 4]);
+    bmp.extend_from_slice(&offset.to_le_bytes());
+    bmp.extend_from_slice(&40u32.to_le_bytes());
+    bmp.extend_from_slice(&width.to_le_bytes());
+    bmp.extend_from_slice(&height.to_le_bytes());
+    bmp.extend_from_slice(&1u16.to_le_bytes());
+    bmp.extend_from_slice(&bits.to_le_bytes());
+    bmp.extend_from_slice(&[0; 16]);
+    bmp.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+    bmp.extend_from_slice(&[0; 4]);
+    for color in palette {
+        bmp.extend_from_slice(color);
+    }
+    bmp.extend_from_slice(rows);
+    bmp
+}
+
+#[test]
+fn bmp_top_down_rows() {
+    // 3x2 in BGR, rows padded from 9 to 12 bytes.
+    let rows = [
+        1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 0, 0,
+        10, 11, 12, 13, 14, 15, 16, 17, 18, 0, 0, 0,
+    ];
+    let top_down = image::decode(&bmp(3, -2, 24, &[], &rows)).unwrap();
+    assert_eq!(top_down.size(), [3, 2]);
+    assert_eq!(
+        top_down.data(),
+        [3, 2, 1, 6, 5, 4, 9, 8, 7, 12, 11, 10, 15, 14, 13, 18, 17, 16]
+    );
+    let bottom_up = image::decode(&bmp(3, 2, 24, &[], &rows)).unwrap();
+    assert_eq!(&bottom_up.data()[..9], &top_down.data()[9..]);
+}
+
+#[test]
+fn bmp_palette_and_32_bits() {
+    let palette = [[255, 0, 0, 0], [0, 255, 0, 0], [0, 0, 255, 0]];
+    // 3x1 8-bit, padded to 4 bytes.
+    let paletted = image::decode(&bmp(3, 1, 8, &palette, &[2, 1, 0, 0])).unwrap();
+    assert_eq!(paletted.data(), [255, 0, 0, 0, 255, 0, 0, 0, 255]);
+    assert_malformed(image::decode(&bmp(3, 1, 8, &palette, &[2, 1, 3, 0])));
+
+    let bgra = image::decode(&bmp(2, 1, 32, &[], &[1, 2, 3, 255, 4, 5, 6, 255])).unwrap();
+    assert_eq!(bgra.data(), [3, 2, 1, 6, 5, 4]);
+}
+
+#[test]
+fn broken_bmps() {
+    assert_malformed(image::decode(b"BM\0\0"));
+    assert_malformed(image::decode(&bmp(3, 2, 24, &[], &[0; 12])));
+    assert_malformed(image::decode(&bmp(0, 2, 24, &[], &[])));
+    assert_malformed(image::decode(&bmp(i32::MAX, i32::MIN + 1, 32, &[], &[0; 8])));
+    assert!(matches!(
+        image::decode(&bmp(2, 1, 16, &[], &[0; 4])),
+        Err(ImageError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn png_variants() {
+    let encode = |color: png::ColorType, pixels: &[u8]| {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(pixels).unwrap();
+        writer.finish().unwrap();
+        bytes
+    };
+    let grey = image::decode(&encode(png::ColorType::Grayscale, &[10, 200])).unwrap();
+    assert_eq!(grey.data(), [10, 10, 10, 200, 200, 200]);
+    let rgba = encode(png::ColorType::Rgba, &[1, 2, 3, 0, 4, 5, 6, 255]);
+    let rgba = image::decode(&rgba).unwrap();
+    assert_eq!(rgba.data(), [1, 2, 3, 4, 5, 6]);
+    let grey_alpha = encode(png::ColorType::GrayscaleAlpha, &[7, 0, 9, 1]);
+    let grey_alpha = image::decode(&grey_alpha).unwrap();
+    assert_eq!(grey_alpha.data(), [7, 7, 7, 9, 9, 9]);
+    assert_malformed(image::decode(b"\x89PNG\r\n\x1a\n\0\0"));
+}
+
+#[test]
+fn pgm_with_comments_and_a_small_maximum() {
+    let pgm = b"P5\n# made by hand\n2 1 # size\n15\n\x00\x0f";
+    let grey = image::decode(pgm).unwrap();
+    assert_eq!(grey.size(), [2, 1]);
+    assert_eq!(grey.data(), [0, 0, 0, 255, 255, 255]);
+}
+
+#[test]
+fn broken_pnms() {
+    assert_malformed(image::decode(b"P6 4294967295 4294967295 255\n"));
+    assert_malformed(image::decode(b"P5 4294967295 4294967295 255\n"));
+    assert_malformed(image::decode(b"P6 2 2 255\n\0\0\0"));
+    assert_malformed(image::decode(b"P6 2 x 255\n"));
+    assert_malformed(image::decode(b"P6"));
+    let deep = image::decode(b"P6 1 1 65535\n\0\0\0\0\0\0");
+    assert!(matches!(deep, Err(ImageError::Unsupported(_))));
+}
+
+#[test]
+fn formats_by_name_and_content() {
+    assert_eq!(ImageFormat::from_path("a/b.PNG"), Some(ImageFormat::Png));
+    assert_eq!(ImageFormat::from_path("x.pgm"), Some(ImageFormat::Ppm));
+    assert_eq!(ImageFormat::from_path("x.jpeg"), Some(ImageFormat::Jpeg));
+    assert_eq!(ImageFormat::from_path("x.gif"), None);
+    assert_eq!(ImageFormat::from_path("bmp"), None);
+    assert!(matches!(image::decode(b"GIF89a"), Err(ImageError::Unsupported(_))));
+    assert!(image::encode(&frame(2, 2), ImageFormat::Jpeg).is_err());
+}
+
+#[test]
+fn reads_the_sample_jpeg() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../resource/pose.jpg");
+    let pose = image::read(path).unwrap();
+    assert_eq!(pose.format(), PixelFormat::Rgb24);
+    let [width, height] = pose.size();
+    assert!(width > 0 && height > 0);
+    assert_eq!(pose.data().len(), (width * height * 3) as usize);
+}
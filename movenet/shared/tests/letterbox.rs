@@ -0,0 +1,63 @@
+//! Properties of the letterbox geometry over arbitrary frame and model sizes.
+
+use proptest::prelude::*;
+
+use shared::letterbox::Letterbox;
+
+// Frames not so thin that their fitted image would be empty.
+fn sizes() -> impl Strategy<Value = ([u32; 2], [u32; 2])> {
+    ([1u32..=4096, 1u32..=4096], [1u32..=512, 1u32..=512]).prop_filter(
+        "fitted image is empty",
+        |&(source, target)| Letterbox::new(source, target).fitted().iter().all(|&v| v > 0)
+    )
+}
+
+fn assert_close(a: [f32; 2], b: [f32; 2], size: [u32; 2]) -> Result<(), TestCaseError> {
+    for i in 0..2 {
+        let tolerance = size[i] as f32 * 1e-5;
+        prop_assert!((a[i] - b[i]).abs() <= tolerance, "{:?} != {:?}", a, b);
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn fits_inside_the_target((source, target) in sizes()) {
+        let letterbox = Letterbox::new(source, target);
+        let fitted = letterbox.fitted();
+        let [top, bottom, left, right] = letterbox.padding();
+        prop_assert!(fitted[0] > 0 && fitted[1] > 0, "{:?}", fitted);
+        prop_assert!(fitted[0] == target[0] || fitted[1] == target[1], "{:?}", fitted);
+        prop_assert_eq!(left + fitted[0] + right, target[0]);
+        prop_assert_eq!(top + fitted[1] + bottom, target[1]);
+        prop_assert!(right - left <= 1 && bottom - top <= 1);
+    }
+
+    #[test]
+    fn inverse_undoes_forward(
+        (source, target) in sizes(), x in 0.0f32..=1.0, y in 0.0f32..=1.0
+    ) {
+        let letterbox = Letterbox::new(source, target);
+        let point = [x * source[0] as f32, y * source[1] as f32];
+        assert_close(letterbox.inverse(letterbox.forward(point)), point, source)?;
+    }
+
+    #[test]
+    fn forward_undoes_inverse(
+        (source, target) in sizes(), x in 0.0f32..=1.0, y in 0.0f32..=1.0
+    ) {
+        let letterbox = Letterbox::new(source, target);
+        let point = [x * target[0] as f32, y * target[1] as f32];
+        assert_close(letterbox.forward(letterbox.inverse(point)), point, target)?;
+    }
+
+    #[test]
+    fn corners_map_onto_the_fitted_image((source, target) in sizes()) {
+        let letterbox = Letterbox::new(source, target);
+        let [left, top] = letterbox.offset();
+        let [width, height] = letterbox.fitted();
+        assert_close(letterbox.forward([0.0, 0.0]), [left as f32, top as f32], target)?;
+        let far = [(left + width) as f32, (top + height) as f32];
+        assert_close(letterbox.forward([source[0] as f32, source[1] as f32]), far, target)?;
+    }
+}
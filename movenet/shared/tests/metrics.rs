@@ -0,0 +1,65 @@
+//! Running totals of codecs and of frames dropped by the capture driver.
+
+use std::time::Duration;
+
+use shared::metrics::{CodecStats, DropStats};
+
+fn drops(sequences: &[u64]) -> DropStats {
+    let mut stats = DropStats::new();
+    for &sequence in sequences {
+        stats.record(sequence);
+    }
+    stats
+}
+
+#[test]
+fn gaps_count_as_drops() {
+    let stats = drops(&[0, 1, 2, 3]);
+    assert_eq!((stats.frames, stats.dropped), (4, 0));
+    let stats = drops(&[5, 7, 8, 12]);
+    assert_eq!((stats.frames, stats.dropped), (4, 4));
+    assert_eq!(stats.drop_rate(), 0.5);
+    // Whatever came before the first frame is unknown.
+    assert_eq!(drops(&[1000]).dropped, 0);
+}
+
+#[test]
+fn restarts_drop_nothing() {
+    // Streaming restarted from zero, then repeated a number.
+    let stats = drops(&[40, 41, 43, 0, 1, 1, 3]);
+    assert_eq!((stats.frames, stats.dropped), (7, 1 + 1));
+    let stats = drops(&[3_000_000_000, 0, 1]);
+    assert_eq!(stats.dropped, 0);
+}
+
+#[test]
+fn sequences_wrap_at_32_bits() {
+    let max = u32::MAX as u64;
+    assert_eq!(drops(&[max - 1, max, 0, 1]).dropped, 0);
+    assert_eq!(drops(&[max - 2, 1]).dropped, 3);
+    assert_eq!(drops(&[max, max]).dropped, 0);
+    // Sequences past 32 bits, as a playback counts them, never wrap.
+    assert_eq!(drops(&[max + 5, max + 7, 2]).dropped, 1);
+}
+
+#[test]
+fn rates_without_frames() {
+    let stats = DropStats::new();
+    assert_eq!(stats.drop_rate(), 0.0);
+    assert_eq!(stats.to_string(), "0 frames, 0 dropped (0.0%)");
+    assert_eq!(drops(&[1, 5]).to_string(), "2 frames, 3 dropped (60.0%)");
+
+    let codec = CodecStats::new();
+    assert_eq!((codec.ratio(), codec.mean_payload_bytes()), (1.0, 0));
+    assert_eq!(codec.mean_codec_time(), Duration::ZERO);
+}
+
+#[test]
+fn codec_means() {
+    let mut codec = CodecStats::new();
+    codec.record(1000, 250, Duration::from_millis(2));
+    codec.record(1000, 150, Duration::from_millis(4));
+    assert_eq!(codec.ratio(), 0.2);
+    assert_eq!(codec.mean_payload_bytes(), 200);
+    assert_eq!(codec.mean_codec_time(), Duration::from_millis(3));
+}
@@ -0,0 +1,62 @@
+//! Rotation and mirroring of frames, and of keypoints back onto the captured frame.
+
+use shared::format::{Orientation, Rotation};
+use shared::letterbox::Letterbox;
+
+fn orientations() -> impl Iterator<Item = Orientation> {
+    [Rotation::None, Rotation::Cw90, Rotation::Cw180, Rotation::Cw270]
+        .into_iter()
+        .flat_map(|rotation| [false, true].map(|mirror| Orientation::new(rotation, mirror)))
+}
+
+#[test]
+fn source_pixel_covers_the_frame_once() {
+    for orientation in orientations() {
+        for size in [[1, 1], [4, 3], [3, 5]] {
+            let [width, height] = orientation.oriented_size(size);
+            let mut seen = vec![false; (size[0] * size[1]) as usize];
+            for y in 0..height as usize {
+                for x in 0..width as usize {
+                    let [sx, sy] = orientation.source_pixel(x, y, size);
+                    assert!(sx < size[0] as usize && sy < size[1] as usize);
+                    seen[sy * size[0] as usize + sx] = true;
+                }
+            }
+            assert!(seen.iter().all(|&s| s), "{:?} of {:?}", orientation, size);
+        }
+    }
+}
+
+#[test]
+fn restore_point_inverts_source_pixel() {
+    // Sizes whose letterboxes are not truncated, so pixel centers map exactly.
+    let cases = [([640, 480], 192), ([1280, 720], 192), ([4, 2], 192), ([3, 5], 15), ([6, 6], 6)];
+    for orientation in orientations() {
+        for (size, side) in cases {
+            let oriented = orientation.oriented_size(size);
+            let model = Letterbox::new(oriented, [side, side]);
+            let captured = Letterbox::new(size, [side, side]);
+            for y in 0..oriented[1] as usize {
+                for x in 0..oriented[0] as usize {
+                    let [mx, my] = model.forward([x as f32 + 0.5, y as f32 + 0.5]);
+                    let [rx, ry] = orientation.restore_point(mx / side as f32, my / side as f32);
+                    let restored = captured.inverse([rx * side as f32, ry * side as f32]);
+                    let [sx, sy] = orientation.source_pixel(x, y, size);
+                    let expected = [sx as f32 + 0.5, sy as f32 + 0.5];
+                    assert!(
+                        (0..2).all(|i| (restored[i] - expected[i]).abs() < 1e-3),
+                        "{:?} of {:?}: ({}, {}) restored to {:?}, expected {:?}",
+                        orientation, size, x, y, restored, expected
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn quarter_turns_round_trip() {
+    for turns in 0..8 {
+        assert_eq!(Rotation::from_quarter_turns(turns).quarter_turns(), turns % 4);
+    }
+}
@@ -0,0 +1,183 @@
+//! Playing back directories, recordings and still images as a virtual camera.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use shared::format::{Colorimetry, PixelFormat};
+use shared::frame::Frame;
+use shared::image::{self, ImageFormat};
+use shared::playback::{Playback, PlaybackSource};
+use shared::utils::{self, EasyConverter};
+
+const POSE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../resource/pose.jpg");
+
+// A fresh directory for one test, removed again when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("playback-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        Scratch(dir)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+// An RGB frame told apart from others of the same size by `seed`.
+fn rgb(width: u32, height: u32, seed: u32) -> Frame {
+    let data = (0..width * height)
+        .flat_map(|i| [(i * 16 + seed * 40) as u8, (seed * 80) as u8, (i * 8) as u8])
+        .collect();
+    Frame::new(data, width, height, PixelFormat::Rgb24).unwrap()
+}
+
+fn write_png(path: &Path, frame: &Frame) {
+    fs::write(path, image::encode(frame, ImageFormat::Png).unwrap()).unwrap();
+}
+
+fn drain(playback: &mut Playback, count: usize) -> Vec<Frame> {
+    (0..count).map(|_| playback.next_frame().unwrap().expect("playback ended early")).collect()
+}
+
+#[test]
+fn sources_by_path() {
+    let scratch = Scratch::new("sources");
+    assert_eq!(
+        PlaybackSource::from_path(&scratch.0).unwrap(),
+        PlaybackSource::Directory(scratch.0.clone())
+    );
+    for still in ["pose.jpg", "a/b.PNG", "c.pgm"] {
+        assert_eq!(
+            PlaybackSource::from_path(still).unwrap(),
+            PlaybackSource::Still(still.into())
+        );
+    }
+    for (name, size) in [("walk_640x480.yuyv", [640, 480]), ("dir/a_b_2x3.YUYV", [2, 3])] {
+        assert_eq!(
+            PlaybackSource::from_path(name).unwrap(),
+            PlaybackSource::Recording { path: name.into(), size }
+        );
+    }
+    for rejected in ["walk.yuyv", "walk_640x.yuyv", "walk_axb.yuyv", "walk_640x480.raw", "walk"] {
+        let err = PlaybackSource::from_path(rejected).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{}", rejected);
+    }
+}
+
+#[test]
+fn directories_play_in_name_order() {
+    let scratch = Scratch::new("directory");
+    let frames: Vec<Frame> = (0..3).map(|seed| rgb(4, 2, seed)).collect();
+    // Written out of order, next to a file that is no image.
+    for (name, frame) in [("b.png", &frames[1]), ("c.png", &frames[2]), ("a.png", &frames[0])] {
+        write_png(&scratch.path(name), frame);
+    }
+    fs::write(scratch.path("notes.txt"), "not a frame").unwrap();
+
+    let converter = EasyConverter::default();
+    let expected: Vec<Frame> = frames
+        .iter()
+        .map(|frame| converter.encode(frame, PixelFormat::Yuyv).unwrap())
+        .collect();
+    let source = PlaybackSource::from_path(&scratch.0).unwrap();
+    let mut playback = Playback::open(source.clone(), Colorimetry::default()).unwrap().with_fps(0);
+    assert_eq!(playback.size(), [4, 2]);
+    for (frame, expected) in drain(&mut playback, 3).iter().zip(&expected) {
+        assert_eq!(frame.format(), PixelFormat::Yuyv);
+        assert_eq!(frame.data(), expected.data());
+    }
+    assert!(playback.next_frame().unwrap().is_none());
+
+    let mut looping =
+        Playback::open(source, Colorimetry::default()).unwrap().with_fps(0).with_looping(true);
+    let played = drain(&mut looping, 7);
+    for (i, frame) in played.iter().enumerate() {
+        assert_eq!(frame.data(), expected[i % 3].data(), "frame {}", i);
+    }
+}
+
+#[test]
+fn directories_reject_mismatched_and_missing_images() {
+    let scratch = Scratch::new("mismatched");
+    write_png(&scratch.path("a.png"), &rgb(4, 2, 0));
+    write_png(&scratch.path("b.png"), &rgb(6, 2, 1));
+    let source = PlaybackSource::Directory(scratch.0.clone());
+    let mut playback = Playback::open(source, Colorimetry::default()).unwrap().with_fps(0);
+    assert!(playback.next_frame().unwrap().is_some());
+    let err = playback.next_frame().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    let empty = Scratch::new("empty");
+    let source = PlaybackSource::Directory(empty.0.clone());
+    let err = Playback::open(source, Colorimetry::default()).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn truncated_recordings_end_or_loop() {
+    let scratch = Scratch::new("recording");
+    let path = scratch.path("clip_4x2.yuyv");
+    let frame_len = 4 * 2 * 2;
+    let frames: Vec<Vec<u8>> = (0..2u8).map(|i| vec![i * 50 + 10; frame_len]).collect();
+    // Two whole frames and half of a third.
+    let mut recording = frames.concat();
+    recording.extend_from_slice(&[200; 8]);
+    fs::write(&path, recording).unwrap();
+
+    let source = PlaybackSource::from_path(&path).unwrap();
+    let mut playback = Playback::open(source.clone(), Colorimetry::default()).unwrap().with_fps(0);
+    for (frame, expected) in drain(&mut playback, 2).iter().zip(&frames) {
+        assert_eq!(frame.size(), [4, 2]);
+        assert_eq!(frame.data(), expected);
+    }
+    assert!(playback.next_frame().unwrap().is_none());
+
+    let mut looping =
+        Playback::open(source, Colorimetry::default()).unwrap().with_fps(0).with_looping(true);
+    for (i, frame) in drain(&mut looping, 5).iter().enumerate() {
+        assert_eq!(frame.data(), frames[i % 2], "frame {}", i);
+        assert_eq!(frame.sequence(), i as u64 + 1);
+    }
+}
+
+#[test]
+fn recordings_need_an_even_width() {
+    for size in [[3, 2], [0, 2], [4, 0]] {
+        let source = PlaybackSource::Recording { path: "missing.yuyv".into(), size };
+        let err = Playback::open(source, Colorimetry::default()).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{:?}", size);
+    }
+}
+
+#[test]
+fn stills_repeat_paced_to_the_frame_rate() {
+    let expected = utils::load_yuyv(POSE, Colorimetry::default()).unwrap();
+    let source = PlaybackSource::from_path(POSE).unwrap();
+    let fps = 50;
+    let mut playback = Playback::open(source, Colorimetry::default()).unwrap().with_fps(fps);
+    assert_eq!(playback.size(), expected.size());
+
+    let start = Instant::now();
+    let frames = drain(&mut playback, 6);
+    // The first frame is due right away, the other five one interval apart.
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_secs(1) / fps * 5, "{:?}", elapsed);
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.sequence(), i as u64 + 1);
+        assert_eq!(frame.data(), expected.data());
+    }
+    assert!(frames.windows(2).all(|pair| pair[0].timestamp() <= pair[1].timestamp()));
+}
@@ -0,0 +1,155 @@
+//! Letterboxing into the model input, from RGB and fused from YUYV.
+
+use shared::bench::synthetic_yuyv;
+use shared::format::{Colorimetry, Orientation, Rotation, YuvMatrix, YuvRange};
+use shared::preprocess::{self, TensorElement};
+use shared::utils::EasyConverter;
+
+// Every row of a `width` x `colors.len()` frame in its own color.
+fn striped(width: u32, colors: &[[u8; 3]]) -> Vec<u8> {
+    colors.iter().flat_map(|color| color.repeat(width as usize)).collect()
+}
+
+fn row(rgb: &[u8], width: u32, y: u32) -> &[u8] {
+    &rgb[(y * width * 3) as usize..][..(width * 3) as usize]
+}
+
+#[test]
+fn edge_rows_come_from_the_edge_of_the_frame() {
+    let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+    let rgb = striped(4, &colors);
+    let out = preprocess::resize_with_padding(&rgb, 4, 3, [8, 6], Orientation::default());
+    assert_eq!(row(&out, 8, 0), striped(8, &colors[..1]));
+    assert_eq!(row(&out, 8, 5), striped(8, &colors[2..]));
+}
+
+#[test]
+fn a_single_row_fills_the_fitted_image() {
+    let rgb = [92, 0, 28, 113, 16, 49];
+    let out = preprocess::resize_with_padding(&rgb, 2, 1, [7, 5], Orientation::default());
+    // Fitted to 7x3, one row of padding above and below.
+    assert!(row(&out, 7, 0).iter().all(|&v| v == 0));
+    assert!(row(&out, 7, 4).iter().all(|&v| v == 0));
+    let first = row(&out, 7, 1);
+    assert_eq!(&first[..3], &rgb[..3]);
+    assert_eq!(&first[18..], &rgb[3..]);
+    assert_eq!(row(&out, 7, 2), first);
+    assert_eq!(row(&out, 7, 3), first);
+}
+
+// The filter runs on YUV samples in the fused path, see `yuyv_to_tensor`.
+// Camera frames are downscaled and stay within a level or two, small frames
+// of fine texture drift further.
+const DOWNSCALES: [([u32; 2], [u32; 2]); 3] = [
+    ([1280, 720], [192, 192]),
+    ([640, 480], [256, 256]),
+    ([642, 363], [191, 127]),
+];
+const SMALL: [([u32; 2], [u32; 2]); 3] = [
+    ([98, 77], [192, 192]),
+    ([2, 1], [7, 5]),
+    ([30, 41], [33, 17]),
+];
+
+fn orientations() -> impl Iterator<Item = Orientation> {
+    [Rotation::None, Rotation::Cw90, Rotation::Cw180, Rotation::Cw270]
+        .into_iter()
+        .flat_map(|rotation| [false, true].map(|mirror| Orientation::new(rotation, mirror)))
+}
+
+// The fused tensor as the `u8` levels it was made of.
+fn fused<T: TensorElement + Default + Clone>(
+    yuyv: &[u8], size: [u32; 2], colorimetry: Colorimetry, shape: [u32; 2],
+    orientation: Orientation, to_u8: fn(T) -> u8
+) -> Vec<u8> {
+    let mut tensor = vec![T::default(); (shape[0] * shape[1] * 3) as usize];
+    preprocess::yuyv_to_tensor(
+        yuyv, size[0], size[1], colorimetry, shape, orientation, &mut tensor
+    );
+    tensor.into_iter().map(to_u8).collect()
+}
+
+fn assert_fused_close(cases: &[([u32; 2], [u32; 2])], max_diff: u8, max_mean: f64) {
+    let full = Colorimetry::new(YuvMatrix::Bt709, YuvRange::Full);
+    for &(size, shape) in cases {
+        let yuyv = synthetic_yuyv(size[0], size[1]);
+        for colorimetry in [Colorimetry::default(), full] {
+            let rgb = EasyConverter::with_colorimetry(colorimetry).rgb(&yuyv);
+            for orientation in orientations() {
+                let reference =
+                    preprocess::resize_with_padding(&rgb, size[0], size[1], shape, orientation);
+                let tensors = [
+                    ("u8", fused::<u8>(&yuyv, size, colorimetry, shape, orientation, |v| v)),
+                    ("i8", fused::<i8>(&yuyv, size, colorimetry, shape, orientation, |v| {
+                        v as u8 ^ 0x80
+                    })),
+                    ("f32", fused::<f32>(&yuyv, size, colorimetry, shape, orientation, |v| {
+                        assert_eq!(v.fract(), 0.0);
+                        v as u8
+                    })),
+                ];
+                for (element, tensor) in tensors {
+                    let case = format!(
+                        "{:?} -> {:?} as {}, {:?}, {:?}",
+                        size, shape, element, colorimetry, orientation
+                    );
+                    let diffs: Vec<u8> =
+                        reference.iter().zip(&tensor).map(|(a, b)| a.abs_diff(*b)).collect();
+                    let max = diffs.iter().copied().max().unwrap();
+                    let mean = diffs.iter().map(|&d| d as f64).sum::<f64>() / diffs.len() as f64;
+                    assert!(max <= max_diff, "{}: max difference {}", case, max);
+                    assert!(mean <= max_mean, "{}: mean difference {:.3}", case, mean);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn fused_downscales_stay_within_two_levels() {
+    assert_fused_close(&DOWNSCALES, 2, 0.2);
+}
+
+#[test]
+fn fused_small_frames_stay_close() {
+    assert_fused_close(&SMALL, 8, 0.5);
+}
+
+// `rgb` turned a quarter clockwise: the transpose, each row then reversed.
+fn turn(rgb: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let pixel = |x: u32, y: u32| &rgb[((y * width + x) * 3) as usize..][..3];
+    (0..width)
+        .flat_map(|y| (0..height).rev().flat_map(move |x| pixel(y, x).to_vec()))
+        .collect()
+}
+
+fn mirror(rgb: &[u8], width: u32) -> Vec<u8> {
+    rgb.chunks_exact(width as usize * 3)
+        .flat_map(|row| row.chunks_exact(3).rev().flatten().copied())
+        .collect()
+}
+
+#[test]
+fn oriented_letterbox_equals_letterboxing_the_oriented_frame() {
+    for (size, shape) in [([64, 36], [48, 48]), ([30, 41], [33, 17]), ([7, 4], [16, 16])] {
+        let rgb = EasyConverter::default().rgb(&synthetic_yuyv(size[0], size[1]));
+        for orientation in orientations() {
+            let (mut oriented, mut oriented_size) = (rgb.clone(), size);
+            for _ in 0..orientation.rotation.quarter_turns() {
+                oriented = turn(&oriented, oriented_size[0], oriented_size[1]);
+                oriented_size = [oriented_size[1], oriented_size[0]];
+            }
+            if orientation.mirror {
+                oriented = mirror(&oriented, oriented_size[0]);
+            }
+            assert_eq!(oriented_size, orientation.oriented_size(size));
+
+            let expected = preprocess::resize_with_padding(
+                &oriented, oriented_size[0], oriented_size[1], shape, Orientation::default()
+            );
+            let actual =
+                preprocess::resize_with_padding(&rgb, size[0], size[1], shape, orientation);
+            assert!(actual == expected, "{:?} -> {:?}, {:?}", size, shape, orientation);
+        }
+    }
+}
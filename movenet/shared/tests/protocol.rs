@@ -0,0 +1,112 @@
+//! The frame header and responses on the wire.
+
+use std::io;
+
+use shared::format::{Colorimetry, Orientation, Rotation, YuvMatrix, YuvRange};
+use shared::protocol::{FrameHeader, Response};
+use shared::skeleton::{MULTI_POSE_LEN, SINGLE_POSE_LEN};
+
+// Offset of the flags byte: timestamp, width, height, FourCC and colorimetry before it.
+const FLAGS_OFFSET: usize = 16 + 4 + 4 + 4 + 2;
+
+fn header(preprocessed: bool, orientation: Orientation) -> FrameHeader {
+    FrameHeader {
+        timestamp: 1_700_000_000_123,
+        width: 640,
+        height: 480,
+        fourcc: u32::from_le_bytes(*b"YUYV"),
+        colorimetry: Colorimetry::new(YuvMatrix::Bt709, YuvRange::Full),
+        preprocessed,
+        orientation,
+        data_len: 640 * 480 * 2,
+    }
+}
+
+fn bytes(header: &FrameHeader) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    header.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn frame_header_round_trips_every_flag() {
+    for rotation in [Rotation::None, Rotation::Cw90, Rotation::Cw180, Rotation::Cw270] {
+        for mirror in [false, true] {
+            for preprocessed in [false, true] {
+                let header = header(preprocessed, Orientation::new(rotation, mirror));
+                let bytes = bytes(&header);
+                assert_eq!(bytes.len(), FLAGS_OFFSET + 1 + 8);
+                assert_eq!(FrameHeader::read_from(&mut &bytes[..]).unwrap(), header);
+            }
+        }
+    }
+}
+
+#[test]
+fn frame_header_flags_layout() {
+    let flags = |header: &FrameHeader| bytes(header)[FLAGS_OFFSET];
+    assert_eq!(flags(&header(false, Orientation::default())), 0);
+    assert_eq!(flags(&header(true, Orientation::default())), 0b0001);
+    assert_eq!(flags(&header(false, Orientation::MIRRORED)), 0b0010);
+    assert_eq!(flags(&header(false, Orientation::new(Rotation::Cw270, false))), 0b1100);
+}
+
+#[test]
+fn frame_header_rejects_unknown_flags() {
+    let mut bytes = bytes(&header(true, Orientation::MIRRORED));
+    for bit in 4..8 {
+        bytes[FLAGS_OFFSET] |= 1 << bit;
+        let err = FrameHeader::read_from(&mut &bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "bit {}", bit);
+        bytes[FLAGS_OFFSET] &= !(1 << bit);
+    }
+    assert!(FrameHeader::read_from(&mut &bytes[..]).is_ok());
+}
+
+fn response_bytes(status: u8, len: u64, body: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![status];
+    bytes.extend_from_slice(&len.to_be_bytes());
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+#[test]
+fn responses_round_trip() {
+    for response in [
+        Response::Keypoints((0..SINGLE_POSE_LEN).map(|i| i as f32 / 10.0).collect()),
+        Response::Keypoints(vec![0.25; 6 * MULTI_POSE_LEN]),
+        Response::Keypoints(Vec::new()),
+        Response::Dropped,
+        Response::BadRequest("unsupported pixel format 'H264'".to_string()),
+    ] {
+        let mut bytes = Vec::new();
+        response.write_to(&mut bytes).unwrap();
+        assert_eq!(Response::read_from(&mut &bytes[..]).unwrap(), response);
+    }
+}
+
+#[test]
+fn responses_reject_sizes_no_model_outputs() {
+    for len in [u64::MAX, u64::MAX / 4 + 1, 50, 52, 7 * MULTI_POSE_LEN as u64, 1 << 40] {
+        // The values are never read, so none need to follow.
+        let bytes = response_bytes(0, len, &[]);
+        let err = Response::read_from(&mut &bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{} values", len);
+    }
+    let bytes = response_bytes(2, u64::MAX, &[]);
+    let err = Response::read_from(&mut &bytes[..]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn long_reasons_are_cut_to_what_readers_take() {
+    // Two byte characters from an odd offset, so the limit falls inside one.
+    let reason = format!("a{}", "é".repeat(5000));
+    let mut bytes = Vec::new();
+    Response::BadRequest(reason.clone()).write_to(&mut bytes).unwrap();
+    let Response::BadRequest(read) = Response::read_from(&mut &bytes[..]).unwrap() else {
+        panic!("not a bad request");
+    };
+    assert_eq!(read.len(), 4095);
+    assert!(reason.starts_with(&read));
+}
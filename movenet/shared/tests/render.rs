@@ -0,0 +1,205 @@
+//! Snapshot tests of the headless renderer against the PNGs in `tests/golden`.
+//!
+//! After an intended change in rendering, regenerate them with
+//! `UPDATE_GOLDEN=1 cargo test -p shared --test render` and review the images.
+
+use std::env;
+use std::path::PathBuf;
+
+use shared::format::PixelFormat;
+use shared::frame::Frame;
+use shared::image;
+use shared::render::RgbCanvas;
+use shared::skeleton::{self, Canvas, Pose, SkeletonStyle};
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+
+fn assert_matches_golden(name: &str, rgb: Vec<u8>) {
+    let frame = Frame::new(rgb, WIDTH, HEIGHT, PixelFormat::Rgb24).unwrap();
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.png", name));
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        image::write(&path, &frame).unwrap();
+        return;
+    }
+
+    let golden = image::read(&path)
+        .unwrap_or_else(|e| panic!("{}: {}, run with UPDATE_GOLDEN=1 to create it", path.display(), e));
+    assert_eq!(golden.size(), frame.size(), "{} size", name);
+    let differing = golden.data()
+        .chunks_exact(3)
+        .zip(frame.data().chunks_exact(3))
+        .filter(|(a, b)| a != b)
+        .count();
+    if differing > 0 {
+        let actual = env::temp_dir().join(format!("{}.actual.png", name));
+        image::write(&actual, &frame).unwrap();
+        panic!("{}: {} pixels differ from the golden image, see {}", name, differing, actual.display());
+    }
+}
+
+// A standing person facing the camera, in model coordinates.
+fn standing_pose() -> Vec<f32> {
+    let points: [(f32, f32, f32); 17] = [
+        (0.30, 0.50, 0.90), // nose
+        (0.28, 0.52, 0.85),
+        (0.28, 0.48, 0.85),
+        (0.29, 0.55, 0.60),
+        (0.29, 0.45, 0.60),
+        (0.38, 0.58, 0.90), // shoulders
+        (0.38, 0.42, 0.90),
+        (0.48, 0.61, 0.70), // elbows
+        (0.48, 0.39, 0.50),
+        (0.56, 0.63, 0.40), // wrists
+        (0.56, 0.37, 0.10),
+        (0.58, 0.55, 0.80), // hips
+        (0.58, 0.45, 0.80),
+        (0.68, 0.56, 0.70), // knees
+        (0.68, 0.44, 0.70),
+        (0.78, 0.57, 0.60), // ankles
+        (0.78, 0.43, 0.60),
+    ];
+    points.iter().flat_map(|&(y, x, score)| [y, x, score]).collect()
+}
+
+#[test]
+fn primitives() {
+    let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+    let mut canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT);
+    canvas.fill([20, 20, 40]);
+    canvas.line([5, 5], [60, 40], [255, 255, 255], 1);
+    canvas.line([5, 40], [60, 5], [255, 0, 0], 3);
+    canvas.line([70, 10], [70, 10], [0, 255, 0], 4);
+    canvas.circle([100, 25], 0, [255, 255, 0]);
+    canvas.circle([115, 25], 3, [255, 255, 0]);
+    canvas.circle([140, 25], 8, [0, 200, 255]);
+    canvas.rectangle([10, 50], [60, 80], [0, 255, 0], 1);
+    canvas.rectangle([70, 50], [150, 80], [255, 128, 0], 3);
+    // Clipped at every edge.
+    canvas.circle([0, 119], 10, [200, 0, 200]);
+    canvas.line([150, 100], [200, 130], [255, 255, 255], 5);
+    canvas.text([-3, 100], "Hello, MoveNet 0.95 ~{}", [255, 255, 255]);
+    assert_matches_golden("primitives", rgb);
+}
+
+#[test]
+fn scaled_text() {
+    let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+    let mut canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT).with_text_scale(2);
+    canvas.text([4, 4], "ABCDEFGHIJKLM", [255, 255, 255]);
+    canvas.text([4, 24], "NOPQRSTUVWXYZ", [255, 255, 255]);
+    canvas.text([4, 44], "abcdefghijklm", [255, 220, 0]);
+    canvas.text([4, 64], "nopqrstuvwxyz", [255, 220, 0]);
+    canvas.text([4, 84], "0123456789!?#", [0, 220, 255]);
+    canvas.text([4, 104], "\u{e9}(%)[&]<@>", [0, 220, 255]);
+    assert_matches_golden("scaled_text", rgb);
+}
+
+#[test]
+fn skeleton() {
+    let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+    let mut canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT);
+    let style = SkeletonStyle { labels: true, bounding_box: true, ..Default::default() };
+    skeleton::draw(&mut canvas, &Pose::parse_all(&standing_pose()), &style);
+    assert_matches_golden("skeleton", rgb);
+}
+
+#[test]
+fn skeleton_multi_person() {
+    let mut output = Vec::new();
+    for (shift, score) in [(-0.2, 0.9), (0.0, 0.6), (0.2, 0.8), (0.1, 0.1)] {
+        let pose = standing_pose();
+        output.extend(pose.chunks_exact(3).flat_map(|k| [k[0], k[1] + shift, k[2]]));
+        output.extend([0.25, 0.35 + shift, 0.8, 0.65 + shift, score]);
+    }
+    let poses = Pose::parse_all(&output);
+    assert_eq!(poses.len(), 4);
+
+    let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+    let mut canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT);
+    let style = SkeletonStyle { bounding_box: true, shade_by_confidence: false, ..Default::default() };
+    skeleton::draw(&mut canvas, &poses, &style);
+    assert_matches_golden("skeleton_multi_person", rgb);
+}
+
+fn lit(rgb: &[u8]) -> Vec<[i32; 2]> {
+    rgb.chunks_exact(3)
+        .enumerate()
+        .filter(|(_, pixel)| pixel != &[0, 0, 0])
+        .map(|(i, _)| [i as i32 % WIDTH as i32, i as i32 / WIDTH as i32])
+        .collect()
+}
+
+#[test]
+fn far_off_lines_are_clipped() {
+    let white = [255, 255, 255];
+    let far = 1_000_000_000;
+    let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+    let mut canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT);
+    canvas.line([-far, 10], [far, 10], white, 1);
+    canvas.line([-far, -far], [far, far], white, 1);
+    canvas.line([i32::MIN, i32::MIN], [i32::MAX, i32::MAX], white, 1);
+    canvas.line([i32::MIN, 0], [i32::MIN, HEIGHT as i32], white, 1);
+    canvas.line([i32::MAX, i32::MAX], [i32::MAX, i32::MAX], white, 3);
+    let lit = lit(&rgb);
+
+    let row: Vec<[i32; 2]> = (0..WIDTH as i32).map(|x| [x, 10]).collect();
+    assert!(row.iter().all(|p| lit.contains(p)));
+    let diagonal: Vec<[i32; 2]> = (0..HEIGHT as i32).map(|i| [i, i]).collect();
+    assert!(diagonal.iter().all(|p| lit.contains(p)));
+    assert_eq!(lit.len(), row.len() + diagonal.len() - 1);
+}
+
+#[test]
+fn clipped_lines_follow_unclipped_ones() {
+    // Ends just off the canvas. Clipping rounds where the line enters the
+    // canvas, which may shift its pixels by one.
+    let cases = [([-5, 3], [40, 30]), ([150, 100], [170, 125]), ([10, -7], [10, 130])];
+    for (from, to) in cases {
+        let mut big = vec![0u8; 400 * 400 * 3];
+        let mut canvas = RgbCanvas::new(&mut big, 400, 400);
+        let shift = |[x, y]: [i32; 2]| [x + 100, y + 100];
+        canvas.line(shift(from), shift(to), [255, 255, 255], 1);
+        let expected: Vec<[i32; 2]> = big
+            .chunks_exact(3)
+            .enumerate()
+            .filter(|(_, pixel)| pixel != &[0, 0, 0])
+            .map(|(i, _)| [i as i32 % 400 - 100, i as i32 / 400 - 100])
+            .filter(|&[x, y]| x >= 0 && y >= 0 && x < WIDTH as i32 && y < HEIGHT as i32)
+            .collect();
+
+        let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+        RgbCanvas::new(&mut rgb, WIDTH, HEIGHT).line(from, to, [255, 255, 255], 1);
+        let lit = lit(&rgb);
+        assert_eq!(lit.len(), expected.len(), "{:?} -> {:?}", from, to);
+        for [x, y] in lit {
+            let near = expected.iter().any(|&[ex, ey]| (ex - x).abs() <= 1 && (ey - y).abs() <= 1);
+            assert!(near, "{:?} -> {:?}: [{}, {}] is off the line", from, to, x, y);
+        }
+    }
+}
+
+#[test]
+fn huge_shapes_do_not_overflow() {
+    let white = [255, 255, 255];
+    let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+    let mut canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT);
+    canvas.circle([i32::MAX, i32::MIN], u32::MAX, white);
+    canvas.circle([-100_000, 50], 50_000, white);
+    canvas.rectangle([i32::MIN, i32::MIN], [i32::MAX, i32::MAX], white, 1);
+    canvas.rectangle([i32::MAX - 2, 0], [i32::MAX, 10], white, 3);
+    canvas.text([i32::MAX - 3, i32::MAX], "far away", white);
+    canvas.text([i32::MIN, 0], "far away", white);
+    assert!(lit(&rgb).is_empty());
+
+    canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT);
+    canvas.circle([0, 0], u32::MAX, white);
+    assert_eq!(lit(&rgb).len(), (WIDTH * HEIGHT) as usize);
+
+    let mut rgb = vec![0u8; WIDTH as usize * HEIGHT as usize * 3];
+    let mut canvas = RgbCanvas::new(&mut rgb, WIDTH, HEIGHT);
+    canvas.rectangle([i32::MIN, i32::MIN], [i32::MAX, i32::MAX], white, u32::MAX);
+    assert_eq!(lit(&rgb).len(), (WIDTH * HEIGHT) as usize);
+}
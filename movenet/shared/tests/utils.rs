@@ -0,0 +1,126 @@
+//! The RGB to YUV encoders used to build fixtures, against the decoders.
+
+use std::path::PathBuf;
+
+use shared::format::{Colorimetry, PixelFormat, YuvMatrix, YuvRange};
+use shared::frame::Frame;
+use shared::image;
+use shared::utils::{self, EasyConverter};
+
+// Smooth enough that subsampled chroma loses little, for up to 64x48.
+fn gradient(width: usize, height: usize) -> Vec<u8> {
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .flat_map(|(x, y)| [(x * 3 + 20) as u8, (y * 4 + 10) as u8, (x + y + 60) as u8])
+        .collect()
+}
+
+fn max_diff(a: &[u8], b: &[u8]) -> u8 {
+    assert_eq!(a.len(), b.len());
+    a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap_or(0)
+}
+
+fn colorimetries() -> impl Iterator<Item = Colorimetry> {
+    [YuvMatrix::Bt601, YuvMatrix::Bt709, YuvMatrix::Bt2020].into_iter().flat_map(|matrix| {
+        [YuvRange::Limited, YuvRange::Full].map(|range| Colorimetry::new(matrix, range))
+    })
+}
+
+#[test]
+fn encoders_round_trip() {
+    let sizes = [(2, 2), (64, 48), (7, 5), (33, 18), (18, 33), (1, 1)];
+    let formats = [PixelFormat::Yuyv, PixelFormat::Nv12, PixelFormat::I420, PixelFormat::Rgb24];
+    for colorimetry in colorimetries() {
+        let converter = EasyConverter::with_colorimetry(colorimetry);
+        for (width, height) in sizes {
+            let rgb = gradient(width, height);
+            for format in formats {
+                if format == PixelFormat::Yuyv && width % 2 == 1 {
+                    continue;
+                }
+                let case = format!("{}x{} {}, {:?}", width, height, format, colorimetry);
+                let encoded = converter.rgb_to_format(&rgb, width, height, format).unwrap();
+                assert_eq!(Some(encoded.len()), format.frame_len(width as u32, height as u32));
+                let decoded = converter.rgb_from_format(&encoded, width, height, format).unwrap();
+                let diff = max_diff(&decoded, &rgb);
+                // Limited range and averaged chroma cost a few levels.
+                assert!(diff <= 6, "{}: max difference {}", case, diff);
+            }
+        }
+    }
+}
+
+#[test]
+fn flat_colors_survive_subsampling() {
+    let colors = [[0, 0, 0], [255, 255, 255], [200, 30, 90], [10, 240, 120], [128, 128, 128]];
+    for colorimetry in colorimetries() {
+        let converter = EasyConverter::with_colorimetry(colorimetry);
+        for color in colors {
+            for (width, height) in [(4, 4), (5, 3)] {
+                let rgb = color.repeat(width * height);
+                for format in [PixelFormat::Nv12, PixelFormat::I420] {
+                    let encoded = converter.rgb_to_format(&rgb, width, height, format).unwrap();
+                    let decoded =
+                        converter.rgb_from_format(&encoded, width, height, format).unwrap();
+                    let diff = max_diff(&decoded, &rgb);
+                    assert!(diff <= 2, "{:?} as {}, {:?}: {}", color, format, colorimetry, diff);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn grey_round_trips_exactly() {
+    let grey: Vec<u8> = (0..=255).collect();
+    let rgb: Vec<u8> = grey.iter().flat_map(|&y| [y, y, y]).collect();
+    for colorimetry in colorimetries() {
+        let converter = EasyConverter::with_colorimetry(colorimetry);
+        assert_eq!(converter.grey_from_rgb(&rgb), grey, "{:?}", colorimetry);
+        assert_eq!(converter.rgb_from_grey(&grey), rgb, "{:?}", colorimetry);
+    }
+}
+
+#[test]
+fn encode_keeps_metadata_and_rejects_odd_yuyv() {
+    let converter = EasyConverter::new(YuvMatrix::Bt709, YuvRange::Full);
+    let rgb = Frame::new(gradient(6, 4), 6, 4, PixelFormat::Rgb24)
+        .unwrap()
+        .with_timestamp(1234)
+        .with_sequence(7);
+    let nv12 = converter.encode(&rgb, PixelFormat::Nv12).unwrap();
+    assert_eq!((nv12.format(), nv12.size()), (PixelFormat::Nv12, [6, 4]));
+    assert_eq!(nv12.colorimetry(), converter.colorimetry());
+    assert_eq!((nv12.timestamp(), nv12.sequence()), (1234, 7));
+    assert!(converter.encode(&rgb, PixelFormat::Mjpeg).is_err());
+
+    let odd = Frame::new(gradient(5, 4), 5, 4, PixelFormat::Rgb24).unwrap();
+    assert!(converter.encode(&odd, PixelFormat::Yuyv).is_err());
+}
+
+#[test]
+fn load_yuyv_reads_the_sample_image() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../resource/pose.jpg");
+    let colorimetry = Colorimetry::new(YuvMatrix::Bt709, YuvRange::Limited);
+    let yuyv = utils::load_yuyv(&path, colorimetry).unwrap();
+    let rgb = image::read(&path).unwrap();
+
+    let [width, height] = rgb.size();
+    assert_eq!(yuyv.size(), [width & !1, height]);
+    assert_eq!(yuyv.format(), PixelFormat::Yuyv);
+    assert_eq!(yuyv.colorimetry(), colorimetry);
+    assert_eq!(Some(yuyv.data().len()), PixelFormat::Yuyv.frame_len(width & !1, height));
+
+    // Back to RGB, the image is what was read, give or take the chroma of edges.
+    let decoded = yuyv.to_rgb().unwrap();
+    let even = (width & !1) as usize * 3;
+    let diffs: Vec<u8> = rgb.data()
+        .chunks_exact(width as usize * 3)
+        .zip(decoded.data().chunks_exact(even))
+        .flat_map(|(a, b)| a[..even].iter().zip(b).map(|(a, b)| a.abs_diff(*b)))
+        .collect();
+    let mean = diffs.iter().map(|&d| d as f64).sum::<f64>() / diffs.len() as f64;
+    assert!(mean < 2.0, "mean difference {:.3}", mean);
+
+    assert!(utils::load_yuyv(path.with_extension("missing"), colorimetry).is_err());
+}